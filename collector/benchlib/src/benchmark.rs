@@ -1,12 +1,53 @@
 use crate::cli::{parse_cli, Args, BenchmarkArgs, ProfileArgs};
-use crate::comm::messages::{BenchmarkMessage, BenchmarkResult, BenchmarkStats};
+use crate::comm::messages::{BenchmarkMessage, BenchmarkResult, BenchmarkStats, PROTOCOL_VERSION};
 use crate::comm::output_message;
-use crate::measure::benchmark_function;
+use crate::measure::{benchmark_function, Metrics};
 use crate::process::raise_process_priority;
 use crate::profile::profile_function;
-use std::collections::HashMap;
+use std::cell::Cell;
+use std::collections::{HashMap, HashSet};
 use std::rc::Rc;
 
+/// Environment variable used by `collector` to pass the input size to benchmarks that are
+/// parameterized by it.
+pub const INPUT_SIZE_ENV: &str = "RUSTC_PERF_INPUT_SIZE";
+
+/// Returns the input size requested by `collector`, if any.
+/// Benchmarks that are parameterized by an input size can use it to scale their input data.
+pub fn input_size() -> Option<u64> {
+    std::env::var(INPUT_SIZE_ENV).ok()?.parse().ok()
+}
+
+/// Environment variable used by `collector` to pass a seed for random number generators to
+/// benchmarks.
+pub const SEED_ENV: &str = "RUSTC_PERF_SEED";
+
+/// Returns the RNG seed requested by `collector`, if any.
+/// Benchmarks that use randomness should use it to make their results reproducible.
+pub fn seed() -> Option<u64> {
+    std::env::var(SEED_ENV).ok()?.parse().ok()
+}
+
+/// Environment variable set by `collector` when it lists the benchmarks of a group. If it is set,
+/// the `list` command prints a JSON object with the `benchmarks` and the `protocol_version`
+/// instead of a JSON array, so that the version is known without executing the binary again.
+pub const LIST_PROTOCOL_VERSION_ENV: &str = "RUSTC_PERF_LIST_PROTOCOL_VERSION";
+
+/// Environment variable used by `collector` to pass the metrics that should be measured, as a
+/// JSON object that maps benchmark names to lists of metric names. Benchmarks that are not listed
+/// only measure wall time. If the variable is not set, all metrics are measured.
+pub const METRICS_ENV: &str = "RUSTC_PERF_METRICS";
+
+/// Returns the metrics requested by `collector` for each benchmark, if it has restricted them.
+pub fn requested_metrics() -> anyhow::Result<Option<HashMap<String, HashSet<String>>>> {
+    let Ok(metrics) = std::env::var(METRICS_ENV) else {
+        return Ok(None);
+    };
+    let metrics = serde_json::from_str(&metrics)
+        .map_err(|error| anyhow::anyhow!("Cannot parse {METRICS_ENV} `{metrics}`: {error:?}"))?;
+    Ok(Some(metrics))
+}
+
 /// Create and run a new benchmark group. Use the closure argument to register
 /// the individual benchmarks.
 pub fn run_benchmark_group<'a, F>(register: F)
@@ -22,7 +63,7 @@ where
 
 /// Type-erased function that executes a single benchmark and measures counter and wall-time
 /// metrics.
-type BenchmarkFn<'a> = Box<dyn Fn() -> anyhow::Result<BenchmarkStats> + 'a>;
+type BenchmarkFn<'a> = Box<dyn Fn(&Metrics) -> anyhow::Result<BenchmarkStats> + 'a>;
 
 /// Type-erased function that executes a single benchmark once.
 type ProfileFn<'a> = Box<dyn Fn() + 'a>;
@@ -30,6 +71,8 @@ type ProfileFn<'a> = Box<dyn Fn() + 'a>;
 struct BenchmarkProfileFns<'a> {
     benchmark_fn: BenchmarkFn<'a>,
     profile_fn: ProfileFn<'a>,
+    /// Checksum reported by the last execution of the benchmark, if it reports a checksum.
+    checksum: Option<Rc<Cell<Option<u64>>>>,
 }
 
 #[derive(Default)]
@@ -54,9 +97,44 @@ impl<'a> BenchmarkGroup<'a> {
         let constructor = Rc::new(constructor);
         let constructor2 = constructor.clone();
         let benchmark_fns = BenchmarkProfileFns {
-            benchmark_fn: Box::new(move || benchmark_function(constructor.as_ref())),
+            benchmark_fn: Box::new(move |metrics| {
+                benchmark_function(constructor.as_ref(), metrics)
+            }),
             profile_fn: Box::new(move || profile_function(constructor2.as_ref())),
+            checksum: None,
         };
+        self.insert(name, benchmark_fns);
+    }
+
+    /// Registers a single benchmark whose closure returns a checksum of the work that it has
+    /// performed (e.g. a hash of the computed result).
+    ///
+    /// The checksum is reported to `collector`, which can use it to check that the benchmark
+    /// behaves the same when compiled by different toolchains. It should thus be computed in a way
+    /// that does not depend on the toolchain (e.g. not with `DefaultHasher`).
+    pub fn register_benchmark_with_checksum<Ctor, Bench>(
+        &mut self,
+        name: &'static str,
+        constructor: Ctor,
+    ) where
+        Ctor: Fn() -> Bench + 'a,
+        Bench: FnOnce() -> u64,
+    {
+        let checksum = Rc::new(Cell::new(None));
+        let checksum2 = checksum.clone();
+        self.register_benchmark(name, move || {
+            let func = constructor();
+            let checksum = checksum2.clone();
+            move || {
+                let value = func();
+                checksum.set(Some(value));
+                value
+            }
+        });
+        self.benchmarks.get_mut(name).unwrap().checksum = Some(checksum);
+    }
+
+    fn insert(&mut self, name: &'static str, benchmark_fns: BenchmarkProfileFns<'a>) {
         if self.benchmarks.insert(name, benchmark_fns).is_some() {
             panic!("Benchmark '{}' was registered twice", name);
         }
@@ -74,6 +152,7 @@ impl<'a> BenchmarkGroup<'a> {
             }
             Args::Profile(args) => self.profile_benchmark(args)?,
             Args::List => self.list_benchmarks()?,
+            Args::ProtocolVersion => println!("{PROTOCOL_VERSION}"),
         }
 
         Ok(())
@@ -87,19 +166,26 @@ impl<'a> BenchmarkGroup<'a> {
             .collect();
         items.sort_unstable_by_key(|item| item.0);
 
+        let requested_metrics = requested_metrics()?;
         let mut stdout = std::io::stdout().lock();
 
         for (name, benchmark_fns) in items {
+            let metrics = match requested_metrics {
+                Some(ref requested) => {
+                    Metrics::Only(requested.get(name).cloned().unwrap_or_default())
+                }
+                None => Metrics::All,
+            };
             let mut stats: Vec<BenchmarkStats> = Vec::with_capacity(args.iterations as usize);
             // Warm-up
             for _ in 0..3 {
-                let benchmark_stats = (benchmark_fns.benchmark_fn)()?;
+                let benchmark_stats = (benchmark_fns.benchmark_fn)(&metrics)?;
                 black_box(benchmark_stats);
             }
 
             // Actual measurement
             for i in 0..args.iterations {
-                let benchmark_stats = (benchmark_fns.benchmark_fn)()?;
+                let benchmark_stats = (benchmark_fns.benchmark_fn)(&metrics)?;
                 log::info!("Benchmark (run {i}) `{name}` completed: {benchmark_stats:?}");
                 stats.push(benchmark_stats);
             }
@@ -108,6 +194,10 @@ impl<'a> BenchmarkGroup<'a> {
                 BenchmarkMessage::Result(BenchmarkResult {
                     name: name.to_string(),
                     stats,
+                    checksum: benchmark_fns
+                        .checksum
+                        .as_ref()
+                        .and_then(|checksum| checksum.get()),
                 }),
             )?;
         }
@@ -134,7 +224,17 @@ impl<'a> BenchmarkGroup<'a> {
 
     fn list_benchmarks(self) -> anyhow::Result<()> {
         let benchmark_list: Vec<&str> = self.benchmarks.into_keys().collect();
-        serde_json::to_writer(std::io::stdout(), &benchmark_list)?;
+        if std::env::var_os(LIST_PROTOCOL_VERSION_ENV).is_some() {
+            serde_json::to_writer(
+                std::io::stdout(),
+                &serde_json::json!({
+                    "benchmarks": benchmark_list,
+                    "protocol_version": PROTOCOL_VERSION,
+                }),
+            )?;
+        } else {
+            serde_json::to_writer(std::io::stdout(), &benchmark_list)?;
+        }
 
         Ok(())
     }
@@ -8,6 +8,8 @@ pub enum Args {
     Profile(ProfileArgs),
     /// List benchmarks that are defined in the current group as a JSON array.
     List,
+    /// Print the version of the protocol that is used to communicate with the collector.
+    ProtocolVersion,
 }
 
 #[derive(clap::Parser, Debug)]
@@ -3,22 +3,30 @@
 
 use std::time::Duration;
 
+/// Version of the protocol used by benchmark binaries to communicate with `collector`. It should
+/// be increased whenever the messages or the CLI of the binaries change incompatibly.
+pub const PROTOCOL_VERSION: u32 = 1;
+
 #[derive(Debug, serde::Serialize, serde::Deserialize)]
 pub enum BenchmarkMessage {
     Result(BenchmarkResult),
 }
 
 /// Stats gathered by several iterations of a single benchmark.
-#[derive(Debug, serde::Serialize, serde::Deserialize)]
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
 pub struct BenchmarkResult {
     pub name: String,
     pub stats: Vec<BenchmarkStats>,
+    /// Checksum of the work performed by the benchmark, if the benchmark reports it.
+    /// It should not depend on the toolchain used to compile the benchmark.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub checksum: Option<u64>,
 }
 
 /// The stats gathered by a single benchmark execution.
 /// Some of the perf. counters may be missing if the machine that executes the benchmark is unable
 /// to gather them.
-#[derive(Debug, serde::Serialize, serde::Deserialize)]
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
 pub struct BenchmarkStats {
     pub cycles: Option<u64>,
     pub instructions: Option<u64>,
@@ -26,4 +34,7 @@ pub struct BenchmarkStats {
     pub cache_misses: Option<u64>,
     pub cache_references: Option<u64>,
     pub wall_time: Duration,
+    /// Peak resident set size of the benchmark process during the execution, in bytes.
+    #[serde(default)]
+    pub max_rss: Option<u64>,
 }
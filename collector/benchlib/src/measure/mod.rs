@@ -1,3 +1,25 @@
 pub mod perf_counter;
 
 pub use perf_counter::benchmark_function;
+
+use std::collections::HashSet;
+
+/// Metrics that should be measured for a single benchmark, identified by their names in the
+/// (kebab-case) form used by `collector`. Wall time is always measured.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub enum Metrics {
+    /// Measure all metrics.
+    #[default]
+    All,
+    /// Measure only the listed metrics.
+    Only(HashSet<String>),
+}
+
+impl Metrics {
+    pub fn contains(&self, metric: &str) -> bool {
+        match self {
+            Metrics::All => true,
+            Metrics::Only(metrics) => metrics.contains(metric),
+        }
+    }
+}
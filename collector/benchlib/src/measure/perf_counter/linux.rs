@@ -1,65 +1,126 @@
 use crate::benchmark::black_box;
 use crate::comm::messages::BenchmarkStats;
+use crate::measure::Metrics;
 use perf_event::events::Hardware;
 use perf_event::{Builder, Counter, Group};
 use std::time::Instant;
 
-/// A collection of CPU performance counters.
-/// The counters are optional, because some CPUs are not able to record them.
-struct Counters {
-    cycles: Option<Counter>,
-    instructions: Option<Counter>,
-    branch_misses: Option<Counter>,
-    cache_misses: Option<Counter>,
-    cache_references: Option<Counter>,
+/// A collection of CPU performance counters, or of their measured values.
+/// The counters are optional, because some CPUs are not able to record them, and because they are
+/// only measured when requested.
+#[derive(Default)]
+struct Counters<T = Counter> {
+    cycles: Option<T>,
+    instructions: Option<T>,
+    branch_misses: Option<T>,
+    cache_misses: Option<T>,
+    cache_references: Option<T>,
 }
 
+/// Names of the metrics measured by CPU performance counters, with their events.
+const COUNTERS: [(&str, Hardware); 5] = [
+    ("cycles", Hardware::CPU_CYCLES),
+    ("instructions", Hardware::INSTRUCTIONS),
+    ("branch-misses", Hardware::BRANCH_MISSES),
+    ("cache-misses", Hardware::CACHE_MISSES),
+    ("cache-references", Hardware::CACHE_REFERENCES),
+];
+
 /// Benchmarks a single function generated by `benchmark_constructor`.
 /// The function is executed twice, once to gather wall-time measurement and the second time to
 /// gather perf. counters.
+///
+/// Only the counters contained in `metrics` are measured. If there are none, the first
+/// execution is skipped.
 pub fn benchmark_function<F: Fn() -> Bench, R, Bench: FnOnce() -> R>(
     benchmark_constructor: &F,
+    metrics: &Metrics,
 ) -> anyhow::Result<BenchmarkStats> {
-    let mut group = create_group()?;
-    let counters = prepare_counters(&mut group)?;
+    let mut counters = Counters::<u64>::default();
+    if COUNTERS.iter().any(|(name, _)| metrics.contains(name)) {
+        let mut group = create_group()?;
+        let prepared = prepare_counters(&mut group, metrics)?;
 
-    // Measure perf. counters.
-    let func = benchmark_constructor();
+        // Measure perf. counters.
+        let func = benchmark_constructor();
 
-    // Do not act on the return value to avoid including the branch in the measurement
-    let enable_ret = group.enable();
-    let output = func();
-    group.disable()?;
+        // Do not act on the return value to avoid including the branch in the measurement
+        let enable_ret = group.enable();
+        let output = func();
+        group.disable()?;
 
-    // Try to avoid optimizing the result out.
-    black_box(output);
+        // Try to avoid optimizing the result out.
+        black_box(output);
 
-    // Check if we have succeeded before
-    enable_ret?;
+        // Check if we have succeeded before
+        enable_ret?;
 
-    let measurement = group.read()?;
+        let measurement = group.read()?;
+        let read = |counter: Option<Counter>| counter.map(|c| measurement[&c]);
+        counters = Counters {
+            cycles: read(prepared.cycles),
+            instructions: read(prepared.instructions),
+            branch_misses: read(prepared.branch_misses),
+            cache_misses: read(prepared.cache_misses),
+            cache_references: read(prepared.cache_references),
+        };
+    }
 
     // Measure wall time.
     let func = benchmark_constructor();
 
+    // The peak RSS is a high-water mark of the whole process, so it has to be reset to the
+    // current RSS, otherwise it would include previously executed benchmarks.
+    let measure_rss = metrics.contains("max-rss");
+    let rss_reset = measure_rss.then(reset_peak_rss);
+
     let start = Instant::now();
     let output = func();
     let duration = start.elapsed();
 
+    let max_rss = match rss_reset {
+        Some(Ok(())) => read_peak_rss(),
+        Some(Err(error)) => {
+            log::warn!("Could not reset peak RSS: {error:?}");
+            None
+        }
+        None => None,
+    };
+
     // Try to avoid optimizing the result out.
     black_box(output);
 
     let result = BenchmarkStats {
-        cycles: counters.cycles.map(|c| measurement[&c]),
-        instructions: counters.instructions.map(|c| measurement[&c]),
-        branch_misses: counters.branch_misses.map(|c| measurement[&c]),
-        cache_misses: counters.cache_misses.map(|c| measurement[&c]),
-        cache_references: counters.cache_references.map(|c| measurement[&c]),
+        cycles: counters.cycles,
+        instructions: counters.instructions,
+        branch_misses: counters.branch_misses,
+        cache_misses: counters.cache_misses,
+        cache_references: counters.cache_references,
         wall_time: duration,
+        max_rss,
     };
     Ok(result)
 }
 
+/// Resets the peak RSS (`VmHWM`) of the current process to its current RSS.
+fn reset_peak_rss() -> std::io::Result<()> {
+    std::fs::write("/proc/self/clear_refs", "5")
+}
+
+/// Reads the peak RSS of the current process, in bytes.
+fn read_peak_rss() -> Option<u64> {
+    let status = std::fs::read_to_string("/proc/self/status").ok()?;
+    let line = status.lines().find(|line| line.starts_with("VmHWM:"))?;
+    let kib: u64 = line
+        .trim_start_matches("VmHWM:")
+        .trim()
+        .trim_end_matches("kB")
+        .trim()
+        .parse()
+        .ok()?;
+    Some(kib * 1024)
+}
+
 fn create_group() -> anyhow::Result<Group> {
     match Group::new() {
         Ok(group) => Ok(group),
@@ -78,24 +139,26 @@ Try lowering it with `sudo bash -c 'echo -1 > /proc/sys/kernel/perf_event_parano
     }
 }
 
-fn prepare_counters(group: &mut Group) -> anyhow::Result<Counters> {
-    let mut add_event = |event: Hardware| match Builder::new().group(group).kind(event).build() {
-        Ok(counter) => Some(counter),
-        Err(error) => {
-            log::warn!(
-                "Could not add counter {:?}: {:?}. Maybe the CPU doesn't support it?",
-                event,
-                error
-            );
-            None
+fn prepare_counters(group: &mut Group, metrics: &Metrics) -> anyhow::Result<Counters> {
+    let add_event = |(name, event): (&str, Hardware)| {
+        if !metrics.contains(name) {
+            return None;
+        }
+        match Builder::new().group(group).kind(event).build() {
+            Ok(counter) => Some(counter),
+            Err(error) => {
+                log::warn!(
+                    "Could not add counter {:?}: {:?}. Maybe the CPU doesn't support it?",
+                    event,
+                    error
+                );
+                None
+            }
         }
     };
 
-    let cycles = add_event(Hardware::CPU_CYCLES);
-    let instructions = add_event(Hardware::INSTRUCTIONS);
-    let branch_misses = add_event(Hardware::BRANCH_MISSES);
-    let cache_misses = add_event(Hardware::CACHE_MISSES);
-    let cache_references = add_event(Hardware::CACHE_REFERENCES);
+    let [cycles, instructions, branch_misses, cache_misses, cache_references] =
+        COUNTERS.map(add_event);
 
     Ok(Counters {
         cycles,
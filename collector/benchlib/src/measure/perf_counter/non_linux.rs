@@ -1,5 +1,9 @@
 use crate::comm::messages::BenchmarkStats;
+use crate::measure::Metrics;
 
-pub fn benchmark_function<F: FnOnce() -> R, R>(_func: F) -> anyhow::Result<BenchmarkStats> {
+pub fn benchmark_function<F: FnOnce() -> R, R>(
+    _func: F,
+    _metrics: &Metrics,
+) -> anyhow::Result<BenchmarkStats> {
     panic!("Runtime benchmarking is only supported on Linux");
 }
@@ -10,7 +10,8 @@ use std::sync::OnceLock;
 use anyhow::Context;
 use cargo_metadata::Artifact;
 use object::read::archive::ArchiveFile;
-use object::{Object, ObjectSection, ObjectSymbol};
+use object::read::elf::{Dyn, FileHeader, SectionHeader};
+use object::{Endianness, FileKind, Object, ObjectSection, ObjectSymbol};
 use regex::Regex;
 
 use crate::cargo::CargoArtifactIter;
@@ -165,6 +166,44 @@ fn normalize_section_name(section: &str) -> String {
     subsections.into_iter().collect::<Vec<_>>().join(".")
 }
 
+/// Returns the names of shared libraries that the given ELF binary depends on (its `DT_NEEDED`
+/// entries). An empty list means that the binary is statically linked.
+pub fn binary_dynamic_deps(binary: &Path) -> anyhow::Result<Vec<String>> {
+    fn needed_libraries<Elf: FileHeader<Endian = Endianness>>(
+        data: &[u8],
+    ) -> anyhow::Result<Vec<String>> {
+        let header = Elf::parse(data)?;
+        let endian = header.endian()?;
+        let sections = header.sections(endian, data)?;
+
+        let mut libraries = Vec::new();
+        for section in sections.iter() {
+            let Some((entries, link)) = section.dynamic(endian, data)? else {
+                continue;
+            };
+            let strings = sections.strings(endian, data, link)?;
+            for entry in entries {
+                if entry.tag32(endian) == Some(object::elf::DT_NEEDED) {
+                    let name = entry.string(endian, strings)?;
+                    libraries.push(String::from_utf8_lossy(name).into_owned());
+                }
+            }
+        }
+        Ok(libraries)
+    }
+
+    let data = std::fs::read(binary)
+        .with_context(|| format!("Cannot read executable file {}", binary.display()))?;
+    match FileKind::parse(&*data)? {
+        FileKind::Elf32 => needed_libraries::<object::elf::FileHeader32<Endianness>>(&data),
+        FileKind::Elf64 => needed_libraries::<object::elf::FileHeader64<Endianness>>(&data),
+        kind => Err(anyhow::anyhow!(
+            "`{}` is not an ELF binary ({kind:?})",
+            binary.display()
+        )),
+    }
+}
+
 #[derive(Debug, Copy, Clone)]
 pub enum CargoProfile {
     Debug,
@@ -263,6 +302,21 @@ mod tests {
     use super::*;
     use rustc_demangle::demangle;
 
+    #[cfg(all(target_os = "linux", target_env = "gnu"))]
+    #[test]
+    fn dynamic_deps_of_test_binary() {
+        // Test binaries are linked dynamically to glibc.
+        let deps = binary_dynamic_deps(&std::env::current_exe().unwrap()).unwrap();
+        assert!(deps.iter().any(|dep| dep.starts_with("libc.so")));
+    }
+
+    #[test]
+    fn dynamic_deps_of_non_elf_file() {
+        let file = tempfile::NamedTempFile::new().unwrap();
+        std::fs::write(file.path(), b"not an executable").unwrap();
+        assert!(binary_dynamic_deps(file.path()).is_err());
+    }
+
     #[test]
     fn normalize_remove_cold_annotation() {
         // Check that .cold at the end is removed
@@ -47,9 +47,14 @@ use collector::compile::execute::profiler::{ProfileProcessor, Profiler};
 use collector::runtime::{
     bench_runtime, get_runtime_benchmark_groups, prepare_runtime_benchmark_suite,
     runtime_benchmark_dir, BenchmarkSuite, BenchmarkSuiteCompilation, CargoIsolationMode,
-    RuntimeBenchmarkFilter, RuntimeProfiler, DEFAULT_RUNTIME_ITERATIONS,
+    GroupSelection, RuntimeBenchmarkFilter, RuntimeProfiler, RuntimeRunOpts,
+    DEFAULT_RUNTIME_ITERATIONS,
+};
+use collector::runtime::{
+    print_effective_config, profile_runtime, reproducibility_manifest, CgroupConfig,
+    CompileOverrides, FilenameTemplate, FilterSyntax, IoPriorityClass, RunConfig, RunConfigLayer,
+    RunUntil, RuntimeCompilationOpts, StdioMode, StreamAddress,
 };
-use collector::runtime::{profile_runtime, RuntimeCompilationOpts};
 use collector::toolchain::{
     create_toolchain_from_published_version, get_local_toolchain, Sysroot, Toolchain,
     ToolchainConfig,
@@ -108,14 +113,21 @@ struct RuntimeBenchmarkConfig {
     runtime_suite: BenchmarkSuite,
     filter: RuntimeBenchmarkFilter,
     iterations: u32,
+    opts: RuntimeRunOpts,
 }
 
 impl RuntimeBenchmarkConfig {
-    fn new(suite: BenchmarkSuite, filter: RuntimeBenchmarkFilter, iterations: u32) -> Self {
+    fn new(
+        suite: BenchmarkSuite,
+        filter: RuntimeBenchmarkFilter,
+        iterations: u32,
+        opts: RuntimeRunOpts,
+    ) -> Self {
         Self {
             runtime_suite: suite.filter(&filter),
             filter,
             iterations,
+            opts,
         }
     }
 }
@@ -292,6 +304,16 @@ impl<T: clap::ValueEnum + Sync + Send + 'static> TypedValueParser for EnumArgPar
     }
 }
 
+/// Parses a `<GROUP>=<PATH>` argument of `--group-stdin-file`.
+fn parse_group_stdin_file(value: &str) -> Result<(String, PathBuf), String> {
+    match value.split_once('=') {
+        Some((group, path)) if !group.is_empty() && !path.is_empty() => {
+            Ok((group.to_string(), PathBuf::from(path)))
+        }
+        _ => Err(format!("expected `<GROUP>=<PATH>`, got `{value}`")),
+    }
+}
+
 #[derive(Debug, clap::Parser)]
 #[command(about, version, author)]
 struct Cli {
@@ -396,6 +418,56 @@ struct RuntimeOptions {
     group: Option<String>,
 }
 
+#[derive(Debug, clap::Args)]
+struct RuntimeCompileOptions {
+    /// Compile the runtime benchmarks with this `opt-level`.
+    #[arg(long)]
+    opt_level: Option<String>,
+
+    /// Compile the runtime benchmarks with this `lto` setting (`off`, `thin` or `fat`).
+    #[arg(long)]
+    lto: Option<String>,
+
+    /// Compile the runtime benchmarks with this number of codegen units.
+    #[arg(long)]
+    codegen_units: Option<u32>,
+
+    /// Compile the runtime benchmarks with this panic strategy (`unwind` or `abort`).
+    #[arg(long)]
+    panic: Option<String>,
+
+    /// Enable or disable overflow checks in the runtime benchmarks.
+    #[arg(long)]
+    overflow_checks: Option<bool>,
+
+    /// Compile the runtime benchmarks with this `split-debuginfo` setting.
+    #[arg(long)]
+    split_debuginfo: Option<String>,
+
+    /// Cargo profile with which the runtime benchmarks are compiled. Defaults to `release`.
+    #[arg(long)]
+    cargo_profile: Option<String>,
+
+    /// Compile the runtime benchmarks for this target triple.
+    #[arg(long)]
+    target: Option<String>,
+}
+
+impl From<RuntimeCompileOptions> for CompileOverrides {
+    fn from(options: RuntimeCompileOptions) -> Self {
+        Self {
+            opt_level: options.opt_level,
+            lto: options.lto,
+            codegen_units: options.codegen_units,
+            panic: options.panic,
+            overflow_checks: options.overflow_checks,
+            split_debuginfo: options.split_debuginfo,
+            cargo_profile: options.cargo_profile,
+            target: options.target,
+        }
+    }
+}
+
 #[derive(Debug, clap::Args)]
 struct SelfProfileOption {
     /// Collect self-profile data
@@ -503,9 +575,178 @@ enum Commands {
         #[command(flatten)]
         runtime: RuntimeOptions,
 
-        /// How many iterations of each benchmark should be executed.
-        #[arg(long, default_value_t = DEFAULT_RUNTIME_ITERATIONS)]
-        iterations: u32,
+        #[command(flatten)]
+        compile: RuntimeCompileOptions,
+
+        #[arg(
+            long,
+            help = format!(
+                "How many iterations of each benchmark should be executed \
+[default: {DEFAULT_RUNTIME_ITERATIONS}]"
+            )
+        )]
+        iterations: Option<u32>,
+
+        /// Read further options from this TOML file, e.g. `iterations = 10`. Options given on the
+        /// command line take precedence over the file.
+        #[arg(long)]
+        run_config: Option<PathBuf>,
+
+        /// Execute each benchmark once per input size in this comma-separated list.
+        /// The size is passed to benchmarks in the `RUSTC_PERF_INPUT_SIZE` environment variable.
+        #[arg(long, value_delimiter = ',')]
+        input_sizes: Vec<u64>,
+
+        /// Pin the benchmark processes to the given CPU core (Linux only).
+        #[arg(long)]
+        pin_core: Option<usize>,
+
+        /// Disable transparent huge pages for the benchmark processes (Linux only).
+        #[arg(long)]
+        disable_thp: bool,
+
+        /// Re-execute the benchmark process of each group until the mean wall time of each
+        /// benchmark changes by less than this relative tolerance (e.g. `0.01`) between two
+        /// executions, or until `--max-runs` is reached.
+        #[arg(long)]
+        until_converged: Option<f64>,
+
+        /// Maximum number of executions of each benchmark process with `--until-converged`.
+        #[arg(long, default_value_t = 10)]
+        max_runs: u32,
+
+        /// Execute the benchmark processes with this niceness (Unix only).
+        #[arg(long, allow_hyphen_values = true)]
+        nice: Option<i32>,
+
+        /// Execute the benchmark processes in this I/O scheduling class (Linux only).
+        #[arg(long, value_enum)]
+        io_priority: Option<IoPriorityClass>,
+
+        /// What to do with the stderr of the benchmark processes.
+        #[arg(long, value_enum, default_value = "capture")]
+        run_stderr: StdioMode,
+
+        /// Refuse to execute benchmarks while the system load average exceeds this value.
+        #[arg(long)]
+        max_load_average: Option<f64>,
+
+        /// How many seconds to wait for the system load to drop below `--max-load-average`
+        /// before giving up.
+        #[arg(long, default_value_t = 0)]
+        max_load_wait: u64,
+
+        /// Write a JUnit XML report with the outcome of each benchmark to this path.
+        #[arg(long)]
+        junit: Option<PathBuf>,
+
+        /// Write the results in the Prometheus text exposition format to this path.
+        #[arg(long)]
+        prometheus: Option<PathBuf>,
+
+        /// Write the report of the run as JSON to a file named by this template, e.g.
+        /// `{toolchain}-{date}.json`. Supported placeholders are `{toolchain}`, `{commit}`,
+        /// `{date}` and `{seed}`.
+        #[arg(long)]
+        results_file: Option<String>,
+
+        /// Send each result as a JSON line to this socket as soon as it is available, either
+        /// `unix:<path>` or `<host>:<port>`.
+        #[arg(long)]
+        stream_results: Option<String>,
+
+        /// Connect the standard input of the benchmark processes to this file.
+        #[arg(long)]
+        stdin_file: Option<PathBuf>,
+
+        /// Connect the standard input of the benchmark process of a group to a file, overriding
+        /// `--stdin-file`, as `<GROUP>=<PATH>`. Can be repeated.
+        #[arg(long = "group-stdin-file", value_parser = parse_group_stdin_file)]
+        group_stdin_files: Vec<(String, PathBuf)>,
+
+        /// Print how long did the discovery, compilation and execution of the benchmarks take,
+        /// as a JSON object, after the run finishes.
+        #[arg(long)]
+        phase_timings: bool,
+
+        /// Seed for random number generators of the benchmarks, passed to them in the
+        /// `RUSTC_PERF_SEED` environment variable.
+        #[arg(long)]
+        seed: Option<u64>,
+
+        /// Fail benchmark groups in which a benchmark exceeds this peak RSS, in bytes.
+        #[arg(long)]
+        memory_budget: Option<u64>,
+
+        /// Only execute benchmark groups whose tags match this expression, e.g. `simd && !slow`.
+        #[arg(long)]
+        tags: Option<String>,
+
+        /// Only execute benchmark groups whose names match a prefix in this comma-separated list.
+        #[arg(long, value_delimiter = ',')]
+        include_group: Vec<String>,
+
+        /// Skip benchmark groups whose names match a prefix in this comma-separated list.
+        #[arg(long, value_delimiter = ',')]
+        exclude_group: Vec<String>,
+
+        /// Treat the patterns of `--include` and `--exclude` as regular expressions instead of
+        /// prefixes of benchmark names.
+        #[arg(long)]
+        regex: bool,
+
+        /// Treat the patterns of `--include` and `--exclude` as globs (e.g. `sort_*`) matched
+        /// against whole benchmark names instead of prefixes.
+        #[arg(long, conflicts_with = "regex")]
+        glob: bool,
+
+        /// Only execute a random sample of this many benchmarks, chosen from the benchmarks that
+        /// pass the filters.
+        #[arg(long)]
+        sample: Option<usize>,
+
+        /// Seed used to choose the benchmarks for `--sample`.
+        #[arg(long, default_value_t = 0, requires = "sample")]
+        sample_seed: u64,
+
+        /// Stop the run once it takes longer than this number of seconds. The benchmark group that
+        /// is running at that moment is terminated, and no further groups are started.
+        #[arg(long)]
+        total_timeout: Option<u64>,
+
+        /// Identifier of the run (e.g. a CI build id), embedded in the results file, the result
+        /// stream and the database. A random UUID is generated if it is not set.
+        #[arg(long)]
+        run_id: Option<String>,
+
+        /// Command that validates the results after the run, e.g. `./check.sh,--strict`. Its
+        /// arguments are separated by commas, and the path to a JSON file with the results is
+        /// appended to them. The run fails if the command exits unsuccessfully.
+        #[arg(long, value_delimiter = ',')]
+        result_validator: Option<Vec<String>>,
+
+        /// Execute the benchmarks inside this cgroup (v2), given relative to `/sys/fs/cgroup`.
+        /// The cgroup is created if it does not exist. Only supported on Linux.
+        #[arg(long)]
+        cgroup: Option<PathBuf>,
+
+        /// Restrict the cgroup to these CPUs (e.g. `2-3`).
+        #[arg(long, requires = "cgroup")]
+        cgroup_cpus: Option<String>,
+
+        /// Limit the memory of the cgroup to this number of bytes.
+        #[arg(long, requires = "cgroup")]
+        cgroup_memory_max: Option<u64>,
+
+        /// Print the effective configuration of the run as JSON and exit without running
+        /// anything.
+        #[arg(long)]
+        print_config: bool,
+
+        /// Write a manifest with all the inputs of the run (toolchain, benchmark directory commit,
+        /// configuration, environment variables and the benchmark set) as JSON to this path.
+        #[arg(long)]
+        repro_manifest: Option<PathBuf>,
 
         #[command(flatten)]
         db: DbOption,
@@ -752,20 +993,145 @@ fn main_result() -> anyhow::Result<i32> {
         Commands::BenchRuntimeLocal {
             local,
             runtime,
+            compile,
             iterations,
+            run_config,
+            input_sizes,
+            pin_core,
+            disable_thp,
+            until_converged,
+            max_runs,
+            nice,
+            io_priority,
+            run_stderr,
+            max_load_average,
+            max_load_wait,
+            junit,
+            prometheus,
+            results_file,
+            stream_results,
+            stdin_file,
+            group_stdin_files,
+            phase_timings,
+            seed,
+            memory_budget,
+            tags,
+            include_group,
+            exclude_group,
+            regex,
+            glob,
+            sample,
+            sample_seed,
+            total_timeout,
+            run_id,
+            result_validator,
+            cgroup,
+            cgroup_cpus,
+            cgroup_memory_max,
+            print_config,
+            repro_manifest,
             db,
             no_isolate,
             purge,
         } => {
             log_db(&db);
             let toolchain = get_local_toolchain_for_runtime_benchmarks(&local, &target_triple)?;
-            let pool = Pool::open(&db.db);
 
             let isolation_mode = if no_isolate {
                 CargoIsolationMode::Cached
             } else {
                 CargoIsolationMode::Isolated
             };
+            let mut run_opts = RuntimeRunOpts::default();
+            if let Some(core) = pin_core {
+                run_opts = run_opts.pin_core(core);
+            }
+            run_opts = run_opts.disable_thp(disable_thp);
+            if let Some(rel_tolerance) = until_converged {
+                run_opts = run_opts.run_until(RunUntil::Converged {
+                    rel_tolerance,
+                    max_runs,
+                });
+            }
+            if let Some(nice) = nice {
+                run_opts = run_opts.nice(nice);
+            }
+            if let Some(class) = io_priority {
+                run_opts = run_opts.io_priority(class);
+            }
+            run_opts = run_opts.run_stderr(run_stderr);
+            if let Some(max_load) = max_load_average {
+                run_opts = run_opts.max_load_average(max_load, Duration::from_secs(max_load_wait));
+            }
+            if let Some(path) = junit {
+                run_opts = run_opts.junit_report(path);
+            }
+            if let Some(path) = prometheus {
+                run_opts = run_opts.prometheus_report(path);
+            }
+            if let Some(template) = results_file {
+                run_opts = run_opts.results_file(template.parse::<FilenameTemplate>()?);
+            }
+            if let Some(address) = stream_results {
+                run_opts = run_opts.stream_results(address.parse::<StreamAddress>()?);
+            }
+            if let Some(path) = stdin_file {
+                run_opts = run_opts.stdin_file(path);
+            }
+            for (group, path) in group_stdin_files {
+                run_opts = run_opts.group_stdin_file(&group, path);
+            }
+            run_opts = run_opts.print_phase_timings(phase_timings);
+            if let Some(ref run_id) = run_id {
+                run_opts = run_opts.run_id(run_id);
+            }
+            if let Some(validator) = result_validator {
+                run_opts = run_opts.result_validator(validator);
+            }
+            if let Some(path) = cgroup {
+                let mut cgroup = CgroupConfig::new(path);
+                cgroup.cpus = cgroup_cpus;
+                cgroup.memory_max = cgroup_memory_max;
+                run_opts = run_opts.cgroup(cgroup);
+            }
+            let layer = RunConfigLayer {
+                iterations,
+                include: local.include,
+                exclude: local.exclude,
+                filter_syntax: if regex {
+                    Some(FilterSyntax::Regex)
+                } else if glob {
+                    Some(FilterSyntax::Glob)
+                } else {
+                    None
+                },
+                include_group,
+                exclude_group,
+                tags,
+                input_sizes,
+                seed,
+                memory_budget,
+                total_timeout,
+                compile: compile.into(),
+            };
+            let layer = match run_config {
+                Some(path) => layer.or(RunConfigLayer::load(&path)?),
+                None => layer,
+            };
+            let run_config = RunConfig::resolve(
+                &toolchain.id,
+                runtime.group,
+                isolation_mode,
+                sample.map(|count| (count, sample_seed)),
+                layer,
+                run_opts,
+            )?;
+            if print_config {
+                print_effective_config(&run_config)?;
+                return Ok(0);
+            }
+
+            let pool = Pool::open(&db.db);
 
             let rt = build_async_runtime();
             let mut conn = rt.block_on(pool.connection());
@@ -777,14 +1143,33 @@ fn main_result() -> anyhow::Result<i32> {
 
             rt.block_on(purge_old_data(conn.as_mut(), &artifact_id, purge.purge));
 
-            let runtime_suite = rt.block_on(load_runtime_benchmarks(
+            let mut runtime_suite = rt.block_on(load_runtime_benchmarks(
                 conn.as_mut(),
                 &runtime_benchmark_dir,
-                isolation_mode,
-                runtime.group,
+                run_config.isolation_mode,
+                run_config.group.clone(),
                 &toolchain,
                 &artifact_id,
+                run_config.compile.apply(RuntimeCompilationOpts::default()),
             ))?;
+            runtime_suite = runtime_suite.filter_groups(&run_config.group_filter);
+            if let Some(ref tags) = run_config.tags {
+                runtime_suite = runtime_suite.filter_by_tags(tags);
+            }
+            if let Some((count, seed)) = run_config.sample {
+                runtime_suite = runtime_suite.sample(&run_config.filter, count, seed);
+            }
+            if let Some(path) = repro_manifest {
+                let manifest = reproducibility_manifest(
+                    &run_config,
+                    &toolchain,
+                    &runtime_benchmark_dir,
+                    &runtime_suite,
+                );
+                std::fs::write(&path, serde_json::to_vec_pretty(&manifest)?).with_context(
+                    || format!("Cannot write reproducibility manifest {}", path.display()),
+                )?;
+            }
 
             let shared = SharedBenchmarkConfig {
                 artifact_id,
@@ -792,8 +1177,9 @@ fn main_result() -> anyhow::Result<i32> {
             };
             let config = RuntimeBenchmarkConfig::new(
                 runtime_suite,
-                RuntimeBenchmarkFilter::new(local.exclude, local.include),
-                iterations,
+                run_config.filter,
+                run_config.iterations,
+                run_config.opts,
             );
             rt.block_on(run_benchmarks(conn, shared, None, Some(config)))?;
             Ok(0)
@@ -818,12 +1204,12 @@ fn main_result() -> anyhow::Result<i32> {
                     &toolchain,
                     &runtime_benchmark_dir,
                     CargoIsolationMode::Cached,
-                    runtime.group.clone(),
+                    runtime.group.clone().into(),
                     // Compile with debuginfo to have filenames and line numbers available in the
                     // generated profiles.
                     RuntimeCompilationOpts::default().debug_info("1"),
                 )?
-                .extract_suite();
+                .try_extract_suite()?;
                 Ok::<_, anyhow::Error>((toolchain, suite))
             };
 
@@ -833,6 +1219,16 @@ fn main_result() -> anyhow::Result<i32> {
 
             if let Some(rustc2) = rustc2 {
                 match profiler {
+                    RuntimeProfiler::Flamegraph | RuntimeProfiler::Dhat => {
+                        println!("Profiling {rustc2}");
+                        let (toolchain2, suite2) = get_suite(&rustc2, "2")?;
+                        let profile2 = profile_runtime(profiler, &toolchain2, suite2, &benchmark)?;
+                        println!(
+                            "Profiling complete, results can be found in `{}` and `{}`",
+                            profile1.display(),
+                            profile2.display()
+                        );
+                    }
                     RuntimeProfiler::Cachegrind => {
                         println!("Profiling {rustc2}");
                         let (toolchain2, suite2) = get_suite(&rustc2, "2")?;
@@ -878,7 +1274,7 @@ fn main_result() -> anyhow::Result<i32> {
             let toolchain2 = get_toolchain(&rustc2, "2")?;
 
             let mut benchmark_groups =
-                get_runtime_benchmark_groups(&runtime_benchmark_dir, Some(group))?;
+                get_runtime_benchmark_groups(&runtime_benchmark_dir, GroupSelection::only(&group))?;
             let group = benchmark_groups.pop().expect("Benchmark group not found");
             assert!(benchmark_groups.is_empty());
 
@@ -1062,12 +1458,14 @@ fn main_result() -> anyhow::Result<i32> {
                             None,
                             &toolchain,
                             &artifact_id,
+                            RuntimeCompilationOpts::default(),
                         ))?;
 
                         let runtime_config = RuntimeBenchmarkConfig {
                             runtime_suite,
                             filter: RuntimeBenchmarkFilter::keep_all(),
                             iterations: DEFAULT_RUNTIME_ITERATIONS,
+                            opts: RuntimeRunOpts::default(),
                         };
                         let shared = SharedBenchmarkConfig {
                             artifact_id,
@@ -1577,16 +1975,18 @@ async fn load_runtime_benchmarks(
     group: Option<String>,
     toolchain: &Toolchain,
     artifact_id: &ArtifactId,
+    opts: RuntimeCompilationOpts,
 ) -> anyhow::Result<BenchmarkSuite> {
     let BenchmarkSuiteCompilation {
         suite,
         failed_to_compile,
+        ..
     } = prepare_runtime_benchmark_suite(
         toolchain,
         benchmark_dir,
         isolation_mode,
-        group,
-        RuntimeCompilationOpts::default(),
+        group.into(),
+        opts,
     )?;
 
     record_runtime_compilation_errors(conn, artifact_id, failed_to_compile).await;
@@ -1689,8 +2089,19 @@ async fn run_benchmarks(
             &collector,
             runtime.filter,
             runtime.iterations,
+            &runtime.opts,
         )
         .await
+        .and_then(|report| {
+            if report.is_success() {
+                Ok(())
+            } else {
+                Err(anyhow::anyhow!(
+                    "{} runtime benchmark group(s) failed",
+                    report.failures.len()
+                ))
+            }
+        })
         .context("Runtime benchmarks failed")
     } else {
         Ok(())
@@ -1734,6 +2145,7 @@ async fn bench_published_artifact(
         None,
         &toolchain,
         &artifact_id,
+        RuntimeCompilationOpts::default(),
     )
     .await?;
 
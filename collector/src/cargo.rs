@@ -1,16 +1,26 @@
 use anyhow::Context;
-use std::io::BufReader;
-use std::process::{Child, ChildStdout, Command, Stdio};
+use std::fs::File;
+use std::io::{BufReader, BufWriter, Write};
+use std::path::Path;
+use std::process::{Child, ChildStdout, Command, ExitStatus, Stdio};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{mpsc, Arc, Mutex};
+use std::thread::JoinHandle;
+use std::time::Duration;
 
 use cargo_metadata::{Message, MessageIter};
 
 /// Iterator that returns built artifacts from a Cargo command invocation.
-/// It also prints any text lines or messages produced during compilation,
-/// and gathers the messages for better error messages.
+/// It also prints any text lines or messages produced during compilation (either to stdout or to
+/// a log file), and gathers the messages for better error messages.
 pub struct CargoArtifactIter {
     stream: MessageIter<BufReader<ChildStdout>>,
-    cargo_process: Child,
+    cargo_process: Arc<Mutex<Child>>,
     messages: Vec<String>,
+    log: Option<BufWriter<File>>,
+    /// Thread that kills the Cargo process after a timeout, and a channel that stops it.
+    watchdog: Option<(mpsc::Sender<()>, JoinHandle<()>)>,
+    timed_out: Arc<AtomicBool>,
 }
 
 impl CargoArtifactIter {
@@ -22,20 +32,81 @@ impl CargoArtifactIter {
             .stdin(Stdio::null())
             .stdout(Stdio::piped())
             .stderr(Stdio::null());
+        // Start Cargo in a new process group, so that a timeout can also kill the compilers and
+        // build scripts that it has started.
+        #[cfg(unix)]
+        {
+            use std::os::unix::process::CommandExt;
+            cmd.process_group(0);
+        }
         let mut cargo_process = cmd.spawn()?;
         let stream = BufReader::new(cargo_process.stdout.take().unwrap());
         Ok(Self {
             stream: Message::parse_stream(stream),
-            cargo_process,
+            cargo_process: Arc::new(Mutex::new(cargo_process)),
             messages: Default::default(),
+            log: None,
+            watchdog: None,
+            timed_out: Default::default(),
         })
     }
 
+    /// Kills the Cargo process, together with all processes that it has started, if it is still
+    /// running after `timeout`. The iterator then stops returning artifacts, and `timed_out`
+    /// returns true.
+    pub fn kill_after(mut self, timeout: Duration) -> Self {
+        let (stop, stopped) = mpsc::channel::<()>();
+        let cargo_process = self.cargo_process.clone();
+        let timed_out = self.timed_out.clone();
+        let thread = std::thread::spawn(move || {
+            if let Err(mpsc::RecvTimeoutError::Timeout) = stopped.recv_timeout(timeout) {
+                timed_out.store(true, Ordering::SeqCst);
+                if let Err(error) = kill_process_group(&mut cargo_process.lock().unwrap()) {
+                    log::error!("Cannot kill Cargo process: {error:?}");
+                }
+            }
+        });
+        self.watchdog = Some((stop, thread));
+        self
+    }
+
+    /// Returns true if the Cargo process has been killed because of a timeout.
+    pub fn timed_out(&self) -> bool {
+        self.timed_out.load(Ordering::SeqCst)
+    }
+
+    /// Writes the text lines and messages produced during compilation into the file at `path`
+    /// instead of stdout.
+    pub fn log_to_file(mut self, path: &Path) -> anyhow::Result<Self> {
+        let file = File::create(path)
+            .with_context(|| format!("Cannot create log file {}", path.display()))?;
+        self.log = Some(BufWriter::new(file));
+        Ok(self)
+    }
+
+    fn output(&mut self, text: &str) {
+        match self.log {
+            Some(ref mut log) => {
+                if let Err(error) = log.write_all(text.as_bytes()).and_then(|_| log.flush()) {
+                    log::error!("Cannot write to compilation log: {error:?}");
+                }
+            }
+            None => print!("{text}"),
+        }
+    }
+
+    /// Waits for the Cargo process to exit. The timeout no longer applies afterwards.
+    fn wait(&mut self) -> std::io::Result<ExitStatus> {
+        if let Some((stop, thread)) = self.watchdog.take() {
+            // Disconnecting the channel stops the watchdog.
+            drop(stop);
+            thread.join().expect("Watchdog thread has panicked");
+        }
+        self.cargo_process.lock().unwrap().wait()
+    }
+
     pub fn finish(mut self) -> anyhow::Result<()> {
-        let output = self
-            .cargo_process
-            .wait()
-            .context("Cargo did not exit successfully")?;
+        let output = self.wait().context("Cargo did not exit successfully")?;
         if !output.success() {
             return Err(anyhow::anyhow!(
                 "Failed to run cargo, exit code {}\n{}",
@@ -47,10 +118,28 @@ impl CargoArtifactIter {
     }
 }
 
+/// Kills the process and the other processes in its process group.
+#[cfg(unix)]
+fn kill_process_group(process: &mut Child) -> std::io::Result<()> {
+    // The process has already exited, and its pid might have been reused.
+    if process.try_wait()?.is_some() {
+        return Ok(());
+    }
+    let pid = process.id() as libc::pid_t;
+    if unsafe { libc::kill(-pid, libc::SIGKILL) } != 0 {
+        return Err(std::io::Error::last_os_error());
+    }
+    Ok(())
+}
+
+#[cfg(not(unix))]
+fn kill_process_group(process: &mut Child) -> std::io::Result<()> {
+    process.kill()
+}
+
 impl Drop for CargoArtifactIter {
     fn drop(&mut self) {
-        self.cargo_process
-            .wait()
+        self.wait()
             .expect("Cargo process did not exit successfully");
     }
 }
@@ -65,12 +154,10 @@ impl Iterator for CargoArtifactIter {
                     Message::CompilerArtifact(artifact) => {
                         return Some(Ok(artifact));
                     }
-                    Message::TextLine(line) => {
-                        println!("{line}")
-                    }
+                    Message::TextLine(line) => self.output(&format!("{line}\n")),
                     Message::CompilerMessage(msg) => {
                         let message = msg.message.rendered.unwrap_or(msg.message.message);
-                        print!("{message}");
+                        self.output(&message);
                         self.messages.push(message);
                     }
                     _ => {}
@@ -80,3 +167,67 @@ impl Iterator for CargoArtifactIter {
         }
     }
 }
+
+#[cfg(all(test, unix))]
+mod tests {
+    use std::process::Command;
+    use std::time::Duration;
+
+    use super::CargoArtifactIter;
+
+    #[test]
+    fn test_log_to_file() {
+        let dir = tempfile::TempDir::new().unwrap();
+        for group in ["a", "b"] {
+            let mut cmd = Command::new("sh");
+            cmd.arg("-c").arg(format!("echo compiling {group}"));
+            let log = dir.path().join(format!("{group}.log"));
+            let mut iter = CargoArtifactIter::from_cargo_cmd(cmd)
+                .unwrap()
+                .log_to_file(&log)
+                .unwrap();
+            assert!(iter.next().is_none());
+            iter.finish().unwrap();
+        }
+
+        for group in ["a", "b"] {
+            let log = std::fs::read_to_string(dir.path().join(format!("{group}.log"))).unwrap();
+            assert_eq!(log, format!("compiling {group}\n"));
+        }
+    }
+
+    #[test]
+    fn test_kill_after() {
+        let mut cmd = Command::new("sh");
+        cmd.arg("-c").arg("exec sleep 10");
+        let start = std::time::Instant::now();
+        let mut iter = CargoArtifactIter::from_cargo_cmd(cmd)
+            .unwrap()
+            .kill_after(Duration::from_millis(100));
+        assert!(iter.next().is_none());
+        assert!(iter.timed_out());
+        assert!(iter.finish().is_err());
+        assert!(start.elapsed() < Duration::from_secs(5));
+
+        // The processes started by Cargo (which keep its stdout open) are killed as well.
+        let mut cmd = Command::new("sh");
+        cmd.arg("-c").arg("sleep 10 &\nwait");
+        let start = std::time::Instant::now();
+        let mut iter = CargoArtifactIter::from_cargo_cmd(cmd)
+            .unwrap()
+            .kill_after(Duration::from_millis(100));
+        assert!(iter.next().is_none());
+        assert!(iter.timed_out());
+        assert!(iter.finish().is_err());
+        assert!(start.elapsed() < Duration::from_secs(5));
+
+        let mut cmd = Command::new("sh");
+        cmd.arg("-c").arg("true");
+        let mut iter = CargoArtifactIter::from_cargo_cmd(cmd)
+            .unwrap()
+            .kill_after(Duration::from_secs(10));
+        assert!(iter.next().is_none());
+        assert!(!iter.timed_out());
+        iter.finish().unwrap();
+    }
+}
@@ -190,7 +190,7 @@ impl Processor for BenchProcessor<'_> {
                     };
 
                     let version = get_rustc_perf_commit();
-                    let collection = self.conn.collection_id(&version).await;
+                    let collection = self.conn.collection_id(&version, None).await;
 
                     if let Some(files) = res.2 {
                         self.self_profiles.push(RecordedSelfProfile {
@@ -156,7 +156,7 @@ async fn record(
     }
 
     let version = get_rustc_perf_commit();
-    let collection = conn.collection_id(&version).await;
+    let collection = conn.collection_id(&version, None).await;
 
     for (krate, timing) in timing_data {
         conn.record_rustc_crate(collection, aid, krate, timing)
@@ -0,0 +1,221 @@
+use std::collections::HashMap;
+use std::path::Path;
+
+use anyhow::Context;
+
+use benchlib::comm::messages::BenchmarkResult;
+
+use crate::runtime::{
+    benchmark_metrics, compare_to_baseline, execute_runtime_benchmark_group, merge_results,
+    prepare_runtime_benchmark_suite, qualified_name, BenchmarkSuite, CargoIsolationMode,
+    ComparisonConfig, GroupSelection, MetricDelta, RuntimeBenchmarkFilter, RuntimeCompilationOpts,
+    RuntimeRunOpts, DEFAULT_RUNTIME_ITERATIONS,
+};
+use crate::toolchain::Toolchain;
+
+/// How many times is the benchmark process of each group executed with each toolchain by
+/// `ab_compare`.
+const AB_ROUNDS: u32 = 3;
+
+/// Comparison of a single benchmark between a baseline and a candidate toolchain.
+#[derive(Clone, Debug, PartialEq, serde::Serialize)]
+pub struct BenchmarkComparison {
+    /// Qualified name of the benchmark (`<group>/<benchmark>`).
+    pub benchmark: String,
+    /// Deltas of all metrics measured with both toolchains, sorted by the metric name.
+    pub deltas: Vec<MetricDelta>,
+    /// True if any metric has regressed by at least the threshold passed to `ab_compare`.
+    pub regressed: bool,
+    /// True if any metric has improved by at least the threshold passed to `ab_compare`.
+    pub improved: bool,
+}
+
+/// Compiles the runtime benchmarks in `dir` with a baseline and a candidate toolchain, executes
+/// them and compares their results. Changes whose relative magnitude is at least `threshold`
+/// (e.g. `0.01` for 1%) are marked as regressions or improvements.
+///
+/// To reduce the noise caused by changing conditions of the machine, the executions are
+/// interleaved: the benchmark process of each group is executed alternately with the baseline
+/// and the candidate binary, several times, and the statistics of all executions are merged.
+/// Groups and benchmarks that exist only with one of the toolchains are skipped.
+pub fn ab_compare(
+    baseline: &Toolchain,
+    candidate: &Toolchain,
+    dir: &Path,
+    filter: &RuntimeBenchmarkFilter,
+    run_opts: &RuntimeRunOpts,
+    threshold: f64,
+) -> anyhow::Result<Vec<BenchmarkComparison>> {
+    let baseline_suite = compile_suite(baseline, dir)?.filter(filter);
+    let candidate_suite = compile_suite(candidate, dir)?.filter(filter);
+
+    let mut comparisons = vec![];
+    for baseline_group in &baseline_suite.groups {
+        let Some(candidate_group) = candidate_suite
+            .groups
+            .iter()
+            .find(|group| group.name == baseline_group.name)
+        else {
+            log::warn!(
+                "Runtime benchmark group `{}` was not compiled by {}, skipping it",
+                baseline_group.name,
+                candidate.id
+            );
+            continue;
+        };
+
+        let mut baseline_results: Vec<BenchmarkResult> = vec![];
+        let mut candidate_results: Vec<BenchmarkResult> = vec![];
+        for _ in 0..AB_ROUNDS {
+            for (group, results) in [
+                (baseline_group, &mut baseline_results),
+                (candidate_group, &mut candidate_results),
+            ] {
+                merge_results(
+                    results,
+                    execute_runtime_benchmark_group(
+                        &group.binary,
+                        &group.original_names,
+                        filter,
+                        DEFAULT_RUNTIME_ITERATIONS,
+                        run_opts,
+                    )?,
+                );
+            }
+        }
+
+        let candidate_results: HashMap<&str, &BenchmarkResult> = candidate_results
+            .iter()
+            .map(|result| (result.name.as_str(), result))
+            .collect();
+        for baseline_result in &baseline_results {
+            let Some(candidate_result) = candidate_results.get(baseline_result.name.as_str())
+            else {
+                continue;
+            };
+            let deltas = compare_to_baseline(
+                &benchmark_metrics(baseline_result),
+                &benchmark_metrics(candidate_result),
+                &ComparisonConfig::default(),
+            );
+            let significant = |delta: &&MetricDelta| delta.relative_change.abs() >= threshold;
+            comparisons.push(BenchmarkComparison {
+                benchmark: qualified_name(&baseline_group.name, &baseline_result.name),
+                regressed: deltas
+                    .iter()
+                    .filter(significant)
+                    .any(|delta| delta.is_regression()),
+                improved: deltas
+                    .iter()
+                    .filter(significant)
+                    .any(|delta| delta.is_improvement()),
+                deltas,
+            });
+        }
+    }
+    comparisons.sort_by(|a, b| a.benchmark.cmp(&b.benchmark));
+    Ok(comparisons)
+}
+
+fn compile_suite(toolchain: &Toolchain, dir: &Path) -> anyhow::Result<BenchmarkSuite> {
+    let compilation = prepare_runtime_benchmark_suite(
+        toolchain,
+        dir,
+        CargoIsolationMode::Isolated,
+        GroupSelection::all(),
+        RuntimeCompilationOpts::default(),
+    )?;
+    compilation
+        .try_extract_suite()
+        .with_context(|| format!("Cannot compile runtime benchmarks with {}", toolchain.id))
+}
+
+#[cfg(all(test, target_os = "linux"))]
+mod tests {
+    use std::path::Path;
+
+    use super::ab_compare;
+    use crate::runtime::tests::{compiler_artifact, create_script, echo_result};
+    use crate::runtime::{RuntimeBenchmarkFilter, RuntimeRunOpts, DEFAULT_LIST_COMMAND};
+    use crate::toolchain::{Toolchain, ToolchainComponents};
+
+    /// Creates a toolchain whose `cargo` pretends to compile every crate into a binary with two
+    /// benchmarks, `fast` and `slow`, that report the given wall times.
+    fn stub_toolchain(dir: &Path, id: &str, fast: &str, slow: &str) -> Toolchain {
+        let binary = create_script(
+            &dir.join(id),
+            "group",
+            &format!(
+                r#"if [ "$1" = "{DEFAULT_LIST_COMMAND}" ]; then echo '["fast", "slow"]'; exit 0; fi
+{}
+{}"#,
+                echo_result("fast", fast),
+                echo_result("slow", slow)
+            ),
+        );
+        let cargo = create_script(
+            &dir.join(id),
+            "cargo",
+            &format!("echo '{}'", compiler_artifact(&binary)),
+        );
+        Toolchain {
+            components: ToolchainComponents {
+                cargo,
+                ..Default::default()
+            },
+            id: id.to_string(),
+            triple: "x86_64-unknown-linux-gnu".to_string(),
+        }
+    }
+
+    #[test]
+    fn test_ab_compare() {
+        let dir = tempfile::TempDir::new().unwrap();
+        for id in ["baseline", "candidate"] {
+            std::fs::create_dir_all(dir.path().join(id)).unwrap();
+        }
+        let baseline = stub_toolchain(dir.path(), "baseline", "1000", "1000");
+        let candidate = stub_toolchain(dir.path(), "candidate", "1005", "1500");
+        let benchmark_dir = dir.path().join("benchmarks");
+        std::fs::create_dir_all(benchmark_dir.join("group/src")).unwrap();
+        std::fs::write(
+            benchmark_dir.join("group/Cargo.toml"),
+            "[package]\nname = \"group\"\nversion = \"0.1.0\"\n",
+        )
+        .unwrap();
+        std::fs::write(benchmark_dir.join("group/Cargo.lock"), "").unwrap();
+
+        let comparisons = ab_compare(
+            &baseline,
+            &candidate,
+            &benchmark_dir,
+            &RuntimeBenchmarkFilter::keep_all(),
+            &RuntimeRunOpts::default(),
+            0.01,
+        )
+        .unwrap();
+        let summary: Vec<(&str, bool, bool)> = comparisons
+            .iter()
+            .map(|c| (c.benchmark.as_str(), c.regressed, c.improved))
+            .collect();
+        assert_eq!(
+            summary,
+            [("group/fast", false, false), ("group/slow", true, false)]
+        );
+        let wall_time = &comparisons[1].deltas[0];
+        assert_eq!(wall_time.metric, "wall-time");
+        assert!((wall_time.relative_change - 0.5).abs() < 1e-9);
+
+        let comparisons = ab_compare(
+            &baseline,
+            &candidate,
+            &benchmark_dir,
+            &RuntimeBenchmarkFilter::new(vec![], vec!["fast".to_string()]),
+            &RuntimeRunOpts::default(),
+            0.001,
+        )
+        .unwrap();
+        assert_eq!(comparisons.len(), 1);
+        assert!(comparisons[0].regressed);
+    }
+}
@@ -1,13 +1,24 @@
-use std::collections::HashMap;
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::fmt::{self, Write};
 use std::path::{Path, PathBuf};
-use std::process::Command;
+use std::process::{Command, Stdio};
+use std::str::FromStr;
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::sync::mpsc;
+use std::time::{Duration, Instant};
 
 use anyhow::Context;
 use tempfile::TempDir;
 
-use benchlib::benchmark::passes_filter;
+use benchlib::benchmark::{passes_filter, LIST_PROTOCOL_VERSION_ENV};
 
+use crate::artifact_stats::binary_dynamic_deps;
 use crate::cargo::CargoArtifactIter;
+use crate::command_output;
+use crate::runtime::binary_cache::{toolchain_cache_inputs, BinaryCache};
+use crate::runtime::history::CompileTimeHistory;
+use crate::runtime::resume::{fingerprint_crate, CompilationResume};
+use crate::runtime::{run_command_until, PhaseTimings, RunTimedOut, TagExpr};
 use crate::runtime_group_step_name;
 use crate::toolchain::Toolchain;
 use crate::utils::fs::EnsureImmutableFile;
@@ -21,32 +32,91 @@ pub fn runtime_benchmark_dir() -> PathBuf {
 
 /// A binary that defines several benchmarks using the `run_benchmark_group` function from
 /// `benchlib`.
-#[derive(Debug)]
+#[derive(Debug, serde::Serialize, serde::Deserialize)]
 pub struct BenchmarkGroup {
     pub binary: PathBuf,
     pub name: String,
     pub benchmark_names: Vec<String>,
+    /// Cargo features that were enabled when compiling the group, both the ones requested by the
+    /// collector (e.g. `benchlib/precise-cachegrind`) and the resolved features of the group crate.
+    pub features: Vec<String>,
+    /// True if Cargo reused the binary from a previous build instead of rebuilding it.
+    /// This can only happen in the cached mode.
+    pub cached: bool,
+    /// Size of the benchmark binary in bytes.
+    pub binary_size: u64,
+    /// Maps benchmark names to the names used by the benchmark binary, if they were changed
+    /// by a name transform or if only a subset of the benchmarks was selected. If it is not
+    /// empty, only the benchmarks in this map are executed. Empty otherwise.
+    #[serde(default, skip_serializing_if = "HashMap::is_empty")]
+    pub original_names: HashMap<String, String>,
+    /// Tags of the group, which apply to all of its benchmarks.
+    pub tags: Vec<String>,
+    /// Total size (in bytes) of the LLVM IR emitted when compiling the group, if it was measured.
+    pub llvm_ir_size: Option<u64>,
+    /// Version of the `benchlib` protocol reported by the binary, or `None` if the binary does
+    /// not report it (e.g. because it uses an older `benchlib`).
+    pub protocol_version: Option<u32>,
 }
 
 /// A collection of benchmark suites gathered from a directory.
+///
+/// Benchmarks are always enumerated (and executed) in the canonical order, which is the ascending
+/// order of their qualified names (`<group>/<benchmark>`).
 #[derive(Debug)]
 pub struct BenchmarkSuite {
     /// Toolchain used to compile this suite.
     pub toolchain: Toolchain,
+    /// Sorted by `sort_canonically` when the suite is built, so that iterating over the groups
+    /// and their benchmarks follows the canonical order.
     pub groups: Vec<BenchmarkGroup>,
     /// This field holds onto a temporary directory containing the compiled binaries with the
     /// runtime benchmarks. It is only stored here in order not to be dropped too soon.
     _tmp_artifacts_dir: Option<TempDir>,
+    /// How long did the discovery and the compilation of the suite take.
+    pub(super) phase_timings: PhaseTimings,
+    pub(super) compile_settings: CompileSettings,
+}
+
+/// Settings from `RuntimeCompilationOpts` with which a suite was compiled, which also matter
+/// when the suite is executed or when its results are interpreted.
+#[derive(Debug, Clone, Default, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+pub(super) struct CompileSettings {
+    /// LTO setting with which the suite was compiled, if it was overridden.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub(super) lto: Option<String>,
+    /// Whether the suite was compiled with overflow checks, if it was overridden.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub(super) overflow_checks: Option<bool>,
+    /// Command that executes the benchmark binaries, if they cannot be executed directly.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub(super) runner: Option<Vec<String>>,
 }
 
 impl BenchmarkSuite {
+    pub(super) fn from_groups(toolchain: Toolchain, mut groups: Vec<BenchmarkGroup>) -> Self {
+        sort_canonically(&mut groups);
+        Self {
+            toolchain,
+            groups,
+            _tmp_artifacts_dir: None,
+            phase_timings: Default::default(),
+            compile_settings: Default::default(),
+        }
+    }
+
     /// Returns a new suite containing only groups that contains at least a single benchmark
     /// that matches the filter.
+    ///
+    /// Benchmark binaries can only select benchmarks by prefixes, so for a regex or glob filter,
+    /// the execution of each group is also restricted to the names of the matching benchmarks.
     pub fn filter(self, filter: &RuntimeBenchmarkFilter) -> Self {
         let BenchmarkSuite {
             toolchain,
             groups,
             _tmp_artifacts_dir,
+            phase_timings,
+            compile_settings,
         } = self;
 
         Self {
@@ -57,23 +127,249 @@ impl BenchmarkSuite {
                     group
                         .benchmark_names
                         .iter()
-                        .any(|benchmark| passes_filter(benchmark, &filter.exclude, &filter.include))
+                        .any(|benchmark| filter.matches(benchmark))
+                })
+                .map(|mut group| {
+                    if !filter.is_prefix() {
+                        group.original_names = group
+                            .benchmark_names
+                            .iter()
+                            .filter(|benchmark| filter.matches(benchmark))
+                            .map(|benchmark| {
+                                let original =
+                                    group.original_names.get(benchmark).unwrap_or(benchmark);
+                                (benchmark.clone(), original.clone())
+                            })
+                            .collect();
+                    }
+                    group
                 })
                 .collect(),
             _tmp_artifacts_dir,
+            phase_timings,
+            compile_settings,
+        }
+    }
+
+    /// Returns a new suite containing only groups whose names pass the group filter. This should
+    /// be applied before the benchmark filter, so that groups can be skipped without listing
+    /// their benchmarks.
+    pub fn filter_groups(mut self, filter: &BenchmarkGroupFilter) -> Self {
+        self.groups.retain(|group| filter.matches(&group.name));
+        self
+    }
+
+    /// Returns a new suite containing only groups whose tags match the expression.
+    pub fn filter_by_tags(mut self, expr: &TagExpr) -> Self {
+        self.groups.retain(|group| expr.matches(&group.tags));
+        self
+    }
+
+    /// Returns the names of groups that have the given tag. Tags apply to all benchmarks of
+    /// a group, so these are exactly the groups with at least one benchmark with the tag.
+    pub fn groups_with_tag(&self, tag: &str) -> Vec<&str> {
+        self.groups
+            .iter()
+            .filter(|group| group.tags.iter().any(|t| t == tag))
+            .map(|group| group.name.as_str())
+            .collect()
+    }
+
+    /// Returns a new suite containing a random sample of `count` benchmarks that pass the filter.
+    /// The same `seed` always selects the same benchmarks from the same suite. If fewer than
+    /// `count` benchmarks pass the filter, all of them are kept.
+    pub fn sample(mut self, filter: &RuntimeBenchmarkFilter, count: usize, seed: u64) -> Self {
+        let mut candidates: Vec<String> = self
+            .canonical_benchmarks()
+            .filter(|(_, benchmark)| filter.matches(benchmark))
+            .map(|(group, benchmark)| qualified_name(group, benchmark))
+            .collect();
+
+        // Partial Fisher-Yates shuffle, which moves the sample to the front.
+        let mut rng = SplitMix64(seed);
+        let count = count.min(candidates.len());
+        for i in 0..count {
+            let j = i + (rng.next() % (candidates.len() - i) as u64) as usize;
+            candidates.swap(i, j);
+        }
+        let sample: HashSet<String> = candidates.into_iter().take(count).collect();
+
+        for group in &mut self.groups {
+            let original_names = std::mem::take(&mut group.original_names);
+            group
+                .benchmark_names
+                .retain(|benchmark| sample.contains(&qualified_name(&group.name, benchmark)));
+            // Restrict the execution of the group binary to the sampled benchmarks.
+            group.original_names = group
+                .benchmark_names
+                .iter()
+                .map(|benchmark| {
+                    let original = original_names.get(benchmark).unwrap_or(benchmark);
+                    (benchmark.clone(), original.clone())
+                })
+                .collect();
         }
+        self.groups
+            .retain(|group| !group.benchmark_names.is_empty());
+        self
+    }
+
+    pub fn group_count(&self) -> usize {
+        self.groups.len()
+    }
+
+    /// Returns the number of benchmarks in all groups, without sorting them.
+    pub fn benchmark_count(&self) -> usize {
+        self.groups
+            .iter()
+            .map(|group| group.benchmark_names.len())
+            .sum()
     }
 
     pub fn filtered_benchmark_count(&self, filter: &RuntimeBenchmarkFilter) -> u64 {
         self.benchmark_names()
-            .filter(|benchmark| passes_filter(benchmark, &filter.exclude, &filter.include))
+            .filter(|benchmark| filter.matches(benchmark))
             .count() as u64
     }
 
+    /// Returns the names of all benchmarks in the canonical order.
     pub fn benchmark_names(&self) -> impl Iterator<Item = &str> {
+        self.canonical_benchmarks().map(|(_, benchmark)| benchmark)
+    }
+
+    /// Returns the qualified names (`<group>/<benchmark>`) of all benchmarks in the canonical
+    /// order.
+    pub fn qualified_names(&self) -> impl Iterator<Item = String> + '_ {
+        self.canonical_benchmarks()
+            .map(|(group, benchmark)| qualified_name(group, benchmark))
+    }
+
+    /// Returns `(group, benchmark)` name pairs in the order of their qualified names. The groups
+    /// are already sorted canonically, so this does not need to sort them again.
+    fn canonical_benchmarks(&self) -> impl Iterator<Item = (&str, &str)> {
+        self.groups.iter().flat_map(|group| {
+            group
+                .benchmark_names
+                .iter()
+                .map(|benchmark| (group.name.as_str(), benchmark.as_str()))
+        })
+    }
+
+    /// Returns the entries of a blocklist (excluded benchmark name prefixes, in the format of
+    /// `RuntimeBenchmarkFilter::exclude`) that do not match any benchmark of the suite, so that
+    /// they can be pruned.
+    pub fn stale_blocklist_entries(&self, blocklist: &[String]) -> Vec<String> {
+        let benchmarks: Vec<&str> = self.benchmark_names().collect();
+        blocklist
+            .iter()
+            .filter(|entry| {
+                !benchmarks
+                    .iter()
+                    .any(|benchmark| benchmark.starts_with(entry.as_str()))
+            })
+            .cloned()
+            .collect()
+    }
+
+    /// Returns an error listing all `required` benchmarks that are not present in the suite.
+    pub fn assert_required_present(&self, required: &[&str]) -> anyhow::Result<()> {
+        let present: HashSet<&str> = self.benchmark_names().collect();
+        let missing: Vec<&str> = required
+            .iter()
+            .copied()
+            .filter(|benchmark| !present.contains(benchmark))
+            .collect();
+        if missing.is_empty() {
+            Ok(())
+        } else {
+            Err(anyhow::anyhow!(
+                "Required runtime benchmark(s) are missing from the suite: {}",
+                missing.join(", ")
+            ))
+        }
+    }
+
+    /// Returns a map from each benchmark name to the path of the binary that contains it.
+    pub fn benchmark_to_binary_map(&self) -> HashMap<String, PathBuf> {
         self.groups
             .iter()
-            .flat_map(|suite| suite.benchmark_names.iter().map(|n| n.as_ref()))
+            .flat_map(|group| {
+                group
+                    .benchmark_names
+                    .iter()
+                    .map(|benchmark| (benchmark.clone(), group.binary.clone()))
+            })
+            .collect()
+    }
+
+    /// Writes the map returned by [`Self::benchmark_to_binary_map`] as a JSON object, sorted by
+    /// the benchmark names, so that external tools can execute the benchmarks themselves.
+    pub fn write_benchmark_to_binary_map<W: std::io::Write>(&self, out: W) -> anyhow::Result<()> {
+        let map: BTreeMap<String, PathBuf> = self.benchmark_to_binary_map().into_iter().collect();
+        serde_json::to_writer_pretty(out, &map)?;
+        Ok(())
+    }
+
+    /// Returns the structure of the suite (its groups with their binaries and benchmarks) as
+    /// JSON, so that external tools can plan the execution of the benchmarks without compiling
+    /// them again. The toolchain is identified by its id and target triple.
+    pub fn to_metadata_json(&self) -> anyhow::Result<String> {
+        let metadata = SuiteMetadata {
+            toolchain: &self.toolchain.id,
+            triple: &self.toolchain.triple,
+            compile_settings: &self.compile_settings,
+            groups: &self.groups,
+        };
+        Ok(serde_json::to_string_pretty(&metadata)?)
+    }
+
+    /// Reconstructs a suite from metadata written by `to_metadata_json`, so that benchmarks
+    /// compiled by another process (or on another machine) can be executed without compiling
+    /// them again. The binaries must therefore outlive the process that compiled them, i.e. they
+    /// should be compiled in the cached mode or copied elsewhere.
+    ///
+    /// Fails if the metadata were written for a different toolchain, or if any of the binaries
+    /// does not exist or is not executable anymore.
+    pub fn from_metadata(path: &Path, toolchain: Toolchain) -> anyhow::Result<Self> {
+        let contents = std::fs::read(path)
+            .with_context(|| format!("Cannot read suite metadata {}", path.display()))?;
+        let metadata: OwnedSuiteMetadata = serde_json::from_slice(&contents)
+            .with_context(|| format!("Cannot parse suite metadata {}", path.display()))?;
+        if metadata.toolchain != toolchain.id || metadata.triple != toolchain.triple {
+            return Err(anyhow::anyhow!(
+                "Suite metadata {} were written for toolchain `{}` ({}), not for `{}` ({})",
+                path.display(),
+                metadata.toolchain,
+                metadata.triple,
+                toolchain.id,
+                toolchain.triple
+            ));
+        }
+
+        let missing: Vec<String> = metadata
+            .groups
+            .iter()
+            .filter(|group| !is_executable(&group.binary))
+            .map(|group| format!("`{}`: {}", group.name, group.binary.display()))
+            .collect();
+        if !missing.is_empty() {
+            return Err(anyhow::anyhow!(
+                "Binaries of {} runtime benchmark group(s) do not exist or are not executable:\n{}",
+                missing.len(),
+                missing.join("\n")
+            ));
+        }
+        check_duplicates(&metadata.groups)?;
+
+        let mut groups = metadata.groups;
+        sort_canonically(&mut groups);
+        Ok(Self {
+            toolchain,
+            groups,
+            _tmp_artifacts_dir: None,
+            phase_timings: Default::default(),
+            compile_settings: metadata.compile_settings,
+        })
     }
 
     pub fn get_group_by_benchmark(&self, benchmark: &str) -> Option<&BenchmarkGroup> {
@@ -86,62 +382,531 @@ impl BenchmarkSuite {
     }
 }
 
+/// Serializable view of a `BenchmarkSuite`, see `BenchmarkSuite::to_metadata_json`.
+#[derive(serde::Serialize)]
+struct SuiteMetadata<'a> {
+    toolchain: &'a str,
+    triple: &'a str,
+    #[serde(flatten)]
+    compile_settings: &'a CompileSettings,
+    groups: &'a [BenchmarkGroup],
+}
+
+/// Owned counterpart of `SuiteMetadata`, used by `BenchmarkSuite::from_metadata`.
+#[derive(serde::Deserialize)]
+struct OwnedSuiteMetadata {
+    toolchain: String,
+    triple: String,
+    #[serde(flatten)]
+    compile_settings: CompileSettings,
+    groups: Vec<BenchmarkGroup>,
+}
+
+#[cfg(unix)]
+fn is_executable(path: &Path) -> bool {
+    use std::os::unix::fs::PermissionsExt;
+
+    std::fs::metadata(path)
+        .is_ok_and(|metadata| metadata.is_file() && metadata.permissions().mode() & 0o111 != 0)
+}
+
+#[cfg(not(unix))]
+fn is_executable(path: &Path) -> bool {
+    path.is_file()
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, serde::Serialize)]
 pub struct RuntimeBenchmarkFilter {
     pub exclude: Vec<String>,
     pub include: Vec<String>,
+    /// How the patterns are matched against benchmark names.
+    #[serde(rename = "syntax", skip_serializing_if = "FilterPatterns::is_prefix")]
+    patterns: FilterPatterns,
 }
 
 impl RuntimeBenchmarkFilter {
     pub fn keep_all() -> Self {
+        Self::new(vec![], vec![])
+    }
+
+    /// Creates a filter that matches prefixes of benchmark names.
+    pub fn new(exclude: Vec<String>, include: Vec<String>) -> Self {
         Self {
-            exclude: vec![],
-            include: vec![],
+            exclude,
+            include,
+            patterns: FilterPatterns::Prefix,
+        }
+    }
+
+    /// Creates a filter whose patterns are regular expressions, e.g. `^hashmap_.*_large$`. The
+    /// patterns are not anchored, so that `insert` matches every benchmark containing `insert`.
+    pub fn regex(exclude: Vec<String>, include: Vec<String>) -> anyhow::Result<Self> {
+        let regex = CompiledPatterns {
+            exclude: compile_patterns(exclude.iter().cloned(), "exclude")?,
+            include: compile_patterns(include.iter().cloned(), "include")?,
+        };
+        Ok(Self {
+            exclude,
+            include,
+            patterns: FilterPatterns::Regex(regex),
+        })
+    }
+
+    /// Creates a filter whose patterns are globs matched against the whole benchmark name, e.g.
+    /// `sort_*`. `*` matches any sequence of characters and `?` any single character.
+    pub fn glob(exclude: Vec<String>, include: Vec<String>) -> anyhow::Result<Self> {
+        let regex = CompiledPatterns {
+            exclude: compile_patterns(exclude.iter().map(|glob| glob_to_regex(glob)), "exclude")?,
+            include: compile_patterns(include.iter().map(|glob| glob_to_regex(glob)), "include")?,
+        };
+        Ok(Self {
+            exclude,
+            include,
+            patterns: FilterPatterns::Glob(regex),
+        })
+    }
+
+    /// Returns `true` if the patterns are prefixes of benchmark names, which is the only kind of
+    /// patterns that can be passed to benchmark binaries.
+    pub fn is_prefix(&self) -> bool {
+        self.patterns.is_prefix()
+    }
+
+    /// Returns `true` if the benchmark with the given name passes the filter.
+    pub fn matches(&self, benchmark: &str) -> bool {
+        match self.patterns {
+            FilterPatterns::Prefix => passes_filter(benchmark, &self.exclude, &self.include),
+            FilterPatterns::Glob(ref regex) | FilterPatterns::Regex(ref regex) => {
+                (self.include.is_empty() || regex.include.is_match(benchmark))
+                    && !regex.exclude.is_match(benchmark)
+            }
+        }
+    }
+}
+
+/// How the patterns of a `RuntimeBenchmarkFilter` are matched against benchmark names.
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum FilterPatterns {
+    /// The patterns are prefixes of benchmark names.
+    Prefix,
+    /// The patterns are globs, compiled into regular expressions that match the whole name.
+    Glob(CompiledPatterns),
+    /// The patterns are regular expressions.
+    Regex(CompiledPatterns),
+}
+
+impl FilterPatterns {
+    fn is_prefix(&self) -> bool {
+        matches!(self, FilterPatterns::Prefix)
+    }
+
+    fn syntax(&self) -> Option<PatternSyntax> {
+        match self {
+            FilterPatterns::Prefix => None,
+            FilterPatterns::Glob(_) => Some(PatternSyntax::Glob),
+            FilterPatterns::Regex(_) => Some(PatternSyntax::Regex),
+        }
+    }
+}
+
+impl serde::Serialize for FilterPatterns {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(match self {
+            FilterPatterns::Prefix => "prefix",
+            FilterPatterns::Glob(_) => "glob",
+            FilterPatterns::Regex(_) => "regex",
+        })
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum PatternSyntax {
+    Regex,
+    Glob,
+}
+
+impl PatternSyntax {
+    const ALL: [PatternSyntax; 2] = [PatternSyntax::Regex, PatternSyntax::Glob];
+
+    /// Marker of the syntax in the canonical string form of a filter.
+    fn marker(self) -> &'static str {
+        match self {
+            PatternSyntax::Regex => "re:",
+            PatternSyntax::Glob => "glob:",
         }
     }
+}
+
+/// Selects whole benchmark groups by their names. Like `RuntimeBenchmarkFilter::new`, the
+/// patterns are prefixes.
+#[derive(Debug, Clone, PartialEq, Eq, serde::Serialize)]
+pub struct BenchmarkGroupFilter {
+    pub exclude: Vec<String>,
+    pub include: Vec<String>,
+}
+
+impl BenchmarkGroupFilter {
+    pub fn keep_all() -> Self {
+        Self::new(vec![], vec![])
+    }
 
     pub fn new(exclude: Vec<String>, include: Vec<String>) -> Self {
         Self { exclude, include }
     }
+
+    /// Returns `true` if the group with the given name passes the filter.
+    pub fn matches(&self, group: &str) -> bool {
+        passes_filter(group, &self.exclude, &self.include)
+    }
+}
+
+/// Regular expressions of a filter, compiled once when the filter is created.
+#[derive(Debug, Clone)]
+struct CompiledPatterns {
+    exclude: regex::RegexSet,
+    include: regex::RegexSet,
+}
+
+impl PartialEq for CompiledPatterns {
+    fn eq(&self, other: &Self) -> bool {
+        self.exclude.patterns() == other.exclude.patterns()
+            && self.include.patterns() == other.include.patterns()
+    }
+}
+
+impl Eq for CompiledPatterns {}
+
+fn compile_patterns(
+    patterns: impl Iterator<Item = String>,
+    kind: &str,
+) -> anyhow::Result<regex::RegexSet> {
+    regex::RegexSet::new(patterns)
+        .with_context(|| format!("Invalid {kind} pattern in benchmark filter"))
+}
+
+/// Translates a glob into an equivalent regular expression that matches the whole name.
+fn glob_to_regex(glob: &str) -> String {
+    let mut regex = "^".to_string();
+    for c in glob.chars() {
+        match c {
+            '*' => regex.push_str(".*"),
+            '?' => regex.push('.'),
+            c => regex.push_str(&regex::escape(c.encode_utf8(&mut [0; 4]))),
+        }
+    }
+    regex.push('$');
+    regex
+}
+
+/// Returns the qualified name of a benchmark, which is unique within a suite.
+pub fn qualified_name(group: &str, benchmark: &str) -> String {
+    format!("{group}/{benchmark}")
+}
+
+/// Small deterministic pseudo-random number generator (SplitMix64), used to sample benchmarks
+/// reproducibly.
+struct SplitMix64(u64);
+
+impl SplitMix64 {
+    fn next(&mut self) -> u64 {
+        self.0 = self.0.wrapping_add(0x9e3779b97f4a7c15);
+        let mut z = self.0;
+        z = (z ^ (z >> 30)).wrapping_mul(0xbf58476d1ce4e5b9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94d049bb133111eb);
+        z ^ (z >> 31)
+    }
+}
+
+/// Sorts the groups, and the benchmarks within each group, so that executing them one after
+/// another follows the canonical order of qualified names.
+fn sort_canonically(groups: &mut [BenchmarkGroup]) {
+    // Qualified names of a group share the `<group>/` prefix, so sorting the groups by it keeps
+    // the benchmarks of each group contiguous in the canonical order.
+    groups.sort_by_cached_key(|group| format!("{}/", group.name));
+    for group in groups {
+        group.benchmark_names.sort();
+    }
+}
+
+/// Canonical string form of a filter: space-separated patterns, each prefixed with a sign that
+/// describes how the pattern is used (`+` for include, `-` for exclude). Spaces and backslashes in
+/// patterns are escaped with a backslash. The filter that keeps all benchmarks is an empty string.
+///
+/// For example, `+hashmap +fmt -hashmap_slow`. Filters whose patterns are not prefixes start
+/// with a marker of their syntax, `re:` for regular expressions and `glob:` for globs, e.g.
+/// `re: +^hashmap_.*_large$`.
+impl fmt::Display for RuntimeBenchmarkFilter {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let mut separate = false;
+        if let Some(syntax) = self.patterns.syntax() {
+            f.write_str(syntax.marker())?;
+            separate = true;
+        }
+        let patterns = self
+            .include
+            .iter()
+            .map(|pattern| ('+', pattern))
+            .chain(self.exclude.iter().map(|pattern| ('-', pattern)));
+        for (sign, pattern) in patterns {
+            if separate {
+                f.write_char(' ')?;
+            }
+            separate = true;
+            f.write_char(sign)?;
+            for c in pattern.chars() {
+                if c == ' ' || c == '\\' {
+                    f.write_char('\\')?;
+                }
+                f.write_char(c)?;
+            }
+        }
+        Ok(())
+    }
+}
+
+impl FromStr for RuntimeBenchmarkFilter {
+    type Err = anyhow::Error;
+
+    /// Parses the canonical string form produced by the `Display` implementation.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut tokens = vec![];
+        let mut token = String::new();
+        let mut chars = s.chars();
+        while let Some(c) = chars.next() {
+            match c {
+                '\\' => token.push(chars.next().ok_or_else(|| {
+                    anyhow::anyhow!("Benchmark filter `{s}` ends with an incomplete escape")
+                })?),
+                ' ' => tokens.push(std::mem::take(&mut token)),
+                c => token.push(c),
+            }
+        }
+        tokens.push(token);
+        let mut tokens = tokens
+            .into_iter()
+            .filter(|token| !token.is_empty())
+            .peekable();
+
+        // Patterns start with a sign, so they cannot be confused with the marker.
+        let syntax = tokens.peek().and_then(|token| {
+            PatternSyntax::ALL
+                .into_iter()
+                .find(|syntax| token == syntax.marker())
+        });
+        if syntax.is_some() {
+            tokens.next();
+        }
+
+        let (mut exclude, mut include) = (vec![], vec![]);
+        for token in tokens {
+            let mut chars = token.chars();
+            match chars.next() {
+                Some('+') => include.push(chars.collect()),
+                Some('-') => exclude.push(chars.collect()),
+                _ => {
+                    return Err(anyhow::anyhow!(
+                        "Invalid benchmark filter pattern `{token}`, expected it to start with `+` or `-`"
+                    ))
+                }
+            }
+        }
+        match syntax {
+            None => Ok(Self::new(exclude, include)),
+            Some(PatternSyntax::Regex) => Self::regex(exclude, include),
+            Some(PatternSyntax::Glob) => Self::glob(exclude, include),
+        }
+    }
 }
 
 /// A single crate located in the runtime benchmark directory.
 pub struct BenchmarkGroupCrate {
     pub name: String,
     pub path: PathBuf,
+    /// Tags from `[package.metadata.rustc-perf] tags = [...]` in the `Cargo.toml` of the crate.
+    pub tags: Vec<String>,
 }
 
 /// Determines whether runtime benchmarks will be recompiled from scratch in a temporary directory
 ///
+#[derive(Debug, Clone, Copy, serde::Serialize)]
+#[serde(rename_all = "lowercase")]
 pub enum CargoIsolationMode {
     Cached,
     Isolated,
 }
 
+/// Restricts how Cargo may access the network and the lockfile while compiling runtime benchmarks.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CargoNetworkMode {
+    /// `--offline`: never access the network.
+    Offline,
+    /// `--frozen`: never access the network, and fail if `Cargo.lock` is out of date.
+    Frozen,
+    /// `--locked`: fail if `Cargo.lock` is out of date.
+    Locked,
+}
+
+impl CargoNetworkMode {
+    fn flag(&self) -> &'static str {
+        match self {
+            CargoNetworkMode::Offline => "--offline",
+            CargoNetworkMode::Frozen => "--frozen",
+            CargoNetworkMode::Locked => "--locked",
+        }
+    }
+}
+
 pub struct BenchmarkSuiteCompilation {
     pub suite: BenchmarkSuite,
     // Maps benchmark group name to compilation error
     pub failed_to_compile: HashMap<String, String>,
+    /// Number of groups that had to be (re)compiled.
+    pub rebuilt_groups: usize,
+    /// Number of groups whose binary was reused from a previous build.
+    pub cached_groups: usize,
+    /// When was each group built, including groups that failed to compile.
+    pub build_timings: Vec<GroupBuildTiming>,
+}
+
+/// Time span during which a single benchmark group was being built.
+#[derive(Debug, Clone)]
+pub struct GroupBuildTiming {
+    pub group: String,
+    pub start: Instant,
+    pub end: Instant,
+}
+
+impl GroupBuildTiming {
+    pub fn duration(&self) -> Duration {
+        self.end.duration_since(self.start)
+    }
 }
 
 impl BenchmarkSuiteCompilation {
-    pub fn extract_suite(self) -> BenchmarkSuite {
-        use std::fmt::Write;
+    /// Returns the group whose build took the longest, together with the duration of its build.
+    /// If groups are built in parallel, this is the lower bound of the total build time.
+    pub fn build_critical_path(&self) -> Option<(String, Duration)> {
+        self.build_timings
+            .iter()
+            .max_by_key(|timing| timing.duration())
+            .map(|timing| (timing.group.clone(), timing.duration()))
+    }
 
+    /// Returns the wall time between the start of the first build and the end of the last one.
+    pub fn build_wall_time(&self) -> Duration {
+        let start = self.build_timings.iter().map(|timing| timing.start).min();
+        let end = self.build_timings.iter().map(|timing| timing.end).max();
+        match (start, end) {
+            (Some(start), Some(end)) => end.duration_since(start),
+            _ => Duration::ZERO,
+        }
+    }
+
+    /// Returns the ratio of groups that were reused from a previous build, or `0.0` if no group
+    /// has been compiled successfully.
+    pub fn cache_hit_rate(&self) -> f64 {
+        let total = self.rebuilt_groups + self.cached_groups;
+        if total == 0 {
+            return 0.0;
+        }
+        self.cached_groups as f64 / total as f64
+    }
+
+    /// Writes the compilation failures as a JSON object, e.g. to be uploaded as a CI artifact:
+    /// `{"succeeded": <count>, "failed": <count>, "failures": {<step name>: <error>}}`. The
+    /// failures are sorted by the step names of the groups.
+    pub fn write_failure_report<W: std::io::Write>(&self, out: W) -> anyhow::Result<()> {
+        let report = CompilationFailureReport {
+            succeeded: self.suite.groups.len(),
+            failed: self.failed_to_compile.len(),
+            failures: self
+                .failed_to_compile
+                .iter()
+                .map(|(group, error)| (group.as_str(), error.as_str()))
+                .collect(),
+        };
+        serde_json::to_writer_pretty(out, &report)?;
+        Ok(())
+    }
+
+    /// Returns the compiled suite, or an error describing the compilation errors of all groups
+    /// that failed to compile.
+    pub fn try_extract_suite(self) -> anyhow::Result<BenchmarkSuite> {
         if !self.failed_to_compile.is_empty() {
             let mut message =
                 "Cannot extract runtime suite because of compilation errors:\n".to_string();
-            for (group, error) in self.failed_to_compile {
+            let failures: BTreeMap<String, String> = self.failed_to_compile.into_iter().collect();
+            for (group, error) in failures {
                 writeln!(message, "{group}\n{error}\n").unwrap();
             }
-            panic!("{message}");
+            return Err(anyhow::anyhow!(message));
+        }
+        Ok(self.suite)
+    }
+
+    /// Like `try_extract_suite`, but panics if any group failed to compile.
+    pub fn extract_suite(self) -> BenchmarkSuite {
+        match self.try_extract_suite() {
+            Ok(suite) => suite,
+            Err(error) => panic!("{error}"),
         }
-        self.suite
     }
 }
 
+/// See `BenchmarkSuiteCompilation::write_failure_report`.
+#[derive(serde::Serialize)]
+struct CompilationFailureReport<'a> {
+    succeeded: usize,
+    failed: usize,
+    failures: BTreeMap<&'a str, &'a str>,
+}
+
+/// Values accepted by `RuntimeCompilationOpts::lto`.
+const LTO_VALUES: &[&str] = &["off", "thin", "fat", "true", "false"];
+
+/// Values accepted by `RuntimeCompilationOpts::panic`.
+const PANIC_VALUES: &[&str] = &["unwind", "abort"];
+
+/// Name of the `benchlib` subcommand that lists the benchmarks of a group.
+pub const DEFAULT_LIST_COMMAND: &str = "list";
+
+/// Function that rewrites benchmark names reported by the benchmark binaries.
+pub type NameTransform = Box<dyn Fn(&str) -> String + Send + Sync>;
+
 #[derive(Default)]
 pub struct RuntimeCompilationOpts {
     debug_info: Option<String>,
+    split_debuginfo: Option<String>,
+    opt_level: Option<String>,
+    lto: Option<String>,
+    panic: Option<String>,
+    overflow_checks: Option<bool>,
+    codegen_units: Option<u32>,
+    profile: Option<String>,
+    target: Option<String>,
+    runner: Option<Vec<String>>,
+    network_mode: Option<CargoNetworkMode>,
+    rustflags: Option<String>,
+    warn_dynamic_deps: bool,
+    list_command: Option<String>,
+    list_timeout: Option<Duration>,
+    compile_timeout: Option<Duration>,
+    force_rebuild: bool,
+    fail_fast: bool,
+    require_same_protocol_version: bool,
+    max_compile_time: Option<(Duration, CompileTimeHistory)>,
+    min_binary_size: Option<u64>,
+    max_binary_size: Option<u64>,
+    resume_file: Option<PathBuf>,
+    binary_cache: Option<PathBuf>,
+    name_transform: Option<NameTransform>,
+    log_dir: Option<PathBuf>,
+    max_disk_bytes: Option<u64>,
+    build_memory_limit: Option<u64>,
+    llvm_ir_size: bool,
+    max_parallel_groups: Option<usize>,
+    pub(super) recursive_discovery: bool,
+    pub(super) workspace: bool,
 }
 
 impl RuntimeCompilationOpts {
@@ -149,71 +914,482 @@ impl RuntimeCompilationOpts {
         self.debug_info = Some(debug_info.to_string());
         self
     }
-}
 
-/// Find all runtime benchmark crates in `benchmark_dir` and compile them.
-/// We assume that each binary defines a benchmark suite using `benchlib`.
-/// We then execute each benchmark suite with the `list-benchmarks` command to find out its
-/// benchmark names.
-///
-/// If `group` is not `None`, only the benchmark group with the given name will be compiled.
-pub fn prepare_runtime_benchmark_suite(
-    toolchain: &Toolchain,
-    benchmark_dir: &Path,
-    isolation_mode: CargoIsolationMode,
-    group: Option<String>,
-    opts: RuntimeCompilationOpts,
-) -> anyhow::Result<BenchmarkSuiteCompilation> {
-    let benchmark_crates = get_runtime_benchmark_groups(benchmark_dir, group)?;
+    /// Sets the `split-debuginfo` profile option (`off`, `packed` or `unpacked`).
+    /// Note that unless it is `off`, debuginfo is moved out of the benchmark binary, so the size
+    /// of the binary will not include it.
+    pub fn split_debuginfo(mut self, split_debuginfo: &str) -> Self {
+        self.split_debuginfo = Some(split_debuginfo.to_string());
+        self
+    }
 
-    let temp_dir: Option<TempDir> = match isolation_mode {
-        CargoIsolationMode::Cached => None,
-        CargoIsolationMode::Isolated => {
-            Some(
-                tempfile::Builder::new()
-                    // Make sure that we will always generate a directory with the same length.
-                    // As history shows us (https://users.cs.northwestern.edu/~robby/courses/322-2013-spring/mytkowicz-wrong-data.pdf),
-                    // even such small details can have unintended consequences.
-                    .rand_bytes(8)
-                    .tempdir()
-                    .context("Cannot create temporary directory")?,
-            )
-        }
-    };
+    /// Overrides the `opt-level` of the build profile (e.g. `2`, `3`, `s` or `z`), so that
+    /// the generated code can be compared between optimization levels.
+    pub fn opt_level(mut self, opt_level: &str) -> Self {
+        self.opt_level = Some(opt_level.to_string());
+        self
+    }
 
-    let group_count = benchmark_crates.len();
-    println!("Compiling {group_count} runtime benchmark group(s)");
+    /// Sets the `lto` option of the build profile (`off`, `thin`, `fat`, `true` or `false`).
+    /// Other values are rejected by `prepare_runtime_benchmark_suite`. The setting is recorded
+    /// in the metadata of runs of the compiled suite.
+    pub fn lto(mut self, lto: &str) -> Self {
+        self.lto = Some(lto.to_string());
+        self
+    }
 
-    let mut groups = Vec::new();
-    let mut failed_to_compile = HashMap::new();
-    for (index, benchmark_crate) in benchmark_crates.into_iter().enumerate() {
-        println!(
-            "Compiling {:<22} ({}/{group_count})",
-            format!("`{}`", benchmark_crate.name),
-            index + 1
-        );
+    /// Enables or disables the `overflow-checks` of the build profile, e.g. to measure their
+    /// cost. By default, the setting of the profile is kept. The setting is recorded in the
+    /// metadata of runs of the compiled suite.
+    pub fn overflow_checks(mut self, overflow_checks: bool) -> Self {
+        self.overflow_checks = Some(overflow_checks);
+        self
+    }
 
-        let target_dir = temp_dir.as_ref().map(|d| d.path());
+    /// Sets the `panic` strategy of the build profile (`unwind` or `abort`). Other values are
+    /// rejected by `prepare_runtime_benchmark_suite`.
+    pub fn panic(mut self, panic: &str) -> Self {
+        self.panic = Some(panic.to_string());
+        self
+    }
 
-        // Make sure that Cargo.lock isn't changed by the build if we're running in isolated mode
-        let _guard = match isolation_mode {
-            CargoIsolationMode::Cached => None,
-            CargoIsolationMode::Isolated => Some(EnsureImmutableFile::new(
-                &benchmark_crate.path.join("Cargo.lock"),
-                benchmark_crate.name.clone(),
-            )?),
+    /// Overrides the `codegen-units` of the build profile, which affects the quality of the
+    /// generated code.
+    pub fn codegen_units(mut self, codegen_units: u32) -> Self {
+        self.codegen_units = Some(codegen_units);
+        self
+    }
+
+    /// Compiles the benchmarks with the given Cargo profile instead of `release`, e.g. with a
+    /// custom profile that inherits from it. The profile overrides (e.g. `codegen_units`) are
+    /// applied to this profile.
+    pub fn profile(mut self, profile: &str) -> Self {
+        self.profile = Some(profile.to_string());
+        self
+    }
+
+    /// Returns the Cargo profile with which the benchmarks are compiled.
+    fn build_profile(&self) -> &str {
+        self.profile.as_deref().unwrap_or(DEFAULT_BUILD_PROFILE)
+    }
+
+    /// Compiles the benchmarks for the given target triple instead of the host. Such binaries
+    /// usually also need a `runner`.
+    pub fn target(mut self, target: &str) -> Self {
+        self.target = Some(target.to_string());
+        self
+    }
+
+    /// Executes the compiled benchmark binaries through the given command (a program followed by
+    /// its arguments, e.g. `qemu-aarch64 -L /usr/aarch64-linux-gnu`), both when listing their
+    /// benchmarks and when running them.
+    pub fn runner(mut self, runner: Vec<String>) -> Self {
+        self.runner = Some(runner);
+        self
+    }
+
+    /// Passes `--offline`, `--frozen` or `--locked` to Cargo, so that a build that would need
+    /// to access the network or to update `Cargo.lock` fails up front.
+    pub fn network_mode(mut self, mode: CargoNetworkMode) -> Self {
+        self.network_mode = Some(mode);
+        self
+    }
+
+    /// Passes additional flags to rustc (e.g. `-C target-feature=+avx2`) through `RUSTFLAGS`.
+    /// The flags are appended to the `RUSTFLAGS` of the collector, so for flags that can only be
+    /// specified once, they take precedence over it. Note that, as with any usage of `RUSTFLAGS`,
+    /// `build.rustflags` from Cargo configs is then ignored.
+    pub fn rustflags(mut self, rustflags: &str) -> Self {
+        self.rustflags = Some(rustflags.to_string());
+        self
+    }
+
+    /// Warns about compiled benchmark binaries that are not statically linked, which makes them
+    /// less portable across machines.
+    pub fn warn_dynamic_deps(mut self, warn: bool) -> Self {
+        self.warn_dynamic_deps = warn;
+        self
+    }
+
+    /// Sets the subcommand used to list the benchmarks of the compiled binaries, for binaries
+    /// whose CLI differs from the one of the current `benchlib`. Defaults to `list`.
+    pub fn list_command(mut self, list_command: &str) -> Self {
+        self.list_command = Some(list_command.to_string());
+        self
+    }
+
+    /// Kills the benchmark binaries if listing their benchmarks takes longer than `timeout`
+    /// (e.g. because of a hanging global constructor), and fails the compilation of their group.
+    pub fn list_timeout(mut self, timeout: Duration) -> Self {
+        self.list_timeout = Some(timeout);
+        self
+    }
+
+    /// Kills the build of a benchmark group if it takes longer than `timeout` (e.g. because of
+    /// a runaway build script), and fails the compilation of the group. Other groups are still
+    /// compiled.
+    pub fn compile_timeout(mut self, timeout: Duration) -> Self {
+        self.compile_timeout = Some(timeout);
+        self
+    }
+
+    /// Cleans the artifacts of each benchmark crate with `cargo clean` before building it, so that
+    /// it is rebuilt from scratch even in the cached mode.
+    pub fn force_rebuild(mut self, force_rebuild: bool) -> Self {
+        self.force_rebuild = force_rebuild;
+        self
+    }
+
+    /// Skips groups whose last compile time recorded in `history` exceeds `max`, without
+    /// compiling them. Groups without a recorded compile time are compiled, as they might be
+    /// fast to build.
+    pub fn max_compile_time(mut self, max: Duration, history: CompileTimeHistory) -> Self {
+        self.max_compile_time = Some((max, history));
+        self
+    }
+
+    /// Returns the error of the first group that fails to compile, instead of recording it in
+    /// `BenchmarkSuiteCompilation::failed_to_compile` and compiling the remaining groups.
+    /// Builds that are already in progress when the failure happens are still awaited.
+    pub fn fail_fast(mut self, fail_fast: bool) -> Self {
+        self.fail_fast = fail_fast;
+        self
+    }
+
+    /// Fails the compilation if the binaries of the groups report different versions of the
+    /// `benchlib` protocol, instead of only warning about it.
+    pub fn require_same_protocol_version(mut self, require: bool) -> Self {
+        self.require_same_protocol_version = require;
+        self
+    }
+
+    /// Only keeps groups whose binary has at least the given size (in bytes).
+    pub fn min_binary_size(mut self, size: u64) -> Self {
+        self.min_binary_size = Some(size);
+        self
+    }
+
+    /// Only keeps groups whose binary has at most the given size (in bytes).
+    pub fn max_binary_size(mut self, size: u64) -> Self {
+        self.max_binary_size = Some(size);
+        self
+    }
+
+    /// Records successfully compiled groups into the given file, and skips groups that have been
+    /// recorded there by a previous (e.g. interrupted) compilation and that have not changed since.
+    pub fn resume_file(mut self, path: PathBuf) -> Self {
+        self.resume_file = Some(path);
+        self
+    }
+
+    /// Reuses binaries from a content-addressed cache in `dir`, which is shared by all
+    /// invocations that use the same directory. A group is looked up by a hash of the sources of
+    /// its crate, of the toolchain (including the version of `rustc`) and of the compilation
+    /// options. If it is found, Cargo is not executed for it at all, otherwise the compiled binary
+    /// is copied into the cache.
+    ///
+    /// Unlike the cached isolation mode, this also works for isolated builds, and the reused
+    /// binaries cannot be affected by later builds in the target directory. The keys are only
+    /// stable for the same build of the collector, see `fingerprint_crate`. `force_rebuild`
+    /// bypasses the lookup, but still updates the cache.
+    pub fn binary_cache(mut self, dir: PathBuf) -> Self {
+        self.binary_cache = Some(dir);
+        self
+    }
+
+    /// Rewrites the names of benchmarks found in the compiled binaries (e.g. to strip a common
+    /// prefix). The transformed names are then used everywhere, i.e. for filtering, for checking
+    /// duplicates and in the results.
+    pub fn name_transform(mut self, transform: NameTransform) -> Self {
+        self.name_transform = Some(transform);
+        self
+    }
+
+    /// Writes the compilation output of each group into `<log_dir>/<group>.log` instead of
+    /// stdout.
+    pub fn log_dir(mut self, log_dir: PathBuf) -> Self {
+        self.log_dir = Some(log_dir);
+        self
+    }
+
+    /// Aborts the compilation if the temporary target directory used by isolated builds grows
+    /// beyond `max_bytes`. The size is checked after every `DISK_USAGE_CHECK_INTERVAL` compiled
+    /// groups and after the last one. Has no effect in the cached mode.
+    pub fn max_disk_bytes(mut self, max_bytes: u64) -> Self {
+        self.max_disk_bytes = Some(max_bytes);
+        self
+    }
+
+    /// Limits the address space of the Cargo process (and of the compiler processes that it
+    /// spawns) to `limit` bytes, so that a runaway build (e.g. a misbehaving proc-macro) fails
+    /// instead of exhausting the memory of the machine. Only supported on Unix, ignored
+    /// elsewhere.
+    pub fn build_memory_limit(mut self, limit: u64) -> Self {
+        self.build_memory_limit = Some(limit);
+        self
+    }
+
+    /// Measures the total size of the LLVM IR emitted for each group (including its
+    /// dependencies), as a proxy metric for codegen investigations.
+    ///
+    /// The IR is emitted by a separate build pass with `--emit=llvm-ir` into its own temporary
+    /// target directory, so that the measured benchmark binary is not affected by it. This
+    /// roughly doubles the compilation time.
+    pub fn llvm_ir_size(mut self, llvm_ir_size: bool) -> Self {
+        self.llvm_ir_size = llvm_ir_size;
+        self
+    }
+
+    /// Compiles up to `n` groups concurrently. By default, the groups are compiled one after
+    /// another, which keeps the build output readable.
+    ///
+    /// In the isolated mode, each concurrent build uses its own target directory, so the
+    /// dependencies shared by the groups are compiled once per build rather than once in total.
+    /// Consider using `log_dir`, otherwise the output of the concurrent builds is interleaved.
+    pub fn max_parallel_groups(mut self, n: usize) -> Self {
+        self.max_parallel_groups = Some(n);
+        self
+    }
+
+    /// Looks for benchmark crates also in nested subdirectories of the benchmark directory, so
+    /// that groups can be organized into categories (e.g. `numeric/fft`). See
+    /// `get_nested_runtime_benchmark_groups`.
+    pub fn recursive_discovery(mut self, recursive_discovery: bool) -> Self {
+        self.recursive_discovery = recursive_discovery;
+        self
+    }
+
+    /// Compiles all benchmark groups with a single `cargo build` invocation in the benchmark
+    /// directory, which must then be a Cargo workspace whose members are the benchmark crates.
+    /// The groups share the build of their common dependencies, which is usually much faster
+    /// than compiling each crate on its own, but they are no longer isolated from each other
+    /// (e.g. they use a single `Cargo.lock`). `max_parallel_groups` is ignored.
+    pub fn workspace(mut self, workspace: bool) -> Self {
+        self.workspace = workspace;
+        self
+    }
+
+    /// Values of the options that affect the compiled binaries. Both the keys of the resume file
+    /// and of the binary cache are computed from them.
+    fn fingerprint_inputs(&self, toolchain: &Toolchain) -> Vec<String> {
+        vec![
+            toolchain.id.clone(),
+            toolchain.components.rustc.display().to_string(),
+            self.debug_info.clone().unwrap_or_default(),
+            self.split_debuginfo.clone().unwrap_or_default(),
+            self.opt_level.clone().unwrap_or_default(),
+            self.lto.clone().unwrap_or_default(),
+            self.panic.clone().unwrap_or_default(),
+            self.overflow_checks
+                .map(|checks| checks.to_string())
+                .unwrap_or_default(),
+            self.codegen_units
+                .map(|units| units.to_string())
+                .unwrap_or_default(),
+            self.build_profile().to_string(),
+            self.target.clone().unwrap_or_default(),
+            self.runner.clone().unwrap_or_default().join(" "),
+            // Cargo also receives the `RUSTFLAGS` of the collector.
+            combined_rustflags(self),
+            requested_features().join(","),
+            self.list_command.clone().unwrap_or_default(),
+        ]
+    }
+}
+
+/// Find all runtime benchmark crates in `benchmark_dir` and compile them.
+/// We assume that each binary defines a benchmark suite using `benchlib`.
+/// We then execute each benchmark suite with the `list-benchmarks` command to find out its
+/// benchmark names.
+///
+/// Only the benchmark groups selected by `group` will be compiled.
+pub fn prepare_runtime_benchmark_suite(
+    toolchain: &Toolchain,
+    benchmark_dir: &Path,
+    isolation_mode: CargoIsolationMode,
+    group: GroupSelection,
+    opts: RuntimeCompilationOpts,
+) -> anyhow::Result<BenchmarkSuiteCompilation> {
+    let max_parallel_groups = opts.max_parallel_groups.unwrap_or(1);
+    if max_parallel_groups == 0 {
+        return Err(anyhow::anyhow!(
+            "The maximum number of runtime benchmark groups compiled in parallel must be at \
+least 1"
+        ));
+    }
+    check_profile_value("LTO setting", opts.lto.as_deref(), LTO_VALUES)?;
+    check_profile_value("panic strategy", opts.panic.as_deref(), PANIC_VALUES)?;
+    if opts.workspace && !benchmark_dir.join("Cargo.toml").is_file() {
+        return Err(anyhow::anyhow!(
+            "Runtime benchmark directory `{}` is not a Cargo workspace, it has no Cargo.toml",
+            benchmark_dir.display()
+        ));
+    }
+    let discovery_start = Instant::now();
+    let mut benchmark_crates = match discover_groups(benchmark_dir, group, opts.recursive_discovery)
+    {
+        Ok(crates) => crates,
+        Err(error @ DiscoveryError::DirNotFound(_)) => {
+            return Err(anyhow::anyhow!(
+                "{error}. Make sure that the collector is executed from the root of the \
+rustc-perf repository."
+            ));
+        }
+        Err(error) => return Err(error.into()),
+    };
+    if let Some((max, ref history)) = opts.max_compile_time {
+        retain_by_compile_time(&mut benchmark_crates, max, history);
+    }
+    let discovery = discovery_start.elapsed();
+    let compilation_start = Instant::now();
+
+    let temp_dir: Option<TempDir> = match isolation_mode {
+        CargoIsolationMode::Cached => None,
+        CargoIsolationMode::Isolated => {
+            Some(
+                tempfile::Builder::new()
+                    // Make sure that we will always generate a directory with the same length.
+                    // As history shows us (https://users.cs.northwestern.edu/~robby/courses/322-2013-spring/mytkowicz-wrong-data.pdf),
+                    // even such small details can have unintended consequences.
+                    .rand_bytes(8)
+                    .tempdir()
+                    .context("Cannot create temporary directory")?,
+            )
+        }
+    };
+
+    let list_command = opts.list_command.as_deref().unwrap_or(DEFAULT_LIST_COMMAND);
+    if let Some(ref log_dir) = opts.log_dir {
+        std::fs::create_dir_all(log_dir)
+            .with_context(|| format!("Cannot create log directory {}", log_dir.display()))?;
+    }
+    let mut resume = opts
+        .resume_file
+        .as_deref()
+        .map(CompilationResume::open)
+        .transpose()?;
+    let fingerprint_inputs = opts.fingerprint_inputs(toolchain);
+    let binary_cache = opts
+        .binary_cache
+        .as_deref()
+        .map(BinaryCache::open)
+        .transpose()?;
+    let cache_inputs = match binary_cache {
+        Some(_) => {
+            let mut inputs = fingerprint_inputs.clone();
+            inputs.extend(toolchain_cache_inputs(toolchain)?);
+            inputs
+        }
+        None => vec![],
+    };
+    let fingerprint_inputs: Vec<&str> = fingerprint_inputs.iter().map(|s| s.as_str()).collect();
+    let cache_inputs: Vec<&str> = cache_inputs.iter().map(|s| s.as_str()).collect();
+
+    let group_count = benchmark_crates.len();
+    println!("Compiling {group_count} runtime benchmark group(s)");
+
+    let mut groups = Vec::new();
+    let mut to_compile = Vec::new();
+    // Counted when it is decided whether a group is reused (from the resume state, the binary
+    // cache or by Cargo, which reports an up-to-date binary as fresh) or rebuilt.
+    let mut cached_groups = 0;
+    let mut rebuilt_groups = 0;
+    for (index, benchmark_crate) in benchmark_crates.into_iter().enumerate() {
+        let fingerprint = if resume.is_some() {
+            Some(fingerprint_crate(
+                &benchmark_crate.path,
+                &fingerprint_inputs,
+            )?)
+        } else {
+            None
         };
-        let result = start_cargo_build(toolchain, &benchmark_crate.path, target_dir, &opts)
-            .with_context(|| {
-                anyhow::anyhow!("Cannot start compilation of {}", benchmark_crate.name)
-            })
-            .and_then(|iter| {
-                parse_benchmark_group(iter, &benchmark_crate.name).with_context(|| {
-                    anyhow::anyhow!("Cannot compile runtime benchmark {}", benchmark_crate.name)
+        let cache_key = if binary_cache.is_some() {
+            Some(fingerprint_crate(&benchmark_crate.path, &cache_inputs)?)
+        } else {
+            None
+        };
+        let reused = if opts.force_rebuild {
+            None
+        } else {
+            resume
+                .as_ref()
+                .zip(fingerprint)
+                .and_then(|(resume, fingerprint)| {
+                    resume.compiled_group(&benchmark_crate.name, fingerprint)
                 })
-            });
-        match result {
-            Ok(group) => groups.push(group),
+                .or_else(|| {
+                    binary_cache
+                        .as_ref()
+                        .zip(cache_key)
+                        .and_then(|(cache, key)| {
+                            cache.cached_group(
+                                &benchmark_crate.name,
+                                key,
+                                opts.name_transform.as_deref(),
+                            )
+                        })
+                })
+        };
+        if let Some(mut group) = reused {
+            group.tags = benchmark_crate.tags.clone();
+            println!(
+                "Reusing   {:<22} ({}/{group_count})",
+                format!("`{}`", benchmark_crate.name),
+                index + 1
+            );
+            cached_groups += 1;
+            groups.push(group);
+            continue;
+        }
+        to_compile.push((index, benchmark_crate, fingerprint, cache_key));
+    }
+
+    let mut failed_to_compile = HashMap::new();
+    let mut build_timings = Vec::new();
+    let build_count = to_compile.len();
+    // Records the outcome of a single build. Called on the main thread in the order in which the
+    // builds finish.
+    let mut finish_build = |benchmark_crate: &BenchmarkGroupCrate,
+                            fingerprint: Option<u64>,
+                            cache_key: Option<u64>,
+                            build: GroupBuild|
+     -> anyhow::Result<()> {
+        build_timings.push(build.timing);
+        // Walking the whole target directory after each build would take quadratic time in the
+        // number of groups, so it is only checked periodically and after the last build.
+        let finished = build_timings.len();
+        if finished % DISK_USAGE_CHECK_INTERVAL == 0 || finished == build_count {
+            if let (Some(max_bytes), Some(temp_dir)) = (opts.max_disk_bytes, &temp_dir) {
+                check_disk_usage(temp_dir.path(), max_bytes, &benchmark_crate.name)?;
+            }
+        }
+        match build.result {
+            Ok(mut group) => {
+                if group.cached {
+                    cached_groups += 1;
+                } else {
+                    rebuilt_groups += 1;
+                }
+                group.tags = benchmark_crate.tags.clone();
+                if opts.warn_dynamic_deps {
+                    warn_about_dynamic_deps(&group);
+                }
+                if let (Some(resume), Some(fingerprint)) = (&mut resume, fingerprint) {
+                    resume.record(&group, fingerprint)?;
+                }
+                if let (Some(cache), Some(key)) = (&binary_cache, cache_key) {
+                    // A failure to fill the cache only slows down the next compilation.
+                    if let Err(error) = cache.store(&group, key) {
+                        log::warn!(
+                            "Cannot store runtime benchmark group `{}` in the binary cache: \
+{error:?}",
+                            benchmark_crate.name
+                        );
+                    }
+                }
+                groups.push(group);
+            }
+            Err(error) if opts.fail_fast => return Err(error),
             Err(error) => {
                 log::error!(
                     "Cannot compile runtime benchmark group `{}` {error:?}",
@@ -225,41 +1401,371 @@ pub fn prepare_runtime_benchmark_suite(
                 );
             }
         }
+        Ok(())
+    };
+
+    let jobs = max_parallel_groups.min(to_compile.len().max(1));
+    if opts.workspace {
+        if !to_compile.is_empty() {
+            let crates: Vec<&BenchmarkGroupCrate> = to_compile
+                .iter()
+                .map(|(_, benchmark_crate, _, _)| benchmark_crate)
+                .collect();
+            let builds = build_workspace(
+                toolchain,
+                benchmark_dir,
+                &crates,
+                isolation_mode,
+                temp_dir.as_ref().map(|d| d.path()),
+                list_command,
+                &opts,
+            )?;
+            for ((_, benchmark_crate, fingerprint, cache_key), build) in
+                to_compile.iter().zip(builds)
+            {
+                finish_build(benchmark_crate, *fingerprint, *cache_key, build)?;
+            }
+        }
+    } else if jobs == 1 {
+        let target_dir = temp_dir.as_ref().map(|d| d.path());
+        for (index, benchmark_crate, fingerprint, cache_key) in &to_compile {
+            let build = build_group(
+                toolchain,
+                benchmark_crate,
+                (*index, group_count),
+                isolation_mode,
+                target_dir,
+                list_command,
+                &opts,
+            )?;
+            finish_build(benchmark_crate, *fingerprint, *cache_key, build)?;
+        }
+    } else {
+        let next = AtomicUsize::new(0);
+        let abort = AtomicBool::new(false);
+        std::thread::scope(|scope| -> anyhow::Result<()> {
+            let (tx, rx) = mpsc::channel();
+            for worker in 0..jobs {
+                let tx = tx.clone();
+                let (next, abort, to_compile, opts) = (&next, &abort, &to_compile, &opts);
+                // Concurrent builds would block each other on the lock of a shared target
+                // directory, so each worker uses its own.
+                let target_dir = temp_dir
+                    .as_ref()
+                    .map(|d| d.path().join(format!("worker-{worker}")));
+                scope.spawn(move || {
+                    while !abort.load(Ordering::Relaxed) {
+                        let i = next.fetch_add(1, Ordering::Relaxed);
+                        let Some((index, benchmark_crate, _, _)) = to_compile.get(i) else {
+                            break;
+                        };
+                        let build = build_group(
+                            toolchain,
+                            benchmark_crate,
+                            (*index, group_count),
+                            isolation_mode,
+                            target_dir.as_deref(),
+                            list_command,
+                            opts,
+                        );
+                        if tx.send((i, build)).is_err() {
+                            break;
+                        }
+                    }
+                });
+            }
+            drop(tx);
+
+            for (i, build) in rx {
+                let (_, benchmark_crate, fingerprint, cache_key) = &to_compile[i];
+                let result = build.and_then(|build| {
+                    finish_build(benchmark_crate, *fingerprint, *cache_key, build)
+                });
+                if result.is_err() {
+                    // Do not start any further builds, the ones in progress are still awaited.
+                    abort.store(true, Ordering::Relaxed);
+                    return result;
+                }
+            }
+            Ok(())
+        })?;
+        // Keep the timings in the order in which the builds have started.
+        build_timings.sort_by_key(|timing| timing.start);
     }
 
-    groups.sort_unstable_by(|a, b| a.binary.cmp(&b.binary));
+    retain_by_binary_size(&mut groups, opts.min_binary_size, opts.max_binary_size);
+    sort_canonically(&mut groups);
     log::debug!("Found binaries: {:?}", groups);
 
     check_duplicates(&groups)?;
+    if let Some(mismatch) = protocol_version_mismatch(&groups) {
+        if opts.require_same_protocol_version {
+            return Err(anyhow::anyhow!(mismatch));
+        }
+        log::warn!("{mismatch}");
+    }
+
+    println!("Rebuilt {rebuilt_groups} runtime benchmark group(s), reused {cached_groups}");
 
     Ok(BenchmarkSuiteCompilation {
         suite: BenchmarkSuite {
             toolchain: toolchain.clone(),
             groups,
             _tmp_artifacts_dir: temp_dir,
+            phase_timings: PhaseTimings {
+                discovery,
+                compilation: compilation_start.elapsed(),
+                running: Duration::ZERO,
+            },
+            compile_settings: CompileSettings {
+                lto: opts.lto.clone(),
+                overflow_checks: opts.overflow_checks,
+                runner: opts.runner.clone(),
+            },
         },
         failed_to_compile,
+        rebuilt_groups,
+        cached_groups,
+        build_timings,
     })
 }
 
+/// Assembles a suite from binaries that were already compiled into `target_dir` (e.g. by a
+/// previous build in the cached mode), without rebuilding them. The binary of each benchmark
+/// crate in `dir` is found by its name reported by `cargo metadata`, and its benchmarks are
+/// listed by executing it.
+///
+/// `opts` should be the options with which the binaries were compiled. They determine where the
+/// binaries are located (e.g. `target`), how they are executed (e.g. `runner`), and the compile
+/// settings recorded for the suite. A group is only reported as cached if its binary is newer
+/// than all the sources of its crate, see `binary_is_current`.
+pub fn suite_from_target_dir(
+    toolchain: &Toolchain,
+    dir: &Path,
+    target_dir: &Path,
+    opts: &RuntimeCompilationOpts,
+) -> anyhow::Result<BenchmarkSuite> {
+    let benchmark_crates = discover_groups(dir, GroupSelection::all(), opts.recursive_discovery)?;
+    let list_command = opts.list_command.as_deref().unwrap_or(DEFAULT_LIST_COMMAND);
+    // Cargo puts the binaries of a `--target` build into a subdirectory named by the triple.
+    let profile_dir = match opts.target {
+        Some(ref target) => target_dir.join(target),
+        None => target_dir.to_path_buf(),
+    }
+    .join(profile_dir_name(opts.build_profile()));
+    let mut groups = Vec::with_capacity(benchmark_crates.len());
+    for benchmark_crate in benchmark_crates {
+        let binary_name = read_binary_name(toolchain, &benchmark_crate.path)?;
+        let binary = profile_dir.join(binary_file_name(&binary_name, opts.target.as_deref()));
+        if !binary.is_file() {
+            return Err(anyhow::anyhow!(
+                "Binary of runtime benchmark group `{}` not found at {}",
+                benchmark_crate.name,
+                binary.display()
+            ));
+        }
+        let cached = binary_is_current(&benchmark_crate, &binary)?;
+        if !cached {
+            log::warn!(
+                "Binary of runtime benchmark group `{}` is older than its sources",
+                benchmark_crate.name
+            );
+        }
+        let mut group = group_from_binary(
+            binary,
+            &benchmark_crate.name,
+            list_command,
+            opts.list_timeout,
+            opts.runner.as_deref().unwrap_or_default(),
+            opts.name_transform.as_deref(),
+        )?;
+        group.cached = cached;
+        group.tags = benchmark_crate.tags;
+        groups.push(group);
+    }
+    check_duplicates(&groups)?;
+    let mut suite = BenchmarkSuite::from_groups(toolchain.clone(), groups);
+    suite.compile_settings = CompileSettings {
+        lto: opts.lto.clone(),
+        overflow_checks: opts.overflow_checks,
+        runner: opts.runner.clone(),
+    };
+    Ok(suite)
+}
+
+/// Returns the file name of the binary target `name` when it is compiled for `target` (or the
+/// host, if it is `None`).
+fn binary_file_name(name: &str, target: Option<&str>) -> String {
+    let windows = match target {
+        Some(target) => target.contains("windows"),
+        None => cfg!(windows),
+    };
+    if windows {
+        format!("{name}.exe")
+    } else {
+        name.to_string()
+    }
+}
+
+/// Returns the name of the binary target of the crate in `path`, according to `cargo metadata`.
+fn read_binary_name(toolchain: &Toolchain, path: &Path) -> anyhow::Result<String> {
+    let mut command = Command::new(&toolchain.components.cargo);
+    command
+        .arg("metadata")
+        .arg("--no-deps")
+        .arg("--format-version")
+        .arg("1")
+        .current_dir(path);
+    let output = command_output(&mut command)
+        .with_context(|| format!("Cannot read metadata of crate {}", path.display()))?;
+    let metadata: serde_json::Value = serde_json::from_slice(&output.stdout)
+        .with_context(|| format!("Cannot parse metadata of crate {}", path.display()))?;
+
+    let mut binaries = metadata["packages"]
+        .as_array()
+        .into_iter()
+        .flatten()
+        .flat_map(|package| package["targets"].as_array().into_iter().flatten())
+        .filter(|target| {
+            target["kind"]
+                .as_array()
+                .is_some_and(|kinds| kinds.iter().any(|kind| kind == "bin"))
+        })
+        .filter_map(|target| target["name"].as_str());
+    match (binaries.next(), binaries.next()) {
+        (Some(name), None) => Ok(name.to_string()),
+        (None, _) => Err(anyhow::anyhow!(
+            "Crate {} does not contain any binary",
+            path.display()
+        )),
+        (Some(_), Some(_)) => Err(anyhow::anyhow!(
+            "Crate {} contains multiple binaries",
+            path.display()
+        )),
+    }
+}
+
+/// Returns false if any file of the benchmark crate (except for its `target` directory) has been
+/// modified after `binary`, which suggests that the binary is stale. This is only a heuristic
+/// based on modification times, see `fingerprint_crate` for a reliable check.
+pub fn binary_is_current(group_crate: &BenchmarkGroupCrate, binary: &Path) -> anyhow::Result<bool> {
+    let binary_mtime = std::fs::metadata(binary)
+        .and_then(|metadata| metadata.modified())
+        .with_context(|| format!("Cannot read modification time of {}", binary.display()))?;
+    for entry in walkdir::WalkDir::new(&group_crate.path)
+        .into_iter()
+        .filter_entry(|entry| entry.depth() != 1 || entry.file_name() != "target")
+    {
+        let entry = entry?;
+        if !entry.file_type().is_file() {
+            continue;
+        }
+        let mtime = entry.metadata()?.modified().with_context(|| {
+            format!(
+                "Cannot read modification time of {}",
+                entry.path().display()
+            )
+        })?;
+        if mtime > binary_mtime {
+            log::debug!(
+                "{} is newer than the binary {}",
+                entry.path().display(),
+                binary.display()
+            );
+            return Ok(false);
+        }
+    }
+    Ok(true)
+}
+
+fn warn_about_dynamic_deps(group: &BenchmarkGroup) {
+    match binary_dynamic_deps(&group.binary) {
+        Ok(deps) if !deps.is_empty() => log::warn!(
+            "Runtime benchmark group `{}` is dynamically linked to {}",
+            group.name,
+            deps.join(", ")
+        ),
+        Ok(_) => {}
+        Err(error) => log::warn!(
+            "Cannot find dynamic dependencies of runtime benchmark group `{}`: {error:?}",
+            group.name
+        ),
+    }
+}
+
+/// Removes crates whose recorded compile time is longer than `max`. Crates with an unknown
+/// compile time are kept.
+fn retain_by_compile_time(
+    crates: &mut Vec<BenchmarkGroupCrate>,
+    max: Duration,
+    history: &CompileTimeHistory,
+) {
+    crates.retain(
+        |benchmark_crate| match history.compile_time(&benchmark_crate.name) {
+            Some(compile_time) if compile_time > max => {
+                log::info!(
+                    "Skipping runtime benchmark group `{}` with compile time {compile_time:?}",
+                    benchmark_crate.name
+                );
+                false
+            }
+            _ => true,
+        },
+    );
+}
+
+/// Removes groups whose binary size is outside of the `[min, max]` range.
+fn retain_by_binary_size(groups: &mut Vec<BenchmarkGroup>, min: Option<u64>, max: Option<u64>) {
+    groups.retain(|group| {
+        let keep = min.is_none_or(|min| group.binary_size >= min)
+            && max.is_none_or(|max| group.binary_size <= max);
+        if !keep {
+            log::info!(
+                "Skipping runtime benchmark group `{}` with binary size {} B",
+                group.name,
+                group.binary_size
+            );
+        }
+        keep
+    });
+}
+
 /// Checks if there are no duplicate runtime benchmark names.
 fn check_duplicates(groups: &[BenchmarkGroup]) -> anyhow::Result<()> {
-    let mut benchmark_to_group_name: HashMap<&str, &str> = HashMap::new();
+    // Maps each benchmark name to the groups that define it, with the number of occurrences in
+    // each group, in the order of the groups.
+    let mut occurrences: BTreeMap<&str, Vec<(&str, usize)>> = BTreeMap::new();
     for group in groups {
         for benchmark in &group.benchmark_names {
-            let benchmark_name = benchmark.as_str();
-            let group_name = group.name.as_str();
-            if let Some(previous_group) = benchmark_to_group_name.get(benchmark_name) {
-                return Err(anyhow::anyhow!(
-                    "Duplicated benchmark name: runtime benchmark `{benchmark_name}` defined both in `{}` and in `{}`",
-                    previous_group,
-                    group_name
-                ));
+            let defined_in = occurrences.entry(benchmark.as_str()).or_default();
+            match defined_in.last_mut() {
+                Some((name, count)) if *name == group.name => *count += 1,
+                _ => defined_in.push((group.name.as_str(), 1)),
             }
-
-            benchmark_to_group_name.insert(benchmark_name, group_name);
         }
     }
+
+    // Results are stored by benchmark names, so all but one of the measurements would be lost.
+    let duplicates: Vec<String> = occurrences
+        .into_iter()
+        .filter(|(_, groups)| groups.len() > 1 || groups[0].1 > 1)
+        .map(|(benchmark, groups)| {
+            let groups: Vec<String> = groups
+                .into_iter()
+                .map(|(group, count)| match count {
+                    1 => format!("`{group}`"),
+                    count => format!("`{group}` ({count} times)"),
+                })
+                .collect();
+            format!("`{benchmark}` in {}", groups.join(", "))
+        })
+        .collect();
+    if !duplicates.is_empty() {
+        return Err(anyhow::anyhow!(
+            "Duplicated runtime benchmark names:\n{}",
+            duplicates.join("\n")
+        ));
+    }
     Ok(())
 }
 
@@ -268,38 +1774,46 @@ fn check_duplicates(groups: &[BenchmarkGroup]) -> anyhow::Result<()> {
 fn parse_benchmark_group(
     mut cargo_iter: CargoArtifactIter,
     group_name: &str,
+    list_command: &str,
+    list_timeout: Option<Duration>,
+    compile_timeout: Option<Duration>,
+    runner: &[String],
+    name_transform: Option<&dyn Fn(&str) -> String>,
 ) -> anyhow::Result<BenchmarkGroup> {
     let mut group: Option<BenchmarkGroup> = None;
 
-    for artifact in &mut cargo_iter {
-        let artifact = artifact?;
-        if let Some(ref executable) = artifact.executable {
-            // Found a binary compiled by a runtime benchmark crate.
-            // Execute it so that we find all the benchmarks it contains.
-            if artifact.target.is_bin() {
-                if group.is_some() {
-                    return Err(anyhow::anyhow!(
-                        "Runtime benchmark group `{group_name}` has produced multiple binaries"
-                    ));
-                }
-
-                let path = executable.as_std_path().to_path_buf();
-                let benchmarks = gather_benchmarks(&path).map_err(|err| {
-                    anyhow::anyhow!(
-                        "Cannot gather benchmarks from `{}`: {err:?}",
-                        path.display()
-                    )
-                })?;
-                log::info!("Compiled {}", path.display());
-
-                group = Some(BenchmarkGroup {
-                    binary: path,
-                    name: group_name.to_string(),
-                    benchmark_names: benchmarks,
-                });
+    if let Some(timeout) = compile_timeout {
+        cargo_iter = cargo_iter.kill_after(timeout);
+    }
+    while let Some(artifact) = cargo_iter.next() {
+        let artifact = match artifact {
+            Ok(artifact) => artifact,
+            // The message stream was cut off by killing Cargo.
+            Err(_) if cargo_iter.timed_out() => break,
+            Err(error) => return Err(error),
+        };
+        // Found a binary compiled by a runtime benchmark crate.
+        if artifact.executable.is_some() && artifact.target.is_bin() {
+            if group.is_some() {
+                return Err(anyhow::anyhow!(
+                    "Runtime benchmark group `{group_name}` has produced multiple binaries"
+                ));
             }
+            group = Some(group_from_artifact(
+                &artifact,
+                group_name,
+                list_command,
+                list_timeout,
+                runner,
+                name_transform,
+            )?);
         }
     }
+    if let Some(timeout) = compile_timeout.filter(|_| cargo_iter.timed_out()) {
+        return Err(anyhow::anyhow!(
+            "Compilation of runtime benchmark group `{group_name}` timed out after {timeout:?}"
+        ));
+    }
     cargo_iter
         .finish()
         .with_context(|| format!("Failed to compile runtime benchmark `{group_name}`"))?;
@@ -309,77 +1823,3216 @@ fn parse_benchmark_group(
     Ok(group)
 }
 
-/// Starts the compilation of a single runtime benchmark crate.
-/// Returns the stdout output stream of Cargo.
-fn start_cargo_build(
-    toolchain: &Toolchain,
-    benchmark_dir: &Path,
-    target_dir: Option<&Path>,
-    opts: &RuntimeCompilationOpts,
-) -> anyhow::Result<CargoArtifactIter> {
-    let mut command = Command::new(&toolchain.components.cargo);
-    command
-        .env("RUSTC", &toolchain.components.rustc)
-        .arg("build")
-        .arg("--release")
-        .current_dir(benchmark_dir);
-
-    if let Some(ref debug_info) = opts.debug_info {
-        command.env("CARGO_PROFILE_RELEASE_DEBUG", debug_info);
-    }
-
-    if let Some(target_dir) = target_dir {
-        command.arg("--target-dir");
-        command.arg(target_dir);
-    }
+/// Creates a benchmark group from the binary reported by a Cargo artifact message. The binary is
+/// executed so that we find all the benchmarks it contains.
+fn group_from_artifact(
+    artifact: &cargo_metadata::Artifact,
+    group_name: &str,
+    list_command: &str,
+    list_timeout: Option<Duration>,
+    runner: &[String],
+    name_transform: Option<&dyn Fn(&str) -> String>,
+) -> anyhow::Result<BenchmarkGroup> {
+    // With `--target`, Cargo reports the executable in `target/<triple>/release`.
+    let path = artifact
+        .executable
+        .as_ref()
+        .ok_or_else(|| anyhow::anyhow!("Runtime benchmark group `{group_name}` has no executable"))?
+        .as_std_path()
+        .to_path_buf();
+    let mut group = group_from_binary(
+        path,
+        group_name,
+        list_command,
+        list_timeout,
+        runner,
+        name_transform,
+    )?;
+    log::info!("Compiled {}", group.binary.display());
+    group.features.extend(artifact.features.iter().cloned());
+    group.cached = artifact.fresh;
+    Ok(group)
+}
 
-    // Enable the precise-cachegrind feature for the benchlib dependency of the runtime group.
-    #[cfg(feature = "precise-cachegrind")]
-    command.arg("--features").arg("benchlib/precise-cachegrind");
+/// Creates a benchmark group from a compiled benchmark binary, by executing it to find out the
+/// benchmarks that it contains.
+fn group_from_binary(
+    path: PathBuf,
+    group_name: &str,
+    list_command: &str,
+    list_timeout: Option<Duration>,
+    runner: &[String],
+    name_transform: Option<&dyn Fn(&str) -> String>,
+) -> anyhow::Result<BenchmarkGroup> {
+    let (benchmarks, protocol_version) =
+        gather_benchmarks(&path, runner, list_command, list_timeout).map_err(|err| {
+            anyhow::anyhow!(
+                "Cannot gather benchmarks of group `{group_name}` from `{}`: {err:?}",
+                path.display()
+            )
+        })?;
+    check_reserved_names(group_name, &benchmarks, list_command)?;
+    let (benchmarks, original_names) = transform_names(benchmarks, name_transform);
 
-    for config in &toolchain.components.cargo_configs {
-        command.arg("--config").arg(config);
-    }
+    let binary_size = std::fs::metadata(&path)
+        .with_context(|| format!("Cannot read metadata of {}", path.display()))?
+        .len();
 
-    CargoArtifactIter::from_cargo_cmd(command)
-        .map_err(|error| anyhow::anyhow!("Failed to start cargo: {:?}", error))
+    Ok(BenchmarkGroup {
+        binary: path,
+        name: group_name.to_string(),
+        benchmark_names: benchmarks,
+        features: requested_features(),
+        cached: false,
+        binary_size,
+        original_names,
+        tags: vec![],
+        llvm_ir_size: None,
+        protocol_version,
+    })
 }
 
-/// Uses a command from `benchlib` to find the benchmark names from the given
-/// benchmark binary.
-fn gather_benchmarks(binary: &Path) -> anyhow::Result<Vec<String>> {
-    let output = Command::new(binary).arg("list").output()?;
-    Ok(serde_json::from_slice(&output.stdout)?)
+/// Gathers the benchmark groups of all `crates` from the message stream of a single Cargo build
+/// of a workspace. Each binary is mapped back to its group by the directory of the manifest of
+/// the package that produced it.
+///
+/// The returned builds are in the same order as `crates`. The build of a group is considered
+/// finished when Cargo reports its binary. If the build fails, the groups whose binaries were not
+/// reported fail with its error.
+fn parse_workspace_groups(
+    mut cargo_iter: CargoArtifactIter,
+    crates: &[&BenchmarkGroupCrate],
+    start: Instant,
+    list_command: &str,
+    opts: &RuntimeCompilationOpts,
+) -> Vec<GroupBuild> {
+    let canonical = |path: &Path| path.canonicalize().unwrap_or_else(|_| path.to_path_buf());
+    let crate_dirs: Vec<PathBuf> = crates.iter().map(|c| canonical(&c.path)).collect();
+    let mut results: Vec<Option<(anyhow::Result<BenchmarkGroup>, Instant)>> =
+        crates.iter().map(|_| None).collect();
+
+    if let Some(timeout) = opts.compile_timeout {
+        cargo_iter = cargo_iter.kill_after(timeout);
+    }
+    let mut stream_error = None;
+    while let Some(artifact) = cargo_iter.next() {
+        let artifact = match artifact {
+            Ok(artifact) => artifact,
+            Err(_) if cargo_iter.timed_out() => break,
+            Err(error) => {
+                stream_error = Some(error);
+                break;
+            }
+        };
+        if artifact.executable.is_none() || !artifact.target.is_bin() {
+            continue;
+        }
+        let manifest_dir = artifact
+            .manifest_path
+            .parent()
+            .map(|dir| canonical(dir.as_std_path()));
+        let Some(index) = crate_dirs
+            .iter()
+            .position(|dir| Some(dir) == manifest_dir.as_ref())
+        else {
+            log::warn!(
+                "Ignoring binary `{}` that does not belong to any runtime benchmark group",
+                artifact.target.name
+            );
+            continue;
+        };
+        let group_name = &crates[index].name;
+        let result = match results[index] {
+            Some(_) => Err(anyhow::anyhow!(
+                "Runtime benchmark group `{group_name}` has produced multiple binaries"
+            )),
+            None => group_from_artifact(
+                &artifact,
+                group_name,
+                list_command,
+                opts.list_timeout,
+                opts.runner.as_deref().unwrap_or_default(),
+                opts.name_transform.as_deref(),
+            ),
+        };
+        results[index] = Some((result, Instant::now()));
+    }
+
+    let build_error = if let Some(error) = stream_error {
+        Some(error)
+    } else if let Some(timeout) = opts.compile_timeout.filter(|_| cargo_iter.timed_out()) {
+        Some(anyhow::anyhow!(
+            "Compilation of the runtime benchmark workspace timed out after {timeout:?}"
+        ))
+    } else {
+        cargo_iter
+            .finish()
+            .context("Failed to compile the runtime benchmark workspace")
+            .err()
+    };
+    let end = Instant::now();
+    crates
+        .iter()
+        .zip(results)
+        .map(|(benchmark_crate, result)| {
+            let (result, end) = match result {
+                Some((result, end)) => (result, end),
+                None => {
+                    let error = match build_error {
+                        Some(ref error) => anyhow::anyhow!("{error:?}"),
+                        None => anyhow::anyhow!("The group has not produced any binary"),
+                    };
+                    let error = error.context(format!(
+                        "Cannot compile runtime benchmark {}",
+                        benchmark_crate.name
+                    ));
+                    (Err(error), end)
+                }
+            };
+            GroupBuild {
+                result,
+                timing: GroupBuildTiming {
+                    group: benchmark_crate.name.clone(),
+                    start,
+                    end,
+                },
+            }
+        })
+        .collect()
 }
 
-/// Finds all runtime benchmarks (crates) in the given directory.
-pub fn get_runtime_benchmark_groups(
-    directory: &Path,
-    group: Option<String>,
-) -> anyhow::Result<Vec<BenchmarkGroupCrate>> {
-    let mut groups = Vec::new();
-    for entry in std::fs::read_dir(directory).with_context(|| {
-        anyhow::anyhow!("Failed to list benchmark dir '{}'", directory.display())
-    })? {
+/// Subcommands of the `benchlib` CLI of benchmark binaries.
+const RESERVED_NAMES: &[&str] = &["run", "profile", "list", "protocol-version"];
+
+/// Checks that no benchmark of the group is named like a subcommand of the benchmark binary
+/// (including a custom `list_command`), because the name would then be ambiguous when passed
+/// to the binary on the command line.
+fn check_reserved_names(
+    group_name: &str,
+    benchmarks: &[String],
+    list_command: &str,
+) -> anyhow::Result<()> {
+    if let Some(name) = benchmarks
+        .iter()
+        .find(|name| RESERVED_NAMES.contains(&name.as_str()) || *name == list_command)
+    {
+        return Err(anyhow::anyhow!(
+            "Runtime benchmark `{name}` of group `{group_name}` is named like a subcommand of \
+benchmark binaries, please rename it"
+        ));
+    }
+    Ok(())
+}
+
+/// Applies the name transform to the given benchmark names. Returns the transformed names and
+/// a map from the transformed names to the original ones.
+pub(super) fn transform_names(
+    benchmarks: Vec<String>,
+    name_transform: Option<&dyn Fn(&str) -> String>,
+) -> (Vec<String>, HashMap<String, String>) {
+    let Some(transform) = name_transform else {
+        return (benchmarks, HashMap::new());
+    };
+    let mut original_names = HashMap::new();
+    let benchmarks = benchmarks
+        .into_iter()
+        .map(|original| {
+            let name = transform(&original);
+            original_names.insert(name.clone(), original);
+            name
+        })
+        .collect();
+    (benchmarks, original_names)
+}
+
+/// How many groups are compiled between two checks of `RuntimeCompilationOpts::max_disk_bytes`.
+const DISK_USAGE_CHECK_INTERVAL: usize = 8;
+
+/// Checks that the size of the files in `target_dir` does not exceed `max_bytes`, after compiling
+/// the given group. Returns the size.
+fn check_disk_usage(target_dir: &Path, max_bytes: u64, group: &str) -> anyhow::Result<u64> {
+    let mut usage = 0;
+    for entry in walkdir::WalkDir::new(target_dir) {
         let entry = entry?;
-        let path = entry.path();
-        if !entry.file_type()?.is_dir() || !path.join("Cargo.toml").is_file() {
-            continue;
+        if entry.file_type().is_file() {
+            usage += entry.metadata()?.len();
         }
-        let name = path
-            .file_name()
-            .and_then(|v| v.to_str())
-            .ok_or_else(|| anyhow::anyhow!("Cannot get filename of {}", path.display()))?
-            .to_string();
+    }
+    log::debug!("Isolated builds use {usage} bytes after compiling `{group}`");
+    if usage > max_bytes {
+        return Err(anyhow::anyhow!(
+            "Isolated builds use {usage} bytes after compiling `{group}`, which exceeds the limit \
+of {max_bytes} bytes"
+        ));
+    }
+    Ok(usage)
+}
+
+/// Outcome of building a single runtime benchmark group.
+struct GroupBuild {
+    result: anyhow::Result<BenchmarkGroup>,
+    timing: GroupBuildTiming,
+}
+
+/// Builds a single runtime benchmark crate and gathers its benchmarks. `position` is the index of
+/// the crate and the total number of crates, for reporting progress.
+///
+/// Compilation errors are returned in the `GroupBuild`, the returned error is only used for
+/// problems that should abort the whole compilation.
+fn build_group(
+    toolchain: &Toolchain,
+    benchmark_crate: &BenchmarkGroupCrate,
+    (index, group_count): (usize, usize),
+    isolation_mode: CargoIsolationMode,
+    target_dir: Option<&Path>,
+    list_command: &str,
+    opts: &RuntimeCompilationOpts,
+) -> anyhow::Result<GroupBuild> {
+    println!(
+        "Compiling {:<22} ({}/{group_count})",
+        format!("`{}`", benchmark_crate.name),
+        index + 1
+    );
+
+    // Make sure that Cargo.lock isn't changed by the build if we're running in isolated mode
+    let _guard = match isolation_mode {
+        CargoIsolationMode::Cached => None,
+        CargoIsolationMode::Isolated => Some(EnsureImmutableFile::new(
+            &benchmark_crate.path.join("Cargo.lock"),
+            benchmark_crate.name.clone(),
+        )?),
+    };
+    let start = Instant::now();
+    let clean_result = if opts.force_rebuild {
+        clean_benchmark_crate(toolchain, &benchmark_crate.path, target_dir, opts)
+    } else {
+        Ok(())
+    };
+    let result = clean_result
+        .and_then(|_| {
+            start_cargo_build(toolchain, &benchmark_crate.path, target_dir, opts).with_context(
+                || anyhow::anyhow!("Cannot start compilation of {}", benchmark_crate.name),
+            )
+        })
+        .and_then(|iter| match opts.log_dir {
+            Some(ref log_dir) => {
+                iter.log_to_file(&log_dir.join(format!("{}.log", benchmark_crate.name)))
+            }
+            None => Ok(iter),
+        })
+        .and_then(|iter| {
+            parse_benchmark_group(
+                iter,
+                &benchmark_crate.name,
+                list_command,
+                opts.list_timeout,
+                opts.compile_timeout,
+                opts.runner.as_deref().unwrap_or_default(),
+                opts.name_transform.as_deref(),
+            )
+            .with_context(|| {
+                anyhow::anyhow!("Cannot compile runtime benchmark {}", benchmark_crate.name)
+            })
+        });
+    let timing = GroupBuildTiming {
+        group: benchmark_crate.name.clone(),
+        start,
+        end: Instant::now(),
+    };
+
+    let result = result.map(|mut group| {
+        if opts.llvm_ir_size {
+            if let Err(error) =
+                record_llvm_ir_size(&mut group, toolchain, &benchmark_crate.path, opts)
+            {
+                log::warn!(
+                    "Cannot measure LLVM IR size of runtime benchmark group `{}`: {error:?}",
+                    benchmark_crate.name
+                );
+            }
+        }
+        group
+    });
+    Ok(GroupBuild { result, timing })
+}
+
+/// Builds the given crates of the runtime benchmark workspace in `workspace_dir` with a single
+/// Cargo invocation, and gathers their benchmarks. Returns one build per crate, in the same
+/// order.
+///
+/// Compilation errors are returned in the `GroupBuild`s, the returned error is only used for
+/// problems that should abort the whole compilation.
+fn build_workspace(
+    toolchain: &Toolchain,
+    workspace_dir: &Path,
+    crates: &[&BenchmarkGroupCrate],
+    isolation_mode: CargoIsolationMode,
+    target_dir: Option<&Path>,
+    list_command: &str,
+    opts: &RuntimeCompilationOpts,
+) -> anyhow::Result<Vec<GroupBuild>> {
+    println!(
+        "Compiling {} runtime benchmark group(s) in workspace {}",
+        crates.len(),
+        workspace_dir.display()
+    );
+
+    // Make sure that Cargo.lock isn't changed by the build if we're running in isolated mode
+    let _guard = match isolation_mode {
+        CargoIsolationMode::Cached => None,
+        CargoIsolationMode::Isolated => Some(EnsureImmutableFile::new(
+            &workspace_dir.join("Cargo.lock"),
+            "runtime benchmark workspace".to_string(),
+        )?),
+    };
+    let start = Instant::now();
+    let result = (|| -> anyhow::Result<CargoArtifactIter> {
+        let mut command = cargo_build_command(toolchain, workspace_dir, target_dir, opts);
+        for benchmark_crate in crates {
+            if opts.force_rebuild {
+                clean_benchmark_crate(toolchain, &benchmark_crate.path, target_dir, opts)?;
+            }
+            command
+                .arg("-p")
+                .arg(read_package_name(&benchmark_crate.path)?);
+        }
+        let iter = CargoArtifactIter::from_cargo_cmd(command)
+            .map_err(|error| anyhow::anyhow!("Failed to start cargo: {:?}", error))?;
+        match opts.log_dir {
+            Some(ref log_dir) => iter.log_to_file(&log_dir.join("workspace.log")),
+            None => Ok(iter),
+        }
+    })();
+    let mut builds = match result {
+        Ok(iter) => parse_workspace_groups(iter, crates, start, list_command, opts),
+        Err(error) => {
+            let end = Instant::now();
+            crates
+                .iter()
+                .map(|benchmark_crate| GroupBuild {
+                    result: Err(anyhow::anyhow!("{error:?}").context(format!(
+                        "Cannot start compilation of {}",
+                        benchmark_crate.name
+                    ))),
+                    timing: GroupBuildTiming {
+                        group: benchmark_crate.name.clone(),
+                        start,
+                        end,
+                    },
+                })
+                .collect()
+        }
+    };
 
-        if let Some(ref group) = group {
-            if group != &name {
+    if opts.llvm_ir_size {
+        for (build, benchmark_crate) in builds.iter_mut().zip(crates) {
+            let Ok(ref mut group) = build.result else {
                 continue;
+            };
+            if let Err(error) = record_llvm_ir_size(group, toolchain, &benchmark_crate.path, opts) {
+                log::warn!(
+                    "Cannot measure LLVM IR size of runtime benchmark group `{}`: {error:?}",
+                    benchmark_crate.name
+                );
             }
         }
+    }
+    Ok(builds)
+}
 
-        groups.push(BenchmarkGroupCrate { name, path });
+/// Compiles the benchmark crate once more with `--emit=llvm-ir` into a temporary target
+/// directory, and stores the total size of the emitted `.ll` files into the group.
+fn record_llvm_ir_size(
+    group: &mut BenchmarkGroup,
+    toolchain: &Toolchain,
+    benchmark_dir: &Path,
+    opts: &RuntimeCompilationOpts,
+) -> anyhow::Result<()> {
+    let target_dir = TempDir::new().context("Cannot create temporary directory")?;
+    let mut command = cargo_build_command(toolchain, benchmark_dir, Some(target_dir.path()), opts);
+    let mut rustflags = combined_rustflags(opts);
+    if !rustflags.is_empty() {
+        rustflags.push(' ');
+    }
+    rustflags.push_str("--emit=llvm-ir");
+    command.env("RUSTFLAGS", rustflags);
+    command_output(&mut command)
+        .with_context(|| format!("Cannot emit LLVM IR of runtime benchmark `{}`", group.name))?;
+
+    let mut size = 0;
+    for entry in walkdir::WalkDir::new(target_dir.path()) {
+        let entry = entry?;
+        if entry.file_type().is_file() && entry.path().extension().is_some_and(|ext| ext == "ll") {
+            size += entry.metadata()?.len();
+        }
+    }
+    group.llvm_ir_size = Some(size);
+    Ok(())
+}
+
+/// Removes the compiled artifacts of a single runtime benchmark crate, so that the next build
+/// will compile it from scratch.
+fn clean_benchmark_crate(
+    toolchain: &Toolchain,
+    benchmark_dir: &Path,
+    target_dir: Option<&Path>,
+    opts: &RuntimeCompilationOpts,
+) -> anyhow::Result<()> {
+    let package = read_package_name(benchmark_dir)?;
+    let mut command = Command::new(&toolchain.components.cargo);
+    command
+        .arg("clean")
+        .arg("--profile")
+        .arg(opts.build_profile())
+        .arg("-p")
+        .arg(&package)
+        .current_dir(benchmark_dir);
+    if let Some(target_dir) = target_dir {
+        command.arg("--target-dir");
+        command.arg(target_dir);
+    }
+    if let Some(mode) = opts.network_mode {
+        command.arg(mode.flag());
+    }
+    command_output(&mut command)
+        .with_context(|| format!("Cannot clean runtime benchmark crate `{package}`"))?;
+    Ok(())
+}
+
+/// Starts the compilation of a single runtime benchmark crate.
+/// Returns the stdout output stream of Cargo.
+fn start_cargo_build(
+    toolchain: &Toolchain,
+    benchmark_dir: &Path,
+    target_dir: Option<&Path>,
+    opts: &RuntimeCompilationOpts,
+) -> anyhow::Result<CargoArtifactIter> {
+    let command = cargo_build_command(toolchain, benchmark_dir, target_dir, opts);
+    CargoArtifactIter::from_cargo_cmd(command)
+        .map_err(|error| anyhow::anyhow!("Failed to start cargo: {:?}", error))
+}
+
+/// Cargo profile with which the runtime benchmarks are compiled, unless another one is
+/// selected with `RuntimeCompilationOpts::profile`.
+const DEFAULT_BUILD_PROFILE: &str = "release";
+
+/// Returns the name of the directory into which Cargo puts the artifacts of `profile`.
+fn profile_dir_name(profile: &str) -> &str {
+    match profile {
+        "dev" | "test" => "debug",
+        "bench" => "release",
+        profile => profile,
+    }
+}
+
+/// Checks that the overridden value of a profile option is one of the `allowed` values, so that
+/// we do not pass garbage to Cargo.
+fn check_profile_value(option: &str, value: Option<&str>, allowed: &[&str]) -> anyhow::Result<()> {
+    match value {
+        Some(value) if !allowed.contains(&value) => Err(anyhow::anyhow!(
+            "Unknown {option} `{value}`, expected one of: {}",
+            allowed.join(", ")
+        )),
+        _ => Ok(()),
+    }
+}
+
+/// Returns the name of the environment variable that overrides the given option (e.g.
+/// `CODEGEN_UNITS`) of the Cargo profile `profile`.
+fn profile_env_var(profile: &str, option: &str) -> String {
+    // Cargo expects dashes in profile names to be replaced with underscores.
+    format!(
+        "CARGO_PROFILE_{}_{option}",
+        profile.to_uppercase().replace('-', "_")
+    )
+}
+
+/// Prepares the Cargo command that compiles a single runtime benchmark crate.
+fn cargo_build_command(
+    toolchain: &Toolchain,
+    benchmark_dir: &Path,
+    target_dir: Option<&Path>,
+    opts: &RuntimeCompilationOpts,
+) -> Command {
+    let profile = opts.build_profile();
+    let mut command = Command::new(&toolchain.components.cargo);
+    command
+        .env("RUSTC", &toolchain.components.rustc)
+        .arg("build")
+        .arg("--profile")
+        .arg(profile)
+        .current_dir(benchmark_dir);
+
+    if let Some(ref debug_info) = opts.debug_info {
+        command.env(profile_env_var(profile, "DEBUG"), debug_info);
+    }
+    if let Some(ref split_debuginfo) = opts.split_debuginfo {
+        command.env(profile_env_var(profile, "SPLIT_DEBUGINFO"), split_debuginfo);
+    }
+    if let Some(ref opt_level) = opts.opt_level {
+        command.env(profile_env_var(profile, "OPT_LEVEL"), opt_level);
+    }
+    if let Some(ref lto) = opts.lto {
+        command.env(profile_env_var(profile, "LTO"), lto);
+    }
+    if let Some(ref panic) = opts.panic {
+        command.env(profile_env_var(profile, "PANIC"), panic);
+    }
+    if let Some(overflow_checks) = opts.overflow_checks {
+        command.env(
+            profile_env_var(profile, "OVERFLOW_CHECKS"),
+            overflow_checks.to_string(),
+        );
+    }
+    if let Some(codegen_units) = opts.codegen_units {
+        command.env(
+            profile_env_var(profile, "CODEGEN_UNITS"),
+            codegen_units.to_string(),
+        );
+    }
+    if opts.rustflags.is_some() {
+        command.env("RUSTFLAGS", combined_rustflags(opts));
+    }
+
+    if let Some(target_dir) = target_dir {
+        command.arg("--target-dir");
+        command.arg(target_dir);
+    }
+    if let Some(ref target) = opts.target {
+        command.arg("--target").arg(target);
+    }
+    if let Some(mode) = opts.network_mode {
+        command.arg(mode.flag());
+    }
+
+    let features = requested_features();
+    if !features.is_empty() {
+        command.arg("--features").arg(features.join(","));
+    }
+
+    for config in &toolchain.components.cargo_configs {
+        command.arg("--config").arg(config);
+    }
+
+    if let Some(limit) = opts.build_memory_limit {
+        limit_address_space(&mut command, limit);
+    }
+    command
+}
+
+/// Returns the `RUSTFLAGS` of the collector followed by the flags from `opts`.
+fn combined_rustflags(opts: &RuntimeCompilationOpts) -> String {
+    let mut rustflags = std::env::var("RUSTFLAGS").unwrap_or_default();
+    if let Some(ref extra) = opts.rustflags {
+        if !rustflags.is_empty() {
+            rustflags.push(' ');
+        }
+        rustflags.push_str(extra);
+    }
+    rustflags
+}
+
+/// Sets the `RLIMIT_AS` resource limit of the process spawned by `command`, which is inherited
+/// by all of its children.
+#[cfg(unix)]
+fn limit_address_space(command: &mut Command, limit: u64) {
+    use std::os::unix::process::CommandExt;
+
+    let limit = libc::rlimit {
+        rlim_cur: limit as libc::rlim_t,
+        rlim_max: limit as libc::rlim_t,
+    };
+    // SAFETY: the closure only calls async-signal-safe functions.
+    unsafe {
+        command.pre_exec(move || {
+            if libc::setrlimit(libc::RLIMIT_AS, &limit) != 0 {
+                return Err(std::io::Error::last_os_error());
+            }
+            Ok(())
+        });
+    }
+}
+
+#[cfg(not(unix))]
+fn limit_address_space(_command: &mut Command, _limit: u64) {
+    log::warn!("Limiting the memory of runtime benchmark builds is only supported on Unix");
+}
+
+/// Cargo features that are enabled when compiling runtime benchmark groups.
+fn requested_features() -> Vec<String> {
+    let mut features = vec![];
+    // Enable the precise-cachegrind feature for the benchlib dependency of the runtime group.
+    if cfg!(feature = "precise-cachegrind") {
+        features.push("benchlib/precise-cachegrind".to_string());
+    }
+    features
+}
+
+/// Output of the command that lists the benchmarks of a binary. Binaries of an older `benchlib`
+/// (or custom list commands) only print the array of benchmark names.
+#[derive(serde::Deserialize)]
+#[serde(untagged)]
+enum BenchmarkList {
+    WithProtocolVersion {
+        benchmarks: Vec<String>,
+        protocol_version: u32,
+    },
+    Names(Vec<String>),
+}
+
+/// Uses a command from `benchlib` to find the benchmark names from the given
+/// benchmark binary, together with the version of the `benchlib` protocol that it uses, if the
+/// binary reports it.
+/// If the command does not finish within `timeout`, it is killed and an error is returned.
+/// If `runner` is not empty, the binary is executed through it.
+fn gather_benchmarks(
+    binary: &Path,
+    runner: &[String],
+    list_command: &str,
+    timeout: Option<Duration>,
+) -> anyhow::Result<(Vec<String>, Option<u32>)> {
+    let mut command = binary_command(binary, runner);
+    command
+        .arg(list_command)
+        .env(LIST_PROTOCOL_VERSION_ENV, "1");
+    let output = match timeout {
+        Some(timeout) => {
+            command.stdout(Stdio::piped()).stderr(Stdio::piped());
+            run_command_until(&mut command, Instant::now() + timeout).map_err(|error| {
+                if error.downcast_ref::<RunTimedOut>().is_some() {
+                    anyhow::anyhow!(
+                        "`{list_command}` command of {} did not finish within {timeout:?}",
+                        binary.display()
+                    )
+                } else {
+                    error
+                }
+            })?
+        }
+        None => command.output()?,
+    };
+    // The stdout can contain a valid (but incomplete) list even if the binary has failed.
+    if !output.status.success() {
+        return Err(anyhow::anyhow!(
+            "`{list_command}` command of {} {}\n{}",
+            binary.display(),
+            describe_exit_status(output.status),
+            truncate_stderr(&output.stderr)
+        ));
+    }
+    let list = serde_json::from_slice(&output.stdout).map_err(|error| {
+        anyhow::anyhow!(
+            "Cannot parse the output of the `{list_command}` command: {error}\n{}",
+            truncate_stderr(&output.stderr)
+        )
+    })?;
+    Ok(match list {
+        BenchmarkList::WithProtocolVersion {
+            benchmarks,
+            protocol_version,
+        } => (benchmarks, Some(protocol_version)),
+        BenchmarkList::Names(benchmarks) => (benchmarks, None),
+    })
+}
+
+/// Creates a command that executes the binary, through `runner` if it is not empty.
+fn binary_command(binary: &Path, runner: &[String]) -> Command {
+    match runner.split_first() {
+        Some((program, args)) => {
+            let mut command = Command::new(program);
+            command.args(args).arg(binary);
+            command
+        }
+        None => Command::new(binary),
+    }
+}
+
+/// If the groups do not all report the same `benchlib` protocol version, returns a message that
+/// lists the groups whose version differs from the one used by most groups.
+fn protocol_version_mismatch(groups: &[BenchmarkGroup]) -> Option<String> {
+    let mut counts: BTreeMap<Option<u32>, usize> = BTreeMap::new();
+    for group in groups {
+        *counts.entry(group.protocol_version).or_default() += 1;
+    }
+    if counts.len() <= 1 {
+        return None;
+    }
+    // On a tie, the newest version is considered to be the expected one.
+    let (&expected, _) = counts.iter().max_by_key(|(_, &count)| count)?;
+    let describe = |version: Option<u32>| match version {
+        Some(version) => format!("version {version}"),
+        None => "an unknown version".to_string(),
+    };
+    let outliers: Vec<String> = groups
+        .iter()
+        .filter(|group| group.protocol_version != expected)
+        .map(|group| format!("`{}` ({})", group.name, describe(group.protocol_version)))
+        .collect();
+    Some(format!(
+        "Runtime benchmark groups use different benchlib protocol versions, most use {}, but \
+these differ: {}",
+        describe(expected),
+        outliers.join(", ")
+    ))
+}
+
+/// Describes how has a process finished unsuccessfully, e.g. `finished with exit code 101`.
+fn describe_exit_status(status: std::process::ExitStatus) -> String {
+    #[cfg(unix)]
+    {
+        use std::os::unix::process::ExitStatusExt;
+
+        if let Some(signal) = status.signal() {
+            return if signal == libc::SIGABRT {
+                // This is what happens when a benchmark panics with `panic=abort`.
+                format!("was aborted (signal {signal})")
+            } else {
+                format!("was terminated by signal {signal}")
+            };
+        }
+    }
+    match status.code() {
+        Some(code) => format!("finished with exit code {code}"),
+        None => format!("finished with {status}"),
+    }
+}
+
+/// Maximum number of bytes of the stderr of a benchmark binary that is included in errors.
+const MAX_STDERR_LEN: usize = 4096;
+
+/// Decodes the stderr of a process. If it is too long, only its end is kept, which is where
+/// panic messages are usually printed.
+fn truncate_stderr(stderr: &[u8]) -> String {
+    let stderr = String::from_utf8_lossy(stderr);
+    if stderr.len() <= MAX_STDERR_LEN {
+        return stderr.into_owned();
+    }
+    let mut start = stderr.len() - MAX_STDERR_LEN;
+    while !stderr.is_char_boundary(start) {
+        start += 1;
+    }
+    format!("...{}", &stderr[start..])
+}
+
+/// Error that can happen while looking for runtime benchmark crates.
+#[derive(thiserror::Error, Debug)]
+pub enum DiscoveryError {
+    #[error("runtime benchmark directory `{}` does not exist", .0.display())]
+    DirNotFound(PathBuf),
+    #[error("permission denied while reading runtime benchmark directory `{}`", .0.display())]
+    PermissionDenied(PathBuf),
+    #[error("cannot read runtime benchmark directory `{}`", .path.display())]
+    Io {
+        path: PathBuf,
+        #[source]
+        source: std::io::Error,
+    },
+    #[error(
+        "cannot create a runtime benchmark group name from path `{}`, it is not valid UTF-8",
+        .0.display()
+    )]
+    InvalidGroupName(PathBuf),
+    #[error(
+        "runtime benchmark group name `{name}` is used by both `{}` and `{}`",
+        .first.display(),
+        .second.display()
+    )]
+    DuplicateName {
+        name: String,
+        first: PathBuf,
+        second: PathBuf,
+    },
+}
+
+/// Selects runtime benchmark groups by their exact names, before they are compiled.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct GroupSelection {
+    /// If set, only groups with these names are selected.
+    pub include: Option<Vec<String>>,
+    /// Groups with these names are not selected, even if they are included.
+    pub exclude: Vec<String>,
+}
+
+impl GroupSelection {
+    /// Selects all groups.
+    pub fn all() -> Self {
+        Self::default()
+    }
+
+    /// Selects only the group with the given name.
+    pub fn only(name: &str) -> Self {
+        Self {
+            include: Some(vec![name.to_string()]),
+            exclude: vec![],
+        }
+    }
+
+    /// Additionally deselects the groups with the given names.
+    pub fn exclude(mut self, names: Vec<String>) -> Self {
+        self.exclude.extend(names);
+        self
+    }
+
+    pub fn contains(&self, name: &str) -> bool {
+        self.include
+            .as_ref()
+            .is_none_or(|include| include.iter().any(|n| n == name))
+            && !self.exclude.iter().any(|n| n == name)
+    }
+}
+
+/// `Some(name)` selects only the group with the given name, `None` selects all groups.
+impl From<Option<String>> for GroupSelection {
+    fn from(group: Option<String>) -> Self {
+        match group {
+            Some(name) => Self::only(&name),
+            None => Self::all(),
+        }
+    }
+}
+
+/// Finds all runtime benchmarks (crates) in the given directory.
+pub fn get_runtime_benchmark_groups(
+    directory: &Path,
+    group: GroupSelection,
+) -> Result<Vec<BenchmarkGroupCrate>, DiscoveryError> {
+    discover_groups(directory, group, false)
+}
+
+/// Finds all runtime benchmarks (crates) in the given directory and in its nested subdirectories,
+/// e.g. `numeric/fft`. The name of a group is its path relative to `directory`, with the path
+/// separators replaced by `-` (e.g. `numeric-fft`), unless it is overridden in its `Cargo.toml`.
+///
+/// Directories with a `Cargo.toml` are not searched further, and neither are hidden directories
+/// and directories called `target`.
+pub fn get_nested_runtime_benchmark_groups(
+    directory: &Path,
+    group: GroupSelection,
+) -> Result<Vec<BenchmarkGroupCrate>, DiscoveryError> {
+    discover_groups(directory, group, true)
+}
+
+pub(super) fn discover_groups(
+    directory: &Path,
+    group: GroupSelection,
+    recursive: bool,
+) -> Result<Vec<BenchmarkGroupCrate>, DiscoveryError> {
+    let mut crate_dirs = vec![];
+    find_crate_dirs(directory, recursive, &mut crate_dirs, &mut vec![])?;
+    // Sort the directories, so that duplicated names are reported deterministically.
+    crate_dirs.sort();
+
+    let mut paths: HashMap<String, PathBuf> = HashMap::new();
+    let mut groups = Vec::new();
+    for path in crate_dirs {
+        let name = match read_group_name_override(&path) {
+            Some(name) => name,
+            None => default_group_name(directory, &path)
+                .ok_or_else(|| DiscoveryError::InvalidGroupName(path.clone()))?,
+        };
+        if let Some(first) = paths.insert(name.clone(), path.clone()) {
+            return Err(DiscoveryError::DuplicateName {
+                name,
+                first,
+                second: path,
+            });
+        }
+
+        if !group.contains(&name) {
+            continue;
+        }
+
+        let tags = read_group_tags(&path);
+        groups.push(BenchmarkGroupCrate { name, path, tags });
+    }
+    groups.sort_unstable_by(|a, b| a.name.cmp(&b.name));
+    Ok(groups)
+}
+
+/// Returns the directories that are searched by `discover_groups` (with the same `recursive`
+/// setting) and that contain a `src` directory, but no `Cargo.toml`. They were probably meant to
+/// be benchmark crates.
+pub(super) fn find_sources_without_manifest(
+    directory: &Path,
+    recursive: bool,
+) -> Result<Vec<PathBuf>, DiscoveryError> {
+    let mut other_dirs = vec![];
+    find_crate_dirs(directory, recursive, &mut vec![], &mut other_dirs)?;
+    other_dirs.retain(|dir| dir.join("src").is_dir());
+    Ok(other_dirs)
+}
+
+/// Adds the subdirectories of `dir` that contain a `Cargo.toml` to `crate_dirs`, and the other
+/// subdirectories to `other_dirs`. If `recursive` is set, the other subdirectories are searched
+/// as well.
+fn find_crate_dirs(
+    dir: &Path,
+    recursive: bool,
+    crate_dirs: &mut Vec<PathBuf>,
+    other_dirs: &mut Vec<PathBuf>,
+) -> Result<(), DiscoveryError> {
+    let io_error = |source| DiscoveryError::Io {
+        path: dir.to_path_buf(),
+        source,
+    };
+    let entries = std::fs::read_dir(dir).map_err(|error| match error.kind() {
+        std::io::ErrorKind::NotFound => DiscoveryError::DirNotFound(dir.to_path_buf()),
+        std::io::ErrorKind::PermissionDenied => DiscoveryError::PermissionDenied(dir.to_path_buf()),
+        _ => io_error(error),
+    })?;
+    for entry in entries {
+        let entry = entry.map_err(io_error)?;
+        if !entry.file_type().map_err(io_error)?.is_dir() {
+            continue;
+        }
+        let path = entry.path();
+        if path.join("Cargo.toml").is_file() {
+            crate_dirs.push(path);
+            continue;
+        }
+        other_dirs.push(path.clone());
+        if recursive {
+            let name = entry.file_name();
+            let name = name.to_string_lossy();
+            if !name.starts_with('.') && name != "target" {
+                find_crate_dirs(&path, recursive, crate_dirs, other_dirs)?;
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Creates the name of the group in `path` from its path relative to `root`. Returns `None` if
+/// the relative path is empty or is not valid UTF-8.
+fn default_group_name(root: &Path, path: &Path) -> Option<String> {
+    let components: Vec<&str> = path
+        .strip_prefix(root)
+        .unwrap_or(path)
+        .components()
+        .map(|component| component.as_os_str().to_str())
+        .collect::<Option<_>>()?;
+    (!components.is_empty()).then(|| components.join("-"))
+}
+
+/// Finds the names of runtime benchmark groups in `dir` that contain files changed between
+/// `base_ref` and the working tree, according to `git diff --name-only`.
+/// Changed files outside of any benchmark group are ignored.
+pub fn benchmarks_changed_in_diff(dir: &Path, base_ref: &str) -> anyhow::Result<Vec<String>> {
+    let git = |args: &[&str]| -> anyhow::Result<String> {
+        let output = Command::new("git")
+            .arg("-C")
+            .arg(dir)
+            .args(args)
+            .output()
+            .with_context(|| format!("Cannot execute git {}", args.join(" ")))?;
+        if !output.status.success() {
+            return Err(anyhow::anyhow!(
+                "git {} failed: {}",
+                args.join(" "),
+                String::from_utf8_lossy(&output.stderr)
+            ));
+        }
+        Ok(String::from_utf8(output.stdout)?)
+    };
+
+    // Paths printed by `git diff` are relative to the root of the repository.
+    let root = PathBuf::from(git(&["rev-parse", "--show-toplevel"])?.trim());
+    let changed_files: Vec<PathBuf> = git(&["diff", "--name-only", base_ref])?
+        .lines()
+        .map(|file| root.join(file))
+        .collect();
+
+    let mut changed = vec![];
+    for group in get_runtime_benchmark_groups(dir, GroupSelection::all())? {
+        let group_path = group
+            .path
+            .canonicalize()
+            .with_context(|| format!("Cannot canonicalize {}", group.path.display()))?;
+        if changed_files
+            .iter()
+            .any(|file| file.starts_with(&group_path))
+        {
+            changed.push(group.name);
+        }
+    }
+    Ok(changed)
+}
+
+/// Reads the group name override from `[package.metadata.rustc-perf] name = "..."` in the
+/// `Cargo.toml` of a runtime benchmark crate.
+fn read_group_name_override(path: &Path) -> Option<String> {
+    let manifest = match read_manifest(path) {
+        Ok(manifest) => manifest,
+        Err(error) => {
+            log::warn!("{error:?}");
+            return None;
+        }
+    };
+    manifest
+        .get("package")?
+        .get("metadata")?
+        .get("rustc-perf")?
+        .get("name")?
+        .as_str()
+        .map(|name| name.to_string())
+}
+
+/// Reads the group tags from `[package.metadata.rustc-perf] tags = [...]` in the `Cargo.toml` of
+/// a runtime benchmark crate.
+fn read_group_tags(path: &Path) -> Vec<String> {
+    let manifest = match read_manifest(path) {
+        Ok(manifest) => manifest,
+        Err(error) => {
+            log::warn!("{error:?}");
+            return vec![];
+        }
+    };
+    manifest
+        .get("package")
+        .and_then(|package| package.get("metadata"))
+        .and_then(|metadata| metadata.get("rustc-perf"))
+        .and_then(|metadata| metadata.get("tags"))
+        .and_then(|tags| tags.as_array())
+        .map(|tags| {
+            tags.iter()
+                .filter_map(|tag| tag.as_str())
+                .map(|tag| tag.to_string())
+                .collect()
+        })
+        .unwrap_or_default()
+}
+
+/// Reads the package name from the `Cargo.toml` of a runtime benchmark crate.
+fn read_package_name(path: &Path) -> anyhow::Result<String> {
+    read_manifest(path)?
+        .get("package")
+        .and_then(|package| package.get("name"))
+        .and_then(|name| name.as_str())
+        .map(|name| name.to_string())
+        .ok_or_else(|| anyhow::anyhow!("`{}` does not contain a package name", path.display()))
+}
+
+fn read_manifest(path: &Path) -> anyhow::Result<toml::Table> {
+    let manifest_path = path.join("Cargo.toml");
+    std::fs::read_to_string(&manifest_path)
+        .map_err(anyhow::Error::from)
+        .and_then(|contents| Ok(contents.parse::<toml::Table>()?))
+        .with_context(|| format!("Cannot read `{}`", manifest_path.display()))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::ffi::OsStr;
+    use std::path::Path;
+    use std::process::Command;
+    use std::time::{Duration, Instant};
+
+    use crate::toolchain::{Toolchain, ToolchainComponents};
+
+    use super::{
+        benchmarks_changed_in_diff, cargo_build_command, check_disk_usage, check_duplicates,
+        clean_benchmark_crate, gather_benchmarks, get_runtime_benchmark_groups, qualified_name,
+        requested_features, retain_by_binary_size, retain_by_compile_time, transform_names,
+        BenchmarkGroup, BenchmarkGroupCrate, BenchmarkSuite, BenchmarkSuiteCompilation,
+        DiscoveryError, GroupBuildTiming, GroupSelection, RuntimeBenchmarkFilter,
+        RuntimeCompilationOpts,
+    };
+    use crate::runtime::TagExpr;
+
+    fn test_toolchain() -> Toolchain {
+        Toolchain {
+            components: ToolchainComponents::default(),
+            id: "test".to_string(),
+            triple: "x86_64-unknown-linux-gnu".to_string(),
+        }
+    }
+
+    fn create_group_crate(dir: &Path, name: &str, manifest_extra: &str) {
+        let path = dir.join(name);
+        std::fs::create_dir_all(path.join("src")).unwrap();
+        std::fs::write(
+            path.join("Cargo.toml"),
+            format!("[package]\nname = \"{name}-bench\"\nversion = \"0.1.0\"\n{manifest_extra}"),
+        )
+        .unwrap();
+        std::fs::write(path.join("src/main.rs"), "fn main() {}").unwrap();
+    }
+
+    fn get_env<'a>(command: &'a Command, key: &str) -> Option<&'a OsStr> {
+        command
+            .get_envs()
+            .find(|(k, _)| *k == key)
+            .and_then(|(_, v)| v)
+    }
+
+    #[test]
+    fn test_compilation_overrides() {
+        use super::{prepare_runtime_benchmark_suite, CargoIsolationMode, CargoNetworkMode};
+
+        type Setter = fn(RuntimeCompilationOpts) -> RuntimeCompilationOpts;
+        let command = |set: Setter| {
+            cargo_build_command(
+                &test_toolchain(),
+                Path::new("."),
+                None,
+                &set(RuntimeCompilationOpts::default()),
+            )
+        };
+        let default = command(|opts| opts);
+        let default_args: Vec<&OsStr> = default.get_args().collect();
+
+        // The profile overrides are passed as environment variables of the built profile.
+        let env_overrides: &[(Setter, &str, &str)] = &[
+            (
+                |opts| opts.split_debuginfo("packed"),
+                "CARGO_PROFILE_RELEASE_SPLIT_DEBUGINFO",
+                "packed",
+            ),
+            (
+                |opts| opts.opt_level("2"),
+                "CARGO_PROFILE_RELEASE_OPT_LEVEL",
+                "2",
+            ),
+            (
+                |opts| opts.overflow_checks(true),
+                "CARGO_PROFILE_RELEASE_OVERFLOW_CHECKS",
+                "true",
+            ),
+            (
+                |opts| opts.overflow_checks(false),
+                "CARGO_PROFILE_RELEASE_OVERFLOW_CHECKS",
+                "false",
+            ),
+            (
+                |opts| opts.panic("abort"),
+                "CARGO_PROFILE_RELEASE_PANIC",
+                "abort",
+            ),
+            (
+                |opts| opts.codegen_units(1),
+                "CARGO_PROFILE_RELEASE_CODEGEN_UNITS",
+                "1",
+            ),
+            (
+                |opts| opts.profile("release-lto").codegen_units(16),
+                "CARGO_PROFILE_RELEASE_LTO_CODEGEN_UNITS",
+                "16",
+            ),
+            (|opts| opts.lto("off"), "CARGO_PROFILE_RELEASE_LTO", "off"),
+            (|opts| opts.lto("thin"), "CARGO_PROFILE_RELEASE_LTO", "thin"),
+            (|opts| opts.lto("fat"), "CARGO_PROFILE_RELEASE_LTO", "fat"),
+            (|opts| opts.lto("true"), "CARGO_PROFILE_RELEASE_LTO", "true"),
+            (
+                |opts| opts.lto("false"),
+                "CARGO_PROFILE_RELEASE_LTO",
+                "false",
+            ),
+        ];
+        for (set, var, value) in env_overrides {
+            assert_eq!(get_env(&default, var), None, "{var}");
+            assert_eq!(
+                get_env(&command(*set), var),
+                Some(OsStr::new(value)),
+                "{var}"
+            );
+        }
+        // An override of another profile does not touch the release profile.
+        let command_with_profile = command(|opts| opts.profile("release-lto").codegen_units(16));
+        assert_eq!(
+            get_env(&command_with_profile, "CARGO_PROFILE_RELEASE_CODEGEN_UNITS"),
+            None
+        );
+
+        // The other options are passed as arguments.
+        assert!(default_args
+            .windows(2)
+            .any(|args| args == ["--profile", "release"]));
+        let arg_overrides: &[(Setter, &[&str])] = &[
+            (
+                |opts| opts.profile("release-lto"),
+                &["--profile", "release-lto"],
+            ),
+            (
+                |opts| opts.target("aarch64-unknown-linux-gnu"),
+                &["--target", "aarch64-unknown-linux-gnu"],
+            ),
+            (
+                |opts| opts.network_mode(CargoNetworkMode::Offline),
+                &["--offline"],
+            ),
+            (
+                |opts| opts.network_mode(CargoNetworkMode::Frozen),
+                &["--frozen"],
+            ),
+            (
+                |opts| opts.network_mode(CargoNetworkMode::Locked),
+                &["--locked"],
+            ),
+        ];
+        for (set, expected) in arg_overrides {
+            let command = command(*set);
+            let args: Vec<&OsStr> = command.get_args().collect();
+            assert!(!default_args.contains(&OsStr::new(expected[expected.len() - 1])));
+            assert!(
+                args.windows(expected.len()).any(|args| args == *expected),
+                "{args:?}"
+            );
+        }
+
+        // Invalid values are rejected before anything is compiled.
+        let invalid: &[(Setter, &str)] = &[
+            (
+                |opts| opts.lto("full"),
+                "Unknown LTO setting `full`, expected one of: off, thin, fat, true, false",
+            ),
+            (
+                |opts| opts.panic("crash"),
+                "Unknown panic strategy `crash`, expected one of: unwind, abort",
+            ),
+        ];
+        let dir = tempfile::TempDir::new().unwrap();
+        for (set, message) in invalid {
+            let error = prepare_runtime_benchmark_suite(
+                &test_toolchain(),
+                dir.path(),
+                CargoIsolationMode::Cached,
+                GroupSelection::all(),
+                set(RuntimeCompilationOpts::default()),
+            )
+            .unwrap_err()
+            .to_string();
+            assert!(error.contains(message), "{error}");
+        }
+    }
+
+    #[test]
+    fn test_requested_features() {
+        let features = requested_features();
+        assert_eq!(
+            features.contains(&"benchlib/precise-cachegrind".to_string()),
+            cfg!(feature = "precise-cachegrind")
+        );
+
+        let command = cargo_build_command(
+            &test_toolchain(),
+            Path::new("."),
+            None,
+            &RuntimeCompilationOpts::default(),
+        );
+        let args: Vec<&OsStr> = command.get_args().collect();
+        let features_arg = args
+            .iter()
+            .position(|arg| *arg == "--features")
+            .map(|index| args[index + 1]);
+        if features.is_empty() {
+            assert_eq!(features_arg, None);
+        } else {
+            assert_eq!(features_arg, Some(OsStr::new(&features.join(","))));
+        }
+    }
+
+    #[test]
+    fn test_group_name_override() {
+        let dir = tempfile::TempDir::new().unwrap();
+        create_group_crate(dir.path(), "plain", "");
+        create_group_crate(
+            dir.path(),
+            "overridden",
+            "\n[package.metadata.rustc-perf]\nname = \"custom\"\n",
+        );
+
+        let groups = get_runtime_benchmark_groups(dir.path(), GroupSelection::all()).unwrap();
+        let names: Vec<&str> = groups.iter().map(|g| g.name.as_str()).collect();
+        assert_eq!(names, vec!["custom", "plain"]);
+
+        let groups =
+            get_runtime_benchmark_groups(dir.path(), GroupSelection::only("custom")).unwrap();
+        assert_eq!(groups.len(), 1);
+        assert_eq!(groups[0].path, dir.path().join("overridden"));
+        assert!(
+            get_runtime_benchmark_groups(dir.path(), GroupSelection::only("overridden"))
+                .unwrap()
+                .is_empty()
+        );
+    }
+
+    #[test]
+    fn test_group_selection() {
+        let dir = tempfile::TempDir::new().unwrap();
+        for name in ["fast", "flaky", "slow"] {
+            create_group_crate(dir.path(), name, "");
+        }
+        let names = |selection: GroupSelection| -> Vec<String> {
+            get_runtime_benchmark_groups(dir.path(), selection)
+                .unwrap()
+                .into_iter()
+                .map(|group| group.name)
+                .collect()
+        };
+
+        assert_eq!(names(GroupSelection::all()), ["fast", "flaky", "slow"]);
+        assert_eq!(
+            names(GroupSelection::all().exclude(vec!["flaky".to_string()])),
+            ["fast", "slow"]
+        );
+        assert_eq!(
+            names(GroupSelection {
+                include: Some(vec!["fast".to_string(), "flaky".to_string()]),
+                exclude: vec!["flaky".to_string()],
+            }),
+            ["fast"]
+        );
+        assert_eq!(names(Some("slow".to_string()).into()), ["slow"]);
+        assert_eq!(names(None::<String>.into()), ["fast", "flaky", "slow"]);
+    }
+
+    #[test]
+    fn test_nested_discovery() {
+        use super::get_nested_runtime_benchmark_groups;
+
+        let dir = tempfile::TempDir::new().unwrap();
+        create_group_crate(dir.path(), "hashmap", "");
+        create_group_crate(&dir.path().join("numeric"), "fft", "");
+        create_group_crate(&dir.path().join("text/search"), "regex", "");
+        // Neither crates nor ignored directories are searched for nested groups.
+        create_group_crate(&dir.path().join("hashmap"), "inner", "");
+        create_group_crate(&dir.path().join("target"), "built", "");
+        create_group_crate(&dir.path().join(".cache"), "hidden", "");
+        let names = |groups: Vec<BenchmarkGroupCrate>| -> Vec<String> {
+            groups.into_iter().map(|group| group.name).collect()
+        };
+
+        assert_eq!(
+            names(get_runtime_benchmark_groups(dir.path(), GroupSelection::all()).unwrap()),
+            ["hashmap"]
+        );
+        let groups =
+            get_nested_runtime_benchmark_groups(dir.path(), GroupSelection::all()).unwrap();
+        assert_eq!(groups[1].path, dir.path().join("numeric/fft"));
+        assert_eq!(
+            names(groups),
+            ["hashmap", "numeric-fft", "text-search-regex"]
+        );
+        assert_eq!(
+            names(
+                get_nested_runtime_benchmark_groups(
+                    dir.path(),
+                    GroupSelection::only("numeric-fft")
+                )
+                .unwrap()
+            ),
+            ["numeric-fft"]
+        );
+
+        create_group_crate(dir.path(), "numeric-fft", "");
+        let error = get_nested_runtime_benchmark_groups(dir.path(), GroupSelection::all())
+            .unwrap_err()
+            .to_string();
+        assert!(
+            error.contains("group name `numeric-fft` is used by both"),
+            "{error}"
+        );
+    }
+
+    #[test]
+    fn test_group_tags() {
+        let dir = tempfile::TempDir::new().unwrap();
+        create_group_crate(dir.path(), "plain", "");
+        create_group_crate(
+            dir.path(),
+            "tagged",
+            "\n[package.metadata.rustc-perf]\ntags = [\"simd\", \"slow\"]\n",
+        );
+
+        let groups = get_runtime_benchmark_groups(dir.path(), GroupSelection::all()).unwrap();
+        let tags: Vec<(&str, &[String])> = groups
+            .iter()
+            .map(|g| (g.name.as_str(), g.tags.as_slice()))
+            .collect();
+        assert_eq!(
+            tags,
+            vec![
+                ("plain", &[][..]),
+                ("tagged", &["simd".to_string(), "slow".to_string()][..])
+            ]
+        );
+    }
+
+    #[test]
+    fn test_canonical_order() {
+        let group = |name: &str, benchmarks: &[&str]| BenchmarkGroup {
+            binary: name.into(),
+            name: name.to_string(),
+            benchmark_names: benchmarks.iter().map(|b| b.to_string()).collect(),
+            features: vec![],
+            cached: false,
+            binary_size: 0,
+            original_names: Default::default(),
+            tags: vec![],
+            llvm_ir_size: None,
+            protocol_version: None,
+        };
+        let suite = BenchmarkSuite::from_groups(
+            test_toolchain(),
+            vec![
+                group("hashmap", &["remove", "insert"]),
+                group("a", &["z"]),
+                group("a-b", &["y"]),
+            ],
+        );
+
+        let mut expected: Vec<String> = suite.qualified_names().collect();
+        expected.sort();
+        assert_eq!(
+            expected,
+            ["a-b/y", "a/z", "hashmap/insert", "hashmap/remove"]
+        );
+        assert_eq!(suite.qualified_names().collect::<Vec<_>>(), expected);
+        assert_eq!(
+            suite.benchmark_names().collect::<Vec<_>>(),
+            ["y", "z", "insert", "remove"]
+        );
+
+        // Executing the groups one after another follows the same order.
+        let executed: Vec<String> = suite
+            .groups
+            .iter()
+            .flat_map(|group| {
+                group
+                    .benchmark_names
+                    .iter()
+                    .map(|benchmark| qualified_name(&group.name, benchmark))
+            })
+            .collect();
+        assert_eq!(executed, expected);
+    }
+
+    #[test]
+    fn test_assert_required_present() {
+        let suite = BenchmarkSuite::from_groups(
+            test_toolchain(),
+            vec![BenchmarkGroup {
+                binary: "hashmap".into(),
+                name: "hashmap".to_string(),
+                benchmark_names: vec!["insert".to_string(), "remove".to_string()],
+                features: vec![],
+                cached: false,
+                binary_size: 0,
+                original_names: Default::default(),
+                tags: vec![],
+                llvm_ir_size: None,
+                protocol_version: None,
+            }],
+        );
+        suite
+            .assert_required_present(&["insert", "remove"])
+            .unwrap();
+        suite.assert_required_present(&[]).unwrap();
+
+        let error = suite
+            .assert_required_present(&["insert", "lookup", "clear"])
+            .unwrap_err()
+            .to_string();
+        assert!(error.ends_with("lookup, clear"), "{error}");
+    }
+
+    #[test]
+    fn test_stale_blocklist_entries() {
+        let suite = BenchmarkSuite::from_groups(
+            test_toolchain(),
+            vec![BenchmarkGroup {
+                binary: "hashmap".into(),
+                name: "hashmap".to_string(),
+                benchmark_names: vec!["hashmap-insert".to_string()],
+                features: vec![],
+                cached: false,
+                binary_size: 0,
+                original_names: Default::default(),
+                tags: vec![],
+                llvm_ir_size: None,
+                protocol_version: None,
+            }],
+        );
+        let blocklist = vec!["hashmap-ins".to_string(), "nbody".to_string()];
+        assert_eq!(suite.stale_blocklist_entries(&blocklist), ["nbody"]);
+    }
+
+    #[test]
+    fn test_filter_groups() {
+        use super::BenchmarkGroupFilter;
+
+        let group = |name: &str| BenchmarkGroup {
+            binary: name.into(),
+            name: name.to_string(),
+            benchmark_names: vec![format!("{name}-bench")],
+            features: vec![],
+            cached: false,
+            binary_size: 0,
+            original_names: Default::default(),
+            tags: vec![],
+            llvm_ir_size: None,
+            protocol_version: None,
+        };
+        let suite = || {
+            BenchmarkSuite::from_groups(
+                test_toolchain(),
+                vec![group("regex"), group("regex-slow"), group("hashmap")],
+            )
+        };
+        let groups = |filter: BenchmarkGroupFilter| -> Vec<String> {
+            suite()
+                .filter_groups(&filter)
+                .groups
+                .into_iter()
+                .map(|group| group.name)
+                .collect()
+        };
+
+        assert_eq!(
+            groups(BenchmarkGroupFilter::keep_all()),
+            ["hashmap", "regex", "regex-slow"]
+        );
+        assert_eq!(
+            groups(BenchmarkGroupFilter::new(vec![], vec!["regex".to_string()])),
+            ["regex", "regex-slow"]
+        );
+        assert_eq!(
+            groups(BenchmarkGroupFilter::new(
+                vec!["regex-slow".to_string()],
+                vec!["regex".to_string()]
+            )),
+            ["regex"]
+        );
+        assert_eq!(
+            groups(BenchmarkGroupFilter::new(vec!["regex".to_string()], vec![])),
+            ["hashmap"]
+        );
+    }
+
+    #[test]
+    fn test_filter_by_tags() {
+        let group = |name: &str, tags: &[&str]| BenchmarkGroup {
+            binary: name.into(),
+            name: name.to_string(),
+            benchmark_names: vec![format!("{name}-bench")],
+            features: vec![],
+            cached: false,
+            binary_size: 0,
+            original_names: Default::default(),
+            tags: tags.iter().map(|t| t.to_string()).collect(),
+            llvm_ir_size: None,
+            protocol_version: None,
+        };
+        let suite = BenchmarkSuite::from_groups(
+            test_toolchain(),
+            vec![
+                group("vector", &["simd"]),
+                group("vector-large", &["simd", "slow"]),
+                group("hashmap", &[]),
+            ],
+        );
+        assert_eq!(suite.groups_with_tag("simd"), ["vector", "vector-large"]);
+        assert_eq!(suite.groups_with_tag("slow"), ["vector-large"]);
+        assert!(suite.groups_with_tag("unknown").is_empty());
+
+        let expr: TagExpr = "simd && !slow".parse().unwrap();
+        let suite = suite.filter_by_tags(&expr);
+        assert_eq!(
+            suite.benchmark_names().collect::<Vec<_>>(),
+            ["vector-bench"]
+        );
+    }
+
+    #[test]
+    fn test_sample() {
+        let suite = || {
+            let group = |name: &str| BenchmarkGroup {
+                binary: name.into(),
+                name: name.to_string(),
+                benchmark_names: (0..5).map(|i| format!("{name}-{i}")).collect(),
+                features: vec![],
+                cached: false,
+                binary_size: 0,
+                original_names: Default::default(),
+                tags: vec![],
+                llvm_ir_size: None,
+                protocol_version: None,
+            };
+            BenchmarkSuite::from_groups(test_toolchain(), vec![group("a"), group("b")])
+        };
+        let filter = RuntimeBenchmarkFilter::new(vec!["b-4".to_string()], vec![]);
+        let sample = |count, seed| {
+            suite()
+                .sample(&filter, count, seed)
+                .qualified_names()
+                .collect::<Vec<_>>()
+        };
+
+        let picked = sample(3, 42);
+        assert_eq!(picked.len(), 3);
+        assert_eq!(picked, sample(3, 42));
+        assert!(!picked.contains(&"b/b-4".to_string()));
+
+        // The sample is clamped to the benchmarks that pass the filter.
+        assert_eq!(sample(100, 42).len(), 9);
+        assert!(sample(0, 42).is_empty());
+
+        // Only the sampled benchmarks are executed.
+        let sampled = suite().sample(&filter, 3, 42);
+        for group in &sampled.groups {
+            assert_eq!(group.original_names.len(), group.benchmark_names.len());
+        }
+    }
+
+    #[test]
+    fn test_benchmark_to_binary_map() {
+        let group = |name: &str, benchmarks: &[&str]| BenchmarkGroup {
+            binary: format!("/target/release/{name}").into(),
+            name: name.to_string(),
+            benchmark_names: benchmarks.iter().map(|b| b.to_string()).collect(),
+            features: vec![],
+            cached: false,
+            binary_size: 0,
+            original_names: Default::default(),
+            tags: vec![],
+            llvm_ir_size: None,
+            protocol_version: None,
+        };
+        let suite = BenchmarkSuite::from_groups(
+            test_toolchain(),
+            vec![
+                group("hashmap", &["insert", "remove"]),
+                group("vector", &["push"]),
+            ],
+        );
+
+        assert_eq!(suite.group_count(), 2);
+        assert_eq!(suite.benchmark_count(), 3);
+        assert_eq!(suite.benchmark_count(), suite.benchmark_names().count());
+
+        let map = suite.benchmark_to_binary_map();
+        assert_eq!(map.len(), 3);
+        for (benchmark, binary) in [
+            ("insert", "/target/release/hashmap"),
+            ("remove", "/target/release/hashmap"),
+            ("push", "/target/release/vector"),
+        ] {
+            assert_eq!(map[benchmark], Path::new(binary));
+        }
+
+        let mut out = Vec::new();
+        suite.write_benchmark_to_binary_map(&mut out).unwrap();
+        let json: serde_json::Value = serde_json::from_slice(&out).unwrap();
+        assert_eq!(
+            json,
+            serde_json::json!({
+                "insert": "/target/release/hashmap",
+                "push": "/target/release/vector",
+                "remove": "/target/release/hashmap",
+            })
+        );
+    }
+
+    #[cfg(target_os = "linux")]
+    #[test]
+    fn test_suite_from_metadata() {
+        use std::os::unix::fs::PermissionsExt;
+
+        use crate::runtime::tests::create_script;
+
+        let dir = tempfile::TempDir::new().unwrap();
+        let group = |name: &str| BenchmarkGroup {
+            binary: create_script(dir.path(), name, "exit 0"),
+            name: name.to_string(),
+            benchmark_names: vec![format!("{name}-bench")],
+            features: vec![],
+            cached: false,
+            binary_size: 0,
+            original_names: Default::default(),
+            tags: vec![],
+            llvm_ir_size: None,
+            protocol_version: None,
+        };
+        let suite = BenchmarkSuite::from_groups(test_toolchain(), vec![group("a"), group("b")]);
+        let path = dir.path().join("suite.json");
+        std::fs::write(&path, suite.to_metadata_json().unwrap()).unwrap();
+
+        let loaded = BenchmarkSuite::from_metadata(&path, test_toolchain()).unwrap();
+        assert_eq!(
+            loaded.qualified_names().collect::<Vec<_>>(),
+            ["a/a-bench", "b/b-bench"]
+        );
+        assert_eq!(loaded.groups[1].binary, dir.path().join("b"));
+
+        let mut other_toolchain = test_toolchain();
+        other_toolchain.id = "other".to_string();
+        let error = BenchmarkSuite::from_metadata(&path, other_toolchain)
+            .unwrap_err()
+            .to_string();
+        assert!(error.contains("not for `other`"), "{error}");
+
+        std::fs::remove_file(dir.path().join("a")).unwrap();
+        std::fs::write(dir.path().join("b"), "not executable").unwrap();
+        std::fs::set_permissions(dir.path().join("b"), std::fs::Permissions::from_mode(0o644))
+            .unwrap();
+        let error = BenchmarkSuite::from_metadata(&path, test_toolchain())
+            .unwrap_err()
+            .to_string();
+        assert!(
+            error.contains("Binaries of 2 runtime benchmark group(s)"),
+            "{error}"
+        );
+        assert!(error.contains("`a`"), "{error}");
+        assert!(error.contains("`b`"), "{error}");
+    }
+
+    #[test]
+    fn test_suite_metadata_json() {
+        let suite = BenchmarkSuite::from_groups(
+            test_toolchain(),
+            vec![BenchmarkGroup {
+                binary: "/target/release/hashmap".into(),
+                name: "hashmap".to_string(),
+                benchmark_names: vec!["insert".to_string(), "remove".to_string()],
+                features: vec!["benchlib/precise-cachegrind".to_string()],
+                cached: true,
+                binary_size: 1024,
+                original_names: Default::default(),
+                tags: vec!["collections".to_string()],
+                llvm_ir_size: None,
+                protocol_version: Some(1),
+            }],
+        );
+        let json: serde_json::Value =
+            serde_json::from_str(&suite.to_metadata_json().unwrap()).unwrap();
+        assert_eq!(
+            json,
+            serde_json::json!({
+                "toolchain": "test",
+                "triple": "x86_64-unknown-linux-gnu",
+                "groups": [{
+                    "binary": "/target/release/hashmap",
+                    "name": "hashmap",
+                    "benchmark_names": ["insert", "remove"],
+                    "features": ["benchlib/precise-cachegrind"],
+                    "cached": true,
+                    "binary_size": 1024,
+                    "tags": ["collections"],
+                    "llvm_ir_size": null,
+                    "protocol_version": 1,
+                }],
+            })
+        );
+    }
+
+    #[test]
+    fn test_regex_filter() {
+        let group = |name: &str, benchmarks: &[&str]| BenchmarkGroup {
+            binary: name.into(),
+            name: name.to_string(),
+            benchmark_names: benchmarks.iter().map(|b| b.to_string()).collect(),
+            features: vec![],
+            cached: false,
+            binary_size: 0,
+            original_names: Default::default(),
+            tags: vec![],
+            llvm_ir_size: None,
+            protocol_version: None,
+        };
+        let suite = BenchmarkSuite::from_groups(
+            test_toolchain(),
+            vec![
+                group(
+                    "hashmap",
+                    &[
+                        "hashmap_insert_large",
+                        "hashmap_insert_small",
+                        "large_hashmap",
+                    ],
+                ),
+                group("nbody", &["nbody_large"]),
+            ],
+        );
+
+        let filter = RuntimeBenchmarkFilter::regex(
+            vec!["small|nbody".to_string()],
+            vec![
+                "^hashmap_.*_(large|small)$".to_string(),
+                "_large$".to_string(),
+            ],
+        )
+        .unwrap();
+        assert!(filter.matches("hashmap_insert_large"));
+        assert!(!filter.matches("hashmap_insert_small"));
+        assert!(!filter.matches("large_hashmap"));
+        assert!(!filter.matches("nbody_large"));
+        assert_eq!(suite.filtered_benchmark_count(&filter), 1);
+
+        // The execution of the group is restricted to the matching benchmark.
+        let suite = suite.filter(&filter);
+        assert_eq!(suite.groups.len(), 1);
+        assert_eq!(
+            suite.groups[0].original_names.keys().collect::<Vec<_>>(),
+            ["hashmap_insert_large"]
+        );
+
+        // Only excludes keep everything else.
+        let filter = RuntimeBenchmarkFilter::regex(vec!["^nbody".to_string()], vec![]).unwrap();
+        assert!(filter.matches("hashmap_insert_small"));
+        assert!(!filter.matches("nbody_large"));
+
+        let error = RuntimeBenchmarkFilter::regex(vec![], vec!["hashmap_(".to_string()])
+            .unwrap_err()
+            .to_string();
+        assert!(
+            error.contains("Invalid include pattern in benchmark filter"),
+            "{error}"
+        );
+    }
+
+    #[test]
+    fn test_glob_filter() {
+        let filter = RuntimeBenchmarkFilter::glob(
+            vec!["*_slow".to_string()],
+            vec!["sort_*".to_string(), "map".to_string(), "fmt_?".to_string()],
+        )
+        .unwrap();
+        assert!(filter.matches("sort_vec"));
+        assert!(!filter.matches("sort_vec_slow"));
+        assert!(!filter.matches("unstable_sort_vec"));
+        assert!(filter.matches("map"));
+        assert!(!filter.matches("hashmap"));
+        assert!(!filter.matches("mapreduce"));
+        assert!(filter.matches("fmt_a"));
+        assert!(!filter.matches("fmt_ab"));
+        // Other regex metacharacters are literal, and `*` also matches `/`.
+        let filter = RuntimeBenchmarkFilter::glob(vec![], vec!["a.b*".to_string()]).unwrap();
+        assert!(filter.matches("a.b/c"));
+        assert!(!filter.matches("axb"));
+
+        // The default filter still matches prefixes.
+        let filter = RuntimeBenchmarkFilter::new(vec![], vec!["map".to_string()]);
+        assert!(filter.matches("mapreduce"));
+        assert!(filter.is_prefix());
+
+        assert_eq!(
+            serde_json::to_value(RuntimeBenchmarkFilter::glob(vec![], vec![]).unwrap()).unwrap()
+                ["syntax"],
+            "glob"
+        );
+    }
+
+    #[test]
+    fn test_filter_string_round_trip() {
+        let filters = [
+            RuntimeBenchmarkFilter::keep_all(),
+            RuntimeBenchmarkFilter::new(vec![], vec!["hashmap".to_string()]),
+            RuntimeBenchmarkFilter::new(vec!["slow".to_string()], vec![]),
+            RuntimeBenchmarkFilter::new(
+                vec!["hashmap_slow".to_string(), "back\\slash".to_string()],
+                vec![
+                    "hashmap".to_string(),
+                    "with space".to_string(),
+                    "-dash".to_string(),
+                    "".to_string(),
+                ],
+            ),
+            RuntimeBenchmarkFilter::regex(vec![], vec![]).unwrap(),
+            RuntimeBenchmarkFilter::regex(
+                vec!["_slow$".to_string()],
+                vec!["^hashmap_.*_large$".to_string(), "a\\d b".to_string()],
+            )
+            .unwrap(),
+            RuntimeBenchmarkFilter::glob(vec![], vec![]).unwrap(),
+            RuntimeBenchmarkFilter::glob(vec!["*_slow".to_string()], vec!["sort_?".to_string()])
+                .unwrap(),
+        ];
+        for filter in filters {
+            let string = filter.to_string();
+            assert_eq!(string.parse::<RuntimeBenchmarkFilter>().unwrap(), filter);
+        }
+
+        assert_eq!(RuntimeBenchmarkFilter::keep_all().to_string(), "");
+        assert_eq!(
+            RuntimeBenchmarkFilter::new(vec!["c".to_string()], vec!["a b".to_string()]).to_string(),
+            "+a\\ b -c"
+        );
+        assert_eq!(
+            RuntimeBenchmarkFilter::regex(vec![], vec!["^a".to_string()])
+                .unwrap()
+                .to_string(),
+            "re: +^a"
+        );
+        assert_eq!(
+            RuntimeBenchmarkFilter::glob(vec![], vec![])
+                .unwrap()
+                .to_string(),
+            "glob:"
+        );
+        let filter = "glob: +sort_*".parse::<RuntimeBenchmarkFilter>().unwrap();
+        assert!(!filter.is_prefix());
+        assert!(filter.matches("sort_large"));
+        assert!(!filter.matches("mergesort_large"));
+        assert!("re: +(".parse::<RuntimeBenchmarkFilter>().is_err());
+        assert!("hashmap".parse::<RuntimeBenchmarkFilter>().is_err());
+        assert!("+hashmap\\".parse::<RuntimeBenchmarkFilter>().is_err());
+    }
+
+    #[cfg(target_os = "linux")]
+    #[test]
+    fn test_gather_benchmarks_custom_list_command() {
+        let dir = tempfile::TempDir::new().unwrap();
+        let binary = crate::runtime::tests::create_script(
+            dir.path(),
+            "group",
+            r#"if [ "$1" = "enumerate" ]; then echo '["a", "b"]'; else exit 1; fi"#,
+        );
+
+        assert_eq!(
+            gather_benchmarks(&binary, &[], "enumerate", None).unwrap(),
+            (vec!["a".to_string(), "b".to_string()], None)
+        );
+        assert!(gather_benchmarks(&binary, &[], super::DEFAULT_LIST_COMMAND, None).is_err());
+    }
+
+    #[cfg(target_os = "linux")]
+    #[test]
+    fn test_gather_benchmarks_failures() {
+        let dir = tempfile::TempDir::new().unwrap();
+        let binary = crate::runtime::tests::create_script(
+            dir.path(),
+            "group",
+            r#"echo "panicked during static initialization" >&2
+if [ "$1" = "crash" ]; then echo '[]'; exit 101; fi"#,
+        );
+
+        for command in ["list", "crash"] {
+            let error = gather_benchmarks(&binary, &[], command, None)
+                .unwrap_err()
+                .to_string();
+            assert!(
+                error.contains("panicked during static initialization"),
+                "{error}"
+            );
+        }
+        // A valid list of benchmarks does not hide the failure.
+        let error = gather_benchmarks(&binary, &[], "crash", None)
+            .unwrap_err()
+            .to_string();
+        assert!(
+            error.contains(&format!(
+                "of {} finished with exit code 101",
+                binary.display()
+            )),
+            "{error}"
+        );
+
+        // Behaves like a binary compiled with `panic=abort` that panics.
+        let binary = crate::runtime::tests::create_script(
+            dir.path(),
+            "aborting",
+            "echo \"thread 'main' panicked at src/main.rs:1:1\" >&2\nkill -ABRT $$",
+        );
+        let error = gather_benchmarks(&binary, &[], "list", None)
+            .unwrap_err()
+            .to_string();
+        assert!(error.contains("was aborted (signal 6)"), "{error}");
+        assert!(error.contains("thread 'main' panicked"), "{error}");
+    }
+
+    #[cfg(target_os = "linux")]
+    #[test]
+    fn test_gather_benchmarks_timeout() {
+        let dir = tempfile::TempDir::new().unwrap();
+        let binary = crate::runtime::tests::create_script(
+            dir.path(),
+            "group",
+            r#"if [ "$1" = "list" ]; then sleep 10; fi
+echo '["a"]'"#,
+        );
+
+        let start = Instant::now();
+        let error = gather_benchmarks(&binary, &[], "list", Some(Duration::from_millis(200)))
+            .unwrap_err()
+            .to_string();
+        assert!(error.contains("did not finish within 200ms"), "{error}");
+        assert!(start.elapsed() < Duration::from_secs(5));
+
+        assert_eq!(
+            gather_benchmarks(&binary, &[], "enumerate", Some(Duration::from_secs(5)))
+                .unwrap()
+                .0,
+            ["a"]
+        );
+    }
+
+    #[cfg(target_os = "linux")]
+    #[test]
+    fn test_gather_benchmarks_runner() {
+        let dir = tempfile::TempDir::new().unwrap();
+        let log = dir.path().join("runner.log");
+        // Stands in for an emulator like `qemu-aarch64`.
+        let runner = crate::runtime::tests::create_script(
+            dir.path(),
+            "runner",
+            &format!("echo \"$@\" > {}\nexec \"$@\"", log.display()),
+        );
+        let binary = crate::runtime::tests::create_script(dir.path(), "group", r#"echo '["a"]'"#);
+
+        let runner = vec![runner.display().to_string()];
+        assert_eq!(
+            gather_benchmarks(&binary, &runner, "list", None).unwrap().0,
+            ["a"]
+        );
+        assert_eq!(
+            std::fs::read_to_string(&log).unwrap().trim(),
+            format!("{} list", binary.display())
+        );
+    }
+
+    #[test]
+    fn test_truncate_stderr() {
+        use super::{truncate_stderr, MAX_STDERR_LEN};
+
+        assert_eq!(truncate_stderr(b"error"), "error");
+        let long = format!("{}panic", "é".repeat(MAX_STDERR_LEN));
+        let truncated = truncate_stderr(long.as_bytes());
+        assert!(truncated.starts_with("..."));
+        assert!(truncated.ends_with("panic"));
+        assert!(truncated.len() <= MAX_STDERR_LEN + 3);
+    }
+
+    #[test]
+    fn test_discovery_missing_dir() {
+        let dir = tempfile::TempDir::new().unwrap();
+        let missing = dir.path().join("missing");
+        assert!(matches!(
+            get_runtime_benchmark_groups(&missing, GroupSelection::all()),
+            Err(DiscoveryError::DirNotFound(path)) if path == missing
+        ));
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_discovery_permission_denied() {
+        use std::os::unix::fs::PermissionsExt;
+
+        // Permissions are not enforced for root.
+        if unsafe { libc::geteuid() } == 0 {
+            eprintln!("Skipping test, running as root");
+            return;
+        }
+
+        let dir = tempfile::TempDir::new().unwrap();
+        let forbidden = dir.path().join("forbidden");
+        std::fs::create_dir(&forbidden).unwrap();
+        std::fs::set_permissions(&forbidden, std::fs::Permissions::from_mode(0o000)).unwrap();
+
+        let result = get_runtime_benchmark_groups(&forbidden, GroupSelection::all());
+        std::fs::set_permissions(&forbidden, std::fs::Permissions::from_mode(0o755)).unwrap();
+        assert!(matches!(
+            result,
+            Err(DiscoveryError::PermissionDenied(path)) if path == forbidden
+        ));
+    }
+
+    #[cfg(target_os = "linux")]
+    #[test]
+    fn test_discovery_invalid_group_name() {
+        use std::os::unix::ffi::OsStrExt;
+
+        let dir = tempfile::TempDir::new().unwrap();
+        create_group_crate(dir.path(), "valid", "");
+        let invalid = dir.path().join(OsStr::from_bytes(b"invalid-\xff"));
+        std::fs::create_dir(&invalid).unwrap();
+        std::fs::write(invalid.join("Cargo.toml"), "").unwrap();
+
+        let error = get_runtime_benchmark_groups(dir.path(), GroupSelection::all()).unwrap_err();
+        assert!(error.to_string().contains("not valid UTF-8"), "{error}");
+        assert!(matches!(error, DiscoveryError::InvalidGroupName(path) if path == invalid));
+    }
+
+    #[test]
+    fn test_benchmarks_changed_in_diff() {
+        let dir = tempfile::TempDir::new().unwrap();
+        let repo = dir.path();
+        let git = |args: &[&str]| {
+            let status = Command::new("git")
+                .arg("-C")
+                .arg(repo)
+                .args(["-c", "user.name=test", "-c", "user.email=test@example.com"])
+                .args(args)
+                .output()
+                .unwrap()
+                .status;
+            assert!(status.success(), "git {args:?} failed");
+        };
+
+        let benchmarks = repo.join("runtime-benchmarks");
+        std::fs::create_dir(&benchmarks).unwrap();
+        create_group_crate(&benchmarks, "changed", "");
+        create_group_crate(&benchmarks, "unchanged", "");
+        std::fs::write(repo.join("README.md"), "").unwrap();
+        git(&["init", "--quiet"]);
+        git(&["add", "."]);
+        git(&["commit", "--quiet", "-m", "initial"]);
+
+        std::fs::write(benchmarks.join("changed/src/main.rs"), "fn main() { }").unwrap();
+        std::fs::write(repo.join("README.md"), "changed").unwrap();
+
+        assert_eq!(
+            benchmarks_changed_in_diff(&benchmarks, "HEAD").unwrap(),
+            vec!["changed".to_string()]
+        );
+    }
+
+    #[cfg(target_os = "linux")]
+    #[test]
+    fn test_force_rebuild_cleans_crate() {
+        use super::{prepare_runtime_benchmark_suite, CargoIsolationMode};
+        use crate::runtime::tests::{compiler_artifact, create_script};
+
+        let dir = tempfile::TempDir::new().unwrap();
+        let log = dir.path().join("cargo.log");
+        let cargo = create_script(
+            dir.path(),
+            "cargo",
+            &format!("echo \"$@\" >> {}", log.display()),
+        );
+        let mut toolchain = test_toolchain();
+        toolchain.components.cargo = cargo;
+        create_group_crate(dir.path(), "foo", "");
+
+        let target_dir = dir.path().join("target");
+        clean_benchmark_crate(
+            &toolchain,
+            &dir.path().join("foo"),
+            Some(&target_dir),
+            &RuntimeCompilationOpts::default(),
+        )
+        .unwrap();
+        assert_eq!(
+            std::fs::read_to_string(&log).unwrap().trim(),
+            format!(
+                "clean --profile release -p foo-bench --target-dir {}",
+                target_dir.display()
+            )
+        );
+
+        // A forced rebuild neither reuses the binary recorded in the resume file nor the one
+        // stored in the binary cache.
+        let log = dir.path().join("commands.log");
+        let binary = create_script(dir.path(), "hashmap-bench", r#"echo '["insert"]'"#);
+        let artifact = compiler_artifact(&binary);
+        toolchain.components.cargo = create_script(
+            dir.path(),
+            "cargo",
+            &format!(
+                "echo \"$1\" >> {}\nif [ \"$1\" = build ]; then echo '{artifact}'; fi",
+                log.display()
+            ),
+        );
+        toolchain.components.rustc =
+            create_script(dir.path(), "rustc", "echo rustc 1.80.0-nightly");
+        let benchmark_dir = dir.path().join("benchmarks");
+        create_group_crate(&benchmark_dir, "hashmap", "");
+        let cache_dir = dir.path().join("cache");
+        let resume_file = dir.path().join("resume.json");
+
+        let compile = |force_rebuild: bool| {
+            prepare_runtime_benchmark_suite(
+                &toolchain,
+                &benchmark_dir,
+                CargoIsolationMode::Cached,
+                GroupSelection::all(),
+                RuntimeCompilationOpts::default()
+                    .binary_cache(cache_dir.clone())
+                    .resume_file(resume_file.clone())
+                    .force_rebuild(force_rebuild),
+            )
+            .unwrap()
+            .extract_suite()
+        };
+        let commands = || -> Vec<String> {
+            std::fs::read_to_string(&log)
+                .unwrap()
+                .lines()
+                .map(|line| line.to_string())
+                .collect()
+        };
+
+        let suite = compile(false);
+        assert_eq!(commands(), ["build"]);
+        assert!(!suite.groups[0].cached);
+
+        // Without forcing, the binary is reused.
+        let suite = compile(false);
+        assert_eq!(commands(), ["build"]);
+        assert!(suite.groups[0].cached);
+
+        let suite = compile(true);
+        assert_eq!(commands(), ["build", "clean", "build"]);
+        let group = &suite.groups[0];
+        assert!(!group.cached);
+        assert_eq!(group.binary, binary);
+        assert_eq!(group.benchmark_names, ["insert"]);
+    }
+
+    #[cfg(target_os = "linux")]
+    #[test]
+    fn test_parallel_compilation() {
+        use std::collections::HashSet;
+
+        use super::{prepare_runtime_benchmark_suite, CargoIsolationMode};
+        use crate::runtime_group_step_name;
+
+        let dir = tempfile::TempDir::new().unwrap();
+        let log = dir.path().join("cargo.log");
+        let intervals = dir.path().join("intervals.log");
+        // Pretends to be a slow build that fails, logging its target directory and the times (in
+        // nanoseconds) at which it has started and ended.
+        let cargo = crate::runtime::tests::create_script(
+            dir.path(),
+            "cargo",
+            &format!(
+                r#"start=$(date +%s%N)
+while [ $# -gt 0 ]; do
+  if [ "$1" = "--target-dir" ]; then echo "$2" >> {}; fi
+  shift
+done
+sleep 0.5
+echo "$start $(date +%s%N)" >> {}
+exit 1"#,
+                log.display(),
+                intervals.display()
+            ),
+        );
+        let mut toolchain = test_toolchain();
+        toolchain.components.cargo = cargo;
+        let benchmark_dir = dir.path().join("benchmarks");
+        let names = ["a", "b", "c", "d"];
+        for name in names {
+            create_group_crate(&benchmark_dir, name, "");
+            std::fs::write(benchmark_dir.join(name).join("Cargo.lock"), "").unwrap();
+        }
+
+        let compilation = prepare_runtime_benchmark_suite(
+            &toolchain,
+            &benchmark_dir,
+            CargoIsolationMode::Isolated,
+            GroupSelection::all(),
+            RuntimeCompilationOpts::default().max_parallel_groups(4),
+        )
+        .unwrap();
+        assert!(compilation.suite.groups.is_empty());
+        let mut failed: Vec<&str> = compilation
+            .failed_to_compile
+            .keys()
+            .map(|name| name.as_str())
+            .collect();
+        failed.sort();
+        let expected: Vec<String> = names
+            .iter()
+            .map(|name| runtime_group_step_name(name))
+            .collect();
+        assert_eq!(failed, expected);
+
+        // All builds have been running at the same time.
+        let intervals: Vec<(u128, u128)> = std::fs::read_to_string(&intervals)
+            .unwrap()
+            .lines()
+            .map(|line| {
+                let (start, end) = line.split_once(' ').unwrap();
+                (start.parse().unwrap(), end.parse().unwrap())
+            })
+            .collect();
+        assert_eq!(intervals.len(), names.len());
+        let last_start = intervals.iter().map(|&(start, _)| start).max().unwrap();
+        let first_end = intervals.iter().map(|&(_, end)| end).min().unwrap();
+        assert!(
+            last_start < first_end,
+            "the builds have not overlapped: {intervals:?}"
+        );
+
+        // Each concurrent build has used its own target directory.
+        let log = std::fs::read_to_string(&log).unwrap();
+        let target_dirs: HashSet<&str> = log.lines().collect();
+        assert_eq!(target_dirs.len(), names.len());
+
+        let error = prepare_runtime_benchmark_suite(
+            &toolchain,
+            &benchmark_dir,
+            CargoIsolationMode::Isolated,
+            GroupSelection::all(),
+            RuntimeCompilationOpts::default().max_parallel_groups(0),
+        )
+        .unwrap_err()
+        .to_string();
+        assert!(error.contains("must be at least 1"), "{error}");
+    }
+
+    #[cfg(target_os = "linux")]
+    #[test]
+    fn test_build_memory_limit() {
+        use super::{prepare_runtime_benchmark_suite, CargoIsolationMode};
+        use crate::runtime_group_step_name;
+
+        let dir = tempfile::TempDir::new().unwrap();
+        // Pretends to be a build that allocates ~100 MB.
+        let cargo = crate::runtime::tests::create_script(
+            dir.path(),
+            "cargo",
+            "x=$(head -c 100000000 /dev/zero | tr '\\0' a)",
+        );
+        let mut toolchain = test_toolchain();
+        toolchain.components.cargo = cargo;
+        let benchmark_dir = dir.path().join("benchmarks");
+        create_group_crate(&benchmark_dir, "hungry", "");
+
+        let compile = |opts: RuntimeCompilationOpts| {
+            let compilation = prepare_runtime_benchmark_suite(
+                &toolchain,
+                &benchmark_dir,
+                CargoIsolationMode::Cached,
+                GroupSelection::all(),
+                opts,
+            )
+            .unwrap();
+            assert!(compilation.suite.groups.is_empty());
+            compilation.failed_to_compile[&runtime_group_step_name("hungry")].clone()
+        };
+
+        let error = compile(RuntimeCompilationOpts::default().build_memory_limit(32 << 20));
+        assert!(
+            error.contains("Failed to compile runtime benchmark `hungry`"),
+            "{error}"
+        );
+        // Without the limit, the build succeeds (but does not produce any binary).
+        let error = compile(RuntimeCompilationOpts::default());
+        assert!(error.contains("has not produced any binary"), "{error}");
+    }
+
+    #[cfg(target_os = "linux")]
+    #[test]
+    fn test_compile_timeout() {
+        use super::{prepare_runtime_benchmark_suite, CargoIsolationMode};
+        use crate::runtime_group_step_name;
+
+        let dir = tempfile::TempDir::new().unwrap();
+        // Pretends to be a build of `slow` that hangs.
+        let cargo = crate::runtime::tests::create_script(
+            dir.path(),
+            "cargo",
+            r#"case "$PWD" in */slow) exec sleep 10;; esac"#,
+        );
+        let mut toolchain = test_toolchain();
+        toolchain.components.cargo = cargo;
+        let benchmark_dir = dir.path().join("benchmarks");
+        create_group_crate(&benchmark_dir, "fast", "");
+        create_group_crate(&benchmark_dir, "slow", "");
+
+        let start = Instant::now();
+        let compilation = prepare_runtime_benchmark_suite(
+            &toolchain,
+            &benchmark_dir,
+            CargoIsolationMode::Cached,
+            GroupSelection::all(),
+            RuntimeCompilationOpts::default().compile_timeout(Duration::from_millis(200)),
+        )
+        .unwrap();
+        assert!(start.elapsed() < Duration::from_secs(5));
+
+        let error = &compilation.failed_to_compile[&runtime_group_step_name("slow")];
+        assert!(
+            error.contains("Compilation of runtime benchmark group `slow` timed out after 200ms"),
+            "{error}"
+        );
+        // The other group is still compiled (but does not produce any binary).
+        let error = &compilation.failed_to_compile[&runtime_group_step_name("fast")];
+        assert!(error.contains("has not produced any binary"), "{error}");
+    }
+
+    #[cfg(target_os = "linux")]
+    #[test]
+    fn test_fail_fast() {
+        use super::{prepare_runtime_benchmark_suite, CargoIsolationMode};
+
+        let dir = tempfile::TempDir::new().unwrap();
+        let log = dir.path().join("log");
+        let message = serde_json::json!({
+            "reason": "compiler-message",
+            "package_id": "a 0.1.0 (path+file:///a)",
+            "manifest_path": "/a/Cargo.toml",
+            "target": {
+                "kind": ["bin"],
+                "crate_types": ["bin"],
+                "name": "a",
+                "src_path": "/a/src/main.rs",
+                "edition": "2021",
+                "doctest": false,
+                "test": true,
+                "doc": true
+            },
+            "message": {
+                "message": "cannot find value `x` in this scope",
+                "code": null,
+                "level": "error",
+                "spans": [],
+                "children": [],
+                "rendered": "error[E0425]: cannot find value `x` in this scope"
+            }
+        });
+        // Pretends to be a build that fails with a compiler error.
+        let cargo = crate::runtime::tests::create_script(
+            dir.path(),
+            "cargo",
+            &format!(
+                "basename \"$PWD\" >> {}\necho '{message}'\nexit 101",
+                log.display()
+            ),
+        );
+        let mut toolchain = test_toolchain();
+        toolchain.components.cargo = cargo;
+        let benchmark_dir = dir.path().join("benchmarks");
+        create_group_crate(&benchmark_dir, "a", "");
+        create_group_crate(&benchmark_dir, "b", "");
+
+        let compile = |fail_fast| {
+            std::fs::write(&log, "").unwrap();
+            prepare_runtime_benchmark_suite(
+                &toolchain,
+                &benchmark_dir,
+                CargoIsolationMode::Cached,
+                GroupSelection::all(),
+                RuntimeCompilationOpts::default().fail_fast(fail_fast),
+            )
+        };
+
+        let compilation = compile(false).unwrap();
+        assert_eq!(compilation.failed_to_compile.len(), 2);
+        assert_eq!(std::fs::read_to_string(&log).unwrap(), "a\nb\n");
+
+        let error = format!("{:?}", compile(true).err().unwrap());
+        assert!(error.contains("runtime benchmark a"), "{error}");
+        assert!(error.contains("error[E0425]"), "{error}");
+        assert_eq!(std::fs::read_to_string(&log).unwrap(), "a\n");
+    }
+
+    #[cfg(target_os = "linux")]
+    #[test]
+    fn test_rustflags() {
+        use super::start_cargo_build;
+
+        let dir = tempfile::TempDir::new().unwrap();
+        let rustflags_log = dir.path().join("rustflags");
+        let cargo = crate::runtime::tests::create_script(
+            dir.path(),
+            "cargo",
+            &format!(r#"echo "$RUSTFLAGS" > {}"#, rustflags_log.display()),
+        );
+        let mut toolchain = test_toolchain();
+        toolchain.components.cargo = cargo;
+        create_group_crate(dir.path(), "foo", "");
+
+        let iter = start_cargo_build(
+            &toolchain,
+            &dir.path().join("foo"),
+            None,
+            &RuntimeCompilationOpts::default().rustflags("-C target-feature=+avx2"),
+        )
+        .unwrap();
+        iter.finish().unwrap();
+        assert!(std::fs::read_to_string(&rustflags_log)
+            .unwrap()
+            .trim()
+            .ends_with("-C target-feature=+avx2"));
+    }
+
+    #[cfg(target_os = "linux")]
+    #[test]
+    fn test_llvm_ir_size() {
+        use super::record_llvm_ir_size;
+
+        let dir = tempfile::TempDir::new().unwrap();
+        let rustflags_log = dir.path().join("rustflags");
+        // Pretends to emit LLVM IR (and other artifacts) into the target directory.
+        let cargo = crate::runtime::tests::create_script(
+            dir.path(),
+            "cargo",
+            &format!(
+                r#"while [ $# -gt 0 ]; do
+  if [ "$1" = "--target-dir" ]; then target="$2"; fi
+  shift
+done
+echo "$RUSTFLAGS" > {}
+mkdir -p "$target/release/deps"
+head -c 100 /dev/zero > "$target/release/deps/foo.ll"
+head -c 50 /dev/zero > "$target/release/deps/libdep.ll"
+head -c 1000 /dev/zero > "$target/release/deps/libdep.rlib""#,
+                rustflags_log.display()
+            ),
+        );
+        let mut toolchain = test_toolchain();
+        toolchain.components.cargo = cargo;
+        create_group_crate(dir.path(), "foo", "");
+
+        let mut group = BenchmarkGroup {
+            binary: "foo".into(),
+            name: "foo".to_string(),
+            benchmark_names: vec![],
+            features: vec![],
+            cached: false,
+            binary_size: 0,
+            original_names: Default::default(),
+            tags: vec![],
+            llvm_ir_size: None,
+            protocol_version: None,
+        };
+        record_llvm_ir_size(
+            &mut group,
+            &toolchain,
+            &dir.path().join("foo"),
+            &RuntimeCompilationOpts::default().llvm_ir_size(true),
+        )
+        .unwrap();
+        assert_eq!(group.llvm_ir_size, Some(150));
+        assert!(std::fs::read_to_string(&rustflags_log)
+            .unwrap()
+            .trim()
+            .ends_with("--emit=llvm-ir"));
+    }
+
+    #[test]
+    fn test_check_disk_usage() {
+        let dir = tempfile::TempDir::new().unwrap();
+        let target_dir = dir.path().join("target");
+        // Pretends to compile a group, creating its artifacts.
+        let build = |group: &str| {
+            let path = target_dir.join("release").join(group);
+            std::fs::create_dir_all(&path).unwrap();
+            std::fs::write(path.join("binary"), vec![0; 600]).unwrap();
+        };
+
+        build("a");
+        assert_eq!(check_disk_usage(&target_dir, 1000, "a").unwrap(), 600);
+        build("b");
+        let error = check_disk_usage(&target_dir, 1000, "b")
+            .unwrap_err()
+            .to_string();
+        assert!(error.contains("1200 bytes after compiling `b`"), "{error}");
+        assert!(error.contains("limit of 1000 bytes"), "{error}");
+    }
+
+    #[test]
+    fn test_cache_hit_rate() {
+        let compilation = |rebuilt_groups, cached_groups| BenchmarkSuiteCompilation {
+            suite: BenchmarkSuite::from_groups(test_toolchain(), vec![]),
+            failed_to_compile: Default::default(),
+            rebuilt_groups,
+            cached_groups,
+            build_timings: vec![],
+        };
+        assert_eq!(compilation(1, 3).cache_hit_rate(), 0.75);
+        assert_eq!(compilation(2, 0).cache_hit_rate(), 0.0);
+        assert_eq!(compilation(0, 0).cache_hit_rate(), 0.0);
+    }
+
+    #[test]
+    fn test_failure_report() {
+        use crate::runtime_group_step_name;
+
+        let group = BenchmarkGroup {
+            binary: "/target/release/hashmap".into(),
+            name: "hashmap".to_string(),
+            benchmark_names: vec!["insert".to_string()],
+            features: vec![],
+            cached: false,
+            binary_size: 0,
+            original_names: Default::default(),
+            tags: vec![],
+            llvm_ir_size: None,
+            protocol_version: None,
+        };
+        let compilation = BenchmarkSuiteCompilation {
+            suite: BenchmarkSuite::from_groups(test_toolchain(), vec![group]),
+            failed_to_compile: [
+                (
+                    runtime_group_step_name("nbody"),
+                    "error: linking failed".to_string(),
+                ),
+                (
+                    runtime_group_step_name("bufreader"),
+                    "error[E0425]".to_string(),
+                ),
+            ]
+            .into(),
+            rebuilt_groups: 1,
+            cached_groups: 0,
+            build_timings: vec![],
+        };
+
+        let mut out = Vec::new();
+        compilation.write_failure_report(&mut out).unwrap();
+        let json: serde_json::Value = serde_json::from_slice(&out).unwrap();
+        assert_eq!(
+            json,
+            serde_json::json!({
+                "succeeded": 1,
+                "failed": 2,
+                "failures": {
+                    runtime_group_step_name("bufreader"): "error[E0425]",
+                    runtime_group_step_name("nbody"): "error: linking failed",
+                },
+            })
+        );
+    }
+
+    #[test]
+    fn test_binary_is_current() {
+        use std::time::SystemTime;
+
+        use super::binary_is_current;
+
+        let dir = tempfile::TempDir::new().unwrap();
+        create_group_crate(dir.path(), "hashmap", "");
+        let group_crate = BenchmarkGroupCrate {
+            name: "hashmap".to_string(),
+            path: dir.path().join("hashmap"),
+            tags: vec![],
+        };
+        let set_mtime = |path: &Path, mtime: SystemTime| {
+            std::fs::File::options()
+                .write(true)
+                .open(path)
+                .unwrap()
+                .set_modified(mtime)
+                .unwrap();
+        };
+        let now = SystemTime::now();
+        for file in ["Cargo.toml", "src/main.rs"] {
+            set_mtime(&group_crate.path.join(file), now - Duration::from_secs(20));
+        }
+        let binary = group_crate.path.join("target/release/hashmap-bench");
+        std::fs::create_dir_all(binary.parent().unwrap()).unwrap();
+        std::fs::write(&binary, "binary").unwrap();
+        set_mtime(&binary, now - Duration::from_secs(10));
+        assert!(binary_is_current(&group_crate, &binary).unwrap());
+
+        // Files in the target directory do not matter.
+        std::fs::write(group_crate.path.join("target/release/.fingerprint"), "").unwrap();
+        assert!(binary_is_current(&group_crate, &binary).unwrap());
+
+        set_mtime(&group_crate.path.join("src/main.rs"), now);
+        assert!(!binary_is_current(&group_crate, &binary).unwrap());
+
+        assert!(binary_is_current(&group_crate, &dir.path().join("missing")).is_err());
+    }
+
+    #[test]
+    fn test_try_extract_suite() {
+        use crate::runtime_group_step_name;
+
+        let compilation = |failed_to_compile| BenchmarkSuiteCompilation {
+            suite: BenchmarkSuite::from_groups(test_toolchain(), vec![]),
+            failed_to_compile,
+            rebuilt_groups: 0,
+            cached_groups: 0,
+            build_timings: vec![],
+        };
+        assert!(compilation(Default::default()).try_extract_suite().is_ok());
+
+        let error = compilation(
+            [
+                (
+                    runtime_group_step_name("nbody"),
+                    "linking failed".to_string(),
+                ),
+                (
+                    runtime_group_step_name("bufreader"),
+                    "error[E0425]".to_string(),
+                ),
+            ]
+            .into(),
+        )
+        .try_extract_suite()
+        .unwrap_err()
+        .to_string();
+        assert!(error.starts_with("Cannot extract runtime suite"), "{error}");
+        let bufreader = error.find("error[E0425]").unwrap();
+        let nbody = error.find("linking failed").unwrap();
+        assert!(bufreader < nbody, "{error}");
+    }
+
+    #[test]
+    fn test_retain_by_binary_size() {
+        let group = |name: &str, binary_size| BenchmarkGroup {
+            binary: name.into(),
+            name: name.to_string(),
+            benchmark_names: vec![],
+            features: vec![],
+            cached: false,
+            binary_size,
+            original_names: Default::default(),
+            tags: vec![],
+            llvm_ir_size: None,
+            protocol_version: None,
+        };
+        let names = |groups: &[BenchmarkGroup]| -> Vec<String> {
+            groups.iter().map(|group| group.name.clone()).collect()
+        };
+        let groups = || {
+            vec![
+                group("small", 100),
+                group("medium", 1000),
+                group("large", 10000),
+            ]
+        };
+
+        let mut kept = groups();
+        retain_by_binary_size(&mut kept, None, None);
+        assert_eq!(names(&kept), ["small", "medium", "large"]);
+
+        let mut kept = groups();
+        retain_by_binary_size(&mut kept, Some(1000), None);
+        assert_eq!(names(&kept), ["medium", "large"]);
+
+        let mut kept = groups();
+        retain_by_binary_size(&mut kept, Some(500), Some(1000));
+        assert_eq!(names(&kept), ["medium"]);
+    }
+
+    #[test]
+    fn test_retain_by_compile_time() {
+        use crate::runtime::CompileTimeHistory;
+
+        let mut history = CompileTimeHistory::default();
+        history.set_compile_time("fast", Duration::from_secs(5));
+        history.set_compile_time("slow", Duration::from_secs(300));
+        let mut crates: Vec<BenchmarkGroupCrate> = ["fast", "slow", "new"]
+            .into_iter()
+            .map(|name| BenchmarkGroupCrate {
+                name: name.to_string(),
+                path: name.into(),
+                tags: vec![],
+            })
+            .collect();
+
+        retain_by_compile_time(&mut crates, Duration::from_secs(60), &history);
+        let names: Vec<&str> = crates.iter().map(|c| c.name.as_str()).collect();
+        assert_eq!(names, ["fast", "new"]);
+    }
+
+    #[cfg(target_os = "linux")]
+    #[test]
+    fn test_protocol_version_mismatch() {
+        use super::protocol_version_mismatch;
+
+        let dir = tempfile::TempDir::new().unwrap();
+        let group = |name: &str, version: Option<u32>| {
+            // Binaries of an older benchlib only list the names of the benchmarks.
+            let body = match version {
+                Some(version) => format!(
+                    "if [ -n \"$RUSTC_PERF_LIST_PROTOCOL_VERSION\" ]; then \
+echo '{{\"benchmarks\": [], \"protocol_version\": {version}}}'; else exit 2; fi"
+                ),
+                None => "echo '[]'".to_string(),
+            };
+            let binary = crate::runtime::tests::create_script(dir.path(), name, &body);
+            BenchmarkGroup {
+                protocol_version: gather_benchmarks(&binary, &[], "list", None).unwrap().1,
+                binary,
+                name: name.to_string(),
+                benchmark_names: vec![],
+                features: vec![],
+                cached: false,
+                binary_size: 0,
+                original_names: Default::default(),
+                tags: vec![],
+                llvm_ir_size: None,
+            }
+        };
+
+        let groups = vec![
+            group("a", Some(1)),
+            group("b", Some(1)),
+            group("c", Some(1)),
+        ];
+        assert_eq!(groups[0].protocol_version, Some(1));
+        assert_eq!(protocol_version_mismatch(&groups), None);
+
+        let groups = vec![
+            group("a", Some(1)),
+            group("b", Some(2)),
+            group("c", Some(1)),
+        ];
+        assert_eq!(
+            protocol_version_mismatch(&groups).unwrap(),
+            "Runtime benchmark groups use different benchlib protocol versions, most use \
+version 1, but these differ: `b` (version 2)"
+        );
+
+        let groups = vec![group("a", Some(2)), group("b", None)];
+        assert!(protocol_version_mismatch(&groups)
+            .unwrap()
+            .ends_with("most use version 2, but these differ: `b` (an unknown version)"));
+    }
+
+    #[cfg(target_os = "linux")]
+    #[test]
+    fn test_suite_from_target_dir() {
+        use super::{suite_from_target_dir, RuntimeCompilationOpts};
+        use crate::runtime::tests::create_script;
+
+        let dir = tempfile::TempDir::new().unwrap();
+        // Reports a library and a binary target named after the package.
+        let cargo = create_script(
+            dir.path(),
+            "cargo",
+            r#"name="$(basename "$PWD")-bench"
+echo "{\"packages\":[{\"name\":\"$name\",\"targets\":[{\"kind\":[\"lib\"],\"name\":\"lib$name\"},{\"kind\":[\"bin\"],\"name\":\"$name\"}]}]}""#,
+        );
+        let mut toolchain = test_toolchain();
+        toolchain.components.cargo = cargo;
+        let benchmark_dir = dir.path().join("benchmarks");
+        create_group_crate(&benchmark_dir, "hashmap", "");
+        create_group_crate(&benchmark_dir, "nbody", "");
+
+        // A target directory left behind by a previous build.
+        let target_dir = dir.path().join("target");
+        let release_dir = target_dir.join("release");
+        std::fs::create_dir_all(&release_dir).unwrap();
+        create_script(
+            &release_dir,
+            "hashmap-bench",
+            r#"echo '["insert", "remove"]'"#,
+        );
+        create_script(&release_dir, "nbody-bench", r#"echo '["nbody"]'"#);
+
+        let opts = RuntimeCompilationOpts::default();
+        let suite = suite_from_target_dir(&toolchain, &benchmark_dir, &target_dir, &opts).unwrap();
+        assert_eq!(
+            suite.qualified_names().collect::<Vec<_>>(),
+            ["hashmap/insert", "hashmap/remove", "nbody/nbody"]
+        );
+        assert_eq!(suite.groups[0].binary, release_dir.join("hashmap-bench"));
+        assert!(suite.groups.iter().all(|group| group.cached));
+
+        // The binaries of a cross-compiled build are executed through the runner, and the
+        // compile settings of the build are recorded in the suite.
+        let triple_dir = target_dir.join("aarch64-unknown-linux-gnu").join("release");
+        std::fs::create_dir_all(&triple_dir).unwrap();
+        std::fs::rename(
+            release_dir.join("hashmap-bench"),
+            triple_dir.join("hashmap-bench"),
+        )
+        .unwrap();
+        std::fs::rename(
+            release_dir.join("nbody-bench"),
+            triple_dir.join("nbody-bench"),
+        )
+        .unwrap();
+        let runner = create_script(dir.path(), "runner", r#"exec "$@""#);
+        let opts = RuntimeCompilationOpts::default()
+            .target("aarch64-unknown-linux-gnu")
+            .runner(vec![runner.display().to_string()])
+            .lto("thin");
+        let suite = suite_from_target_dir(&toolchain, &benchmark_dir, &target_dir, &opts).unwrap();
+        assert_eq!(suite.groups[1].binary, triple_dir.join("nbody-bench"));
+        assert_eq!(suite.benchmark_count(), 3);
+        assert_eq!(suite.compile_settings.lto.as_deref(), Some("thin"));
+
+        std::fs::remove_file(triple_dir.join("nbody-bench")).unwrap();
+        let error = suite_from_target_dir(&toolchain, &benchmark_dir, &target_dir, &opts)
+            .err()
+            .unwrap()
+            .to_string();
+        assert!(
+            error.contains("Binary of runtime benchmark group `nbody` not found"),
+            "{error}"
+        );
+    }
+
+    #[cfg(target_os = "linux")]
+    #[test]
+    fn test_binary_cache() {
+        use super::{prepare_runtime_benchmark_suite, CargoIsolationMode};
+        use crate::runtime::tests::create_script;
+
+        let dir = tempfile::TempDir::new().unwrap();
+        let log = dir.path().join("log");
+        let version = dir.path().join("version");
+        std::fs::write(&version, "rustc 1.80.0-nightly").unwrap();
+        let binary = create_script(dir.path(), "hashmap-bench", r#"echo '["insert"]'"#);
+        let artifact = crate::runtime::tests::compiler_artifact(&binary);
+        let mut toolchain = test_toolchain();
+        toolchain.components.cargo = create_script(
+            dir.path(),
+            "cargo",
+            &format!("echo build >> {}\necho '{artifact}'", log.display()),
+        );
+        toolchain.components.rustc =
+            create_script(dir.path(), "rustc", &format!("cat {}", version.display()));
+        let benchmark_dir = dir.path().join("benchmarks");
+        create_group_crate(&benchmark_dir, "hashmap", "");
+        std::fs::write(benchmark_dir.join("hashmap/Cargo.lock"), "").unwrap();
+        let cache_dir = dir.path().join("cache");
+
+        let compile = || {
+            prepare_runtime_benchmark_suite(
+                &toolchain,
+                &benchmark_dir,
+                CargoIsolationMode::Isolated,
+                GroupSelection::all(),
+                RuntimeCompilationOpts::default().binary_cache(cache_dir.clone()),
+            )
+            .unwrap()
+        };
+        let builds = || std::fs::read_to_string(&log).unwrap().lines().count();
+
+        let compilation = compile();
+        assert_eq!(builds(), 1);
+        assert!(!compilation.suite.groups[0].cached);
+        assert_eq!(
+            (compilation.rebuilt_groups, compilation.cached_groups),
+            (1, 0)
+        );
+
+        // The binary is reused even if the original one does not exist anymore. Such a group
+        // counts as a cache hit also in the isolated mode.
+        std::fs::rename(&binary, dir.path().join("moved")).unwrap();
+        let compilation = compile();
+        assert_eq!(builds(), 1);
+        assert_eq!(compilation.cache_hit_rate(), 1.0);
+        let suite = compilation.extract_suite();
+        assert!(suite.groups[0].cached);
+        assert!(suite.groups[0].binary.starts_with(&cache_dir));
+        assert_eq!(suite.groups[0].benchmark_names, ["insert"]);
+        std::fs::rename(dir.path().join("moved"), &binary).unwrap();
+
+        // Changing the sources or the toolchain invalidates the cached binary.
+        std::fs::write(benchmark_dir.join("hashmap/src/main.rs"), "fn main() { }").unwrap();
+        compile();
+        assert_eq!(builds(), 2);
+        compile();
+        assert_eq!(builds(), 2);
+        std::fs::write(&version, "rustc 1.81.0-nightly").unwrap();
+        compile();
+        assert_eq!(builds(), 3);
+    }
+
+    #[cfg(target_os = "linux")]
+    #[test]
+    fn test_workspace() {
+        use super::{prepare_runtime_benchmark_suite, CargoIsolationMode};
+        use crate::runtime::tests::{compiler_artifact, create_script};
+        use crate::runtime_group_step_name;
+
+        let dir = tempfile::TempDir::new().unwrap();
+        let log = dir.path().join("log");
+        let benchmark_dir = dir.path().join("benchmarks");
+        let mut artifacts = vec![];
+        for (group, benchmark) in [("hashmap", "insert"), ("nbody", "nbody")] {
+            create_group_crate(&benchmark_dir, group, "");
+            let binary = create_script(
+                dir.path(),
+                &format!("{group}-bench"),
+                &format!(r#"echo '["{benchmark}"]'"#),
+            );
+            // The binaries are mapped to the groups by the manifests of their packages.
+            let mut artifact = compiler_artifact(&binary);
+            artifact["manifest_path"] =
+                serde_json::json!(benchmark_dir.join(group).join("Cargo.toml"));
+            artifacts.push(artifact);
+        }
+        std::fs::write(
+            benchmark_dir.join("Cargo.toml"),
+            "[workspace]\nmembers = [\"hashmap\", \"nbody\"]\n",
+        )
+        .unwrap();
+
+        let mut toolchain = test_toolchain();
+        let compile = |toolchain: &Toolchain| {
+            prepare_runtime_benchmark_suite(
+                toolchain,
+                &benchmark_dir,
+                CargoIsolationMode::Cached,
+                GroupSelection::all(),
+                RuntimeCompilationOpts::default().workspace(true),
+            )
+        };
+
+        toolchain.components.cargo = create_script(
+            dir.path(),
+            "cargo",
+            &format!(
+                "echo \"$@\" >> {}\necho '{}'\necho '{}'",
+                log.display(),
+                artifacts[0],
+                artifacts[1]
+            ),
+        );
+        let suite = compile(&toolchain).unwrap().extract_suite();
+        let groups: Vec<(&str, &[String])> = suite
+            .groups
+            .iter()
+            .map(|group| (group.name.as_str(), group.benchmark_names.as_slice()))
+            .collect();
+        assert_eq!(
+            groups,
+            [
+                ("hashmap", &["insert".to_string()][..]),
+                ("nbody", &["nbody".to_string()][..])
+            ]
+        );
+        // Both groups are compiled by a single build.
+        let builds = std::fs::read_to_string(&log).unwrap();
+        assert_eq!(builds.lines().count(), 1);
+        assert!(
+            builds.contains("-p hashmap-bench -p nbody-bench"),
+            "{builds}"
+        );
+
+        // Groups whose binaries were reported before the build failed are still compiled.
+        toolchain.components.cargo = create_script(
+            dir.path(),
+            "cargo",
+            &format!("echo '{}'\nexit 1", artifacts[0]),
+        );
+        let compilation = compile(&toolchain).unwrap();
+        assert_eq!(compilation.suite.groups.len(), 1);
+        assert_eq!(compilation.suite.groups[0].name, "hashmap");
+        assert_eq!(
+            compilation.failed_to_compile.keys().collect::<Vec<_>>(),
+            [&runtime_group_step_name("nbody")]
+        );
+
+        std::fs::remove_file(benchmark_dir.join("Cargo.toml")).unwrap();
+        let error = compile(&toolchain).err().unwrap().to_string();
+        assert!(error.contains("is not a Cargo workspace"), "{error}");
+    }
+
+    #[test]
+    fn test_reserved_benchmark_names() {
+        use super::check_reserved_names;
+
+        let names =
+            |names: &[&str]| -> Vec<String> { names.iter().map(|name| name.to_string()).collect() };
+        assert!(check_reserved_names("group", &names(&["insert", "listing"]), "list").is_ok());
+
+        let error = check_reserved_names("group", &names(&["insert", "list"]), "list")
+            .unwrap_err()
+            .to_string();
+        assert!(
+            error.contains("Runtime benchmark `list` of group `group` is named like a subcommand"),
+            "{error}"
+        );
+        assert!(
+            check_reserved_names("group", &names(&["list-benchmarks"]), "list-benchmarks").is_err()
+        );
+    }
+
+    #[test]
+    fn test_name_transform() {
+        let strip_prefix = |name: &str| name.trim_start_matches("bench-").to_string();
+        let (names, original_names) = transform_names(
+            vec!["bench-insert".to_string(), "bench-remove".to_string()],
+            Some(&strip_prefix),
+        );
+        assert_eq!(names, ["insert", "remove"]);
+        assert_eq!(original_names["insert"], "bench-insert");
+        assert_eq!(original_names["remove"], "bench-remove");
+
+        let (names, original_names) = transform_names(vec!["bench-insert".to_string()], None);
+        assert_eq!(names, ["bench-insert"]);
+        assert!(original_names.is_empty());
+
+        // Duplicates are checked on the transformed names.
+        let group = |name: &str, benchmarks: &[&str]| {
+            let (benchmark_names, original_names) = transform_names(
+                benchmarks.iter().map(|b| b.to_string()).collect(),
+                Some(&strip_prefix),
+            );
+            BenchmarkGroup {
+                binary: name.into(),
+                name: name.to_string(),
+                benchmark_names,
+                features: vec![],
+                cached: false,
+                binary_size: 0,
+                original_names,
+                tags: vec![],
+                llvm_ir_size: None,
+                protocol_version: None,
+            }
+        };
+        assert!(
+            check_duplicates(&[group("a", &["bench-insert"]), group("b", &["insert"])]).is_err()
+        );
+    }
+
+    #[test]
+    fn test_check_duplicates() {
+        let group = |name: &str, benchmarks: &[&str]| BenchmarkGroup {
+            binary: name.into(),
+            name: name.to_string(),
+            benchmark_names: benchmarks.iter().map(|b| b.to_string()).collect(),
+            features: vec![],
+            cached: false,
+            binary_size: 0,
+            original_names: Default::default(),
+            tags: vec![],
+            llvm_ir_size: None,
+            protocol_version: None,
+        };
+        assert!(
+            check_duplicates(&[group("a", &["insert", "remove"]), group("b", &["push"])]).is_ok()
+        );
+
+        let error = check_duplicates(&[group("a", &["insert", "remove", "insert"])])
+            .unwrap_err()
+            .to_string();
+        assert!(error.contains("`insert` in `a` (2 times)"), "{error}");
+
+        // All duplicates are reported at once.
+        let error = check_duplicates(&[
+            group("a", &["insert", "remove"]),
+            group("b", &["insert", "push", "push"]),
+            group("c", &["insert", "remove"]),
+        ])
+        .unwrap_err()
+        .to_string();
+        assert_eq!(
+            error,
+            "Duplicated runtime benchmark names:
+`insert` in `a`, `b`, `c`
+`push` in `b` (2 times)
+`remove` in `a`, `c`"
+        );
+    }
+
+    #[test]
+    fn test_build_critical_path() {
+        let start = Instant::now();
+        let timing = |group: &str, from: u64, to: u64| GroupBuildTiming {
+            group: group.to_string(),
+            start: start + Duration::from_secs(from),
+            end: start + Duration::from_secs(to),
+        };
+        let mut compilation = BenchmarkSuiteCompilation {
+            suite: BenchmarkSuite::from_groups(test_toolchain(), vec![]),
+            failed_to_compile: Default::default(),
+            rebuilt_groups: 0,
+            cached_groups: 0,
+            build_timings: vec![],
+        };
+        assert_eq!(compilation.build_critical_path(), None);
+        assert_eq!(compilation.build_wall_time(), Duration::ZERO);
+
+        compilation.build_timings = vec![timing("a", 0, 3), timing("b", 1, 8), timing("c", 2, 10)];
+        assert_eq!(
+            compilation.build_critical_path(),
+            Some(("b".to_string(), Duration::from_secs(7)))
+        );
+        assert_eq!(compilation.build_wall_time(), Duration::from_secs(10));
     }
-    groups.sort_unstable_by(|a, b| a.name.cmp(&b.name));
-    Ok(groups)
 }
@@ -0,0 +1,206 @@
+use std::path::{Path, PathBuf};
+use std::process::Command;
+
+use anyhow::Context;
+
+use crate::runtime::BenchmarkGroup;
+use crate::toolchain::Toolchain;
+
+/// Name of the file with the metadata of a cache entry.
+const ENTRY_FILE: &str = "group.json";
+
+/// Content-addressed cache of compiled runtime benchmark binaries, which can be shared by
+/// multiple invocations of the collector (and by different benchmark directories).
+///
+/// Each entry is stored in `<dir>/<group>-<key>/`, where the key is a hash of the sources of the
+/// benchmark crate, of the toolchain and of the compilation options. The binary is copied into
+/// the entry, so it does not depend on the target directory used by the build.
+pub struct BinaryCache {
+    dir: PathBuf,
+}
+
+#[derive(serde::Serialize, serde::Deserialize)]
+struct CacheEntry {
+    /// File name of the binary inside the entry directory.
+    binary: String,
+    /// Names of the benchmarks listed by the binary, before applying any name transform.
+    benchmarks: Vec<String>,
+    features: Vec<String>,
+    binary_size: u64,
+    llvm_ir_size: Option<u64>,
+    protocol_version: Option<u32>,
+}
+
+impl BinaryCache {
+    /// Opens the cache in `dir`, creating the directory if it does not exist.
+    pub fn open(dir: &Path) -> anyhow::Result<Self> {
+        std::fs::create_dir_all(dir)
+            .with_context(|| format!("Cannot create binary cache directory {}", dir.display()))?;
+        Ok(Self {
+            dir: dir.to_path_buf(),
+        })
+    }
+
+    /// Returns the cached group with the given name and key, if it exists. The names of its
+    /// benchmarks are rewritten by `name_transform`, like the names of freshly compiled groups.
+    pub fn cached_group(
+        &self,
+        name: &str,
+        key: u64,
+        name_transform: Option<&dyn Fn(&str) -> String>,
+    ) -> Option<BenchmarkGroup> {
+        let entry_dir = self.entry_dir(name, key);
+        let contents = std::fs::read(entry_dir.join(ENTRY_FILE)).ok()?;
+        let entry: CacheEntry = match serde_json::from_slice(&contents) {
+            Ok(entry) => entry,
+            Err(error) => {
+                log::warn!(
+                    "Ignoring corrupted binary cache entry {}: {error}",
+                    entry_dir.display()
+                );
+                return None;
+            }
+        };
+        let binary = entry_dir.join(&entry.binary);
+        if !binary.is_file() {
+            return None;
+        }
+        let (benchmark_names, original_names) =
+            super::benchmark::transform_names(entry.benchmarks, name_transform);
+        Some(BenchmarkGroup {
+            binary,
+            name: name.to_string(),
+            benchmark_names,
+            features: entry.features,
+            cached: true,
+            binary_size: entry.binary_size,
+            original_names,
+            // Tags are read from the manifest of the crate, not from the cache.
+            tags: vec![],
+            llvm_ir_size: entry.llvm_ir_size,
+            protocol_version: entry.protocol_version,
+        })
+    }
+
+    /// Copies the binary of a compiled group into the cache under the given key, replacing any
+    /// previous entry with the same key.
+    pub fn store(&self, group: &BenchmarkGroup, key: u64) -> anyhow::Result<()> {
+        let binary_name = group
+            .binary
+            .file_name()
+            .and_then(|name| name.to_str())
+            .ok_or_else(|| anyhow::anyhow!("Invalid binary path {}", group.binary.display()))?;
+        let entry = CacheEntry {
+            binary: binary_name.to_string(),
+            benchmarks: group
+                .benchmark_names
+                .iter()
+                .map(|name| group.original_names.get(name).unwrap_or(name).clone())
+                .collect(),
+            features: group.features.clone(),
+            binary_size: group.binary_size,
+            llvm_ir_size: group.llvm_ir_size,
+            protocol_version: group.protocol_version,
+        };
+
+        // Prepare the entry in a temporary directory first, so that an interrupted store (or a
+        // concurrent collector) never sees an incomplete entry.
+        let tmp_dir = tempfile::Builder::new()
+            .prefix(".tmp-")
+            .tempdir_in(&self.dir)
+            .context("Cannot create temporary directory in the binary cache")?;
+        std::fs::copy(&group.binary, tmp_dir.path().join(binary_name)).with_context(|| {
+            format!(
+                "Cannot copy {} into the binary cache",
+                group.binary.display()
+            )
+        })?;
+        std::fs::write(
+            tmp_dir.path().join(ENTRY_FILE),
+            serde_json::to_vec_pretty(&entry)?,
+        )?;
+
+        let entry_dir = self.entry_dir(&group.name, key);
+        if entry_dir.exists() {
+            std::fs::remove_dir_all(&entry_dir).with_context(|| {
+                format!("Cannot remove binary cache entry {}", entry_dir.display())
+            })?;
+        }
+        std::fs::rename(tmp_dir.path(), &entry_dir)
+            .with_context(|| format!("Cannot write binary cache entry {}", entry_dir.display()))?;
+        Ok(())
+    }
+
+    fn entry_dir(&self, name: &str, key: u64) -> PathBuf {
+        self.dir.join(format!("{name}-{key:016x}"))
+    }
+}
+
+/// Returns the inputs that identify the toolchain in the keys of the binary cache: the paths of
+/// its components and the verbose version of `rustc`, which changes with each nightly even if the
+/// toolchain is installed at the same path.
+pub(super) fn toolchain_cache_inputs(toolchain: &Toolchain) -> anyhow::Result<Vec<String>> {
+    let output = Command::new(&toolchain.components.rustc)
+        .arg("-vV")
+        .output()
+        .with_context(|| format!("Cannot execute {}", toolchain.components.rustc.display()))?;
+    if !output.status.success() {
+        return Err(anyhow::anyhow!(
+            "Cannot determine the version of {}: {}",
+            toolchain.components.rustc.display(),
+            String::from_utf8_lossy(&output.stderr)
+        ));
+    }
+    Ok(vec![
+        toolchain.components.cargo.display().to_string(),
+        toolchain.components.cargo_configs.join(" "),
+        String::from_utf8_lossy(&output.stdout).into_owned(),
+    ])
+}
+
+#[cfg(test)]
+mod tests {
+    use super::BinaryCache;
+    use crate::runtime::BenchmarkGroup;
+
+    #[test]
+    fn test_binary_cache() {
+        let dir = tempfile::TempDir::new().unwrap();
+        let binary = dir.path().join("hashmap-bench");
+        std::fs::write(&binary, "binary").unwrap();
+        let group = BenchmarkGroup {
+            binary: binary.clone(),
+            name: "hashmap".to_string(),
+            benchmark_names: vec!["insert".to_string()],
+            features: vec![],
+            cached: false,
+            binary_size: 6,
+            original_names: [("insert".to_string(), "bench-insert".to_string())].into(),
+            tags: vec![],
+            llvm_ir_size: None,
+            protocol_version: Some(1),
+        };
+
+        let cache = BinaryCache::open(&dir.path().join("cache")).unwrap();
+        assert!(cache.cached_group("hashmap", 1, None).is_none());
+        cache.store(&group, 1).unwrap();
+        // The entry does not depend on the original binary.
+        std::fs::remove_file(&binary).unwrap();
+
+        let cached = cache.cached_group("hashmap", 1, None).unwrap();
+        assert!(cached.cached);
+        assert_eq!(cached.benchmark_names, ["bench-insert"]);
+        assert_eq!(cached.protocol_version, Some(1));
+        assert_eq!(std::fs::read_to_string(&cached.binary).unwrap(), "binary");
+        assert!(cached.binary.starts_with(dir.path().join("cache")));
+
+        let strip_prefix = |name: &str| name.trim_start_matches("bench-").to_string();
+        let cached = cache
+            .cached_group("hashmap", 1, Some(&strip_prefix))
+            .unwrap();
+        assert_eq!(cached.benchmark_names, ["insert"]);
+
+        assert!(cache.cached_group("hashmap", 2, None).is_none());
+        assert!(cache.cached_group("nbody", 1, None).is_none());
+    }
+}
@@ -0,0 +1,206 @@
+use std::path::{Path, PathBuf};
+use std::process::Command;
+
+use anyhow::Context;
+
+/// Mount point of the unified (v2) cgroup hierarchy.
+const CGROUP_ROOT: &str = "/sys/fs/cgroup";
+
+/// A cgroup v2 in which the benchmark processes are executed, to isolate them from the rest of
+/// the system.
+#[derive(Clone, Debug, serde::Serialize)]
+pub struct CgroupConfig {
+    /// Path of the cgroup, relative to the root of the cgroup hierarchy. It is created if it does
+    /// not exist yet, otherwise the benchmarks are attached to the existing cgroup.
+    pub path: PathBuf,
+    /// CPUs to which the cgroup is restricted, in the `cpuset.cpus` format (e.g. `2-3`).
+    pub cpus: Option<String>,
+    /// Memory limit of the cgroup in bytes.
+    pub memory_max: Option<u64>,
+}
+
+impl CgroupConfig {
+    pub fn new(path: PathBuf) -> Self {
+        Self {
+            path,
+            cpus: None,
+            memory_max: None,
+        }
+    }
+
+    /// Path of the file that is used to attach processes to the cgroup.
+    pub fn procs_path(&self) -> PathBuf {
+        Path::new(CGROUP_ROOT).join(&self.path).join("cgroup.procs")
+    }
+}
+
+/// A configured cgroup. If the cgroup was created by the collector, it is removed when this is
+/// dropped.
+#[derive(Debug)]
+pub struct BenchmarkCgroup {
+    path: PathBuf,
+    created: bool,
+}
+
+impl BenchmarkCgroup {
+    /// Creates the cgroup (or reuses an existing one) and applies the configured limits.
+    #[cfg(target_os = "linux")]
+    pub fn setup(config: &CgroupConfig) -> anyhow::Result<Self> {
+        let root = Path::new(CGROUP_ROOT);
+        if !root.join("cgroup.controllers").is_file() {
+            return Err(anyhow::anyhow!(
+                "cgroup v2 hierarchy is not mounted at {CGROUP_ROOT}"
+            ));
+        }
+
+        let path = root.join(&config.path);
+        let created = !path.is_dir();
+        if created {
+            std::fs::create_dir(&path)
+                .with_context(|| format!("Cannot create cgroup {}", path.display()))?;
+        }
+        // Construct the guard right away, so that the cgroup is removed if setting a limit fails.
+        let cgroup = Self { path, created };
+
+        if let Some(ref cpus) = config.cpus {
+            cgroup.enable_controller("cpuset");
+            cgroup.write("cpuset.cpus", cpus)?;
+            // The memory nodes have to be configured as well, otherwise the CPUs are not used.
+            let mems = std::fs::read_to_string(root.join("cpuset.mems.effective"))
+                .unwrap_or_else(|_| "0".to_string());
+            cgroup.write("cpuset.mems", mems.trim())?;
+        }
+        if let Some(memory_max) = config.memory_max {
+            cgroup.enable_controller("memory");
+            cgroup.write("memory.max", &memory_max.to_string())?;
+        }
+        Ok(cgroup)
+    }
+
+    #[cfg(not(target_os = "linux"))]
+    pub fn setup(_config: &CgroupConfig) -> anyhow::Result<Self> {
+        Err(anyhow::anyhow!("cgroups are only supported on Linux"))
+    }
+
+    /// Enables a controller for the children of the parent cgroup, so that it can be configured
+    /// in this cgroup. If this fails, configuring the controller will fail with a better error.
+    fn enable_controller(&self, controller: &str) {
+        let Some(parent) = self.path.parent() else {
+            return;
+        };
+        let subtree_control = parent.join("cgroup.subtree_control");
+        if let Err(error) = std::fs::write(&subtree_control, format!("+{controller}")) {
+            log::debug!(
+                "Cannot enable {controller} controller in {}: {error:?}",
+                subtree_control.display()
+            );
+        }
+    }
+
+    fn write(&self, file: &str, value: &str) -> anyhow::Result<()> {
+        let path = self.path.join(file);
+        std::fs::write(&path, value)
+            .with_context(|| format!("Cannot write `{value}` to {}", path.display()))
+    }
+}
+
+impl Drop for BenchmarkCgroup {
+    fn drop(&mut self) {
+        if self.created {
+            if let Err(error) = std::fs::remove_dir(&self.path) {
+                log::warn!("Cannot remove cgroup {}: {error:?}", self.path.display());
+            }
+        }
+    }
+}
+
+/// Makes sure that the process spawned by `command` is attached to the cgroup with the given
+/// `cgroup.procs` file before it starts executing.
+#[cfg(target_os = "linux")]
+pub(super) fn attach_to_cgroup(command: &mut Command, procs_path: &Path) -> anyhow::Result<()> {
+    use std::os::unix::ffi::OsStrExt;
+    use std::os::unix::process::CommandExt;
+
+    let procs_path = std::ffi::CString::new(procs_path.as_os_str().as_bytes())?;
+
+    // SAFETY: the closure only calls async-signal-safe functions.
+    unsafe {
+        command.pre_exec(move || {
+            let fd = libc::open(procs_path.as_ptr(), libc::O_WRONLY);
+            if fd < 0 {
+                return Err(std::io::Error::last_os_error());
+            }
+            // Writing `0` moves the writing process into the cgroup.
+            let written = libc::write(fd, b"0".as_ptr().cast(), 1);
+            let error = std::io::Error::last_os_error();
+            libc::close(fd);
+            if written != 1 {
+                return Err(error);
+            }
+            Ok(())
+        });
+    }
+    Ok(())
+}
+
+#[cfg(not(target_os = "linux"))]
+pub(super) fn attach_to_cgroup(_command: &mut Command, _procs_path: &Path) -> anyhow::Result<()> {
+    Err(anyhow::anyhow!("cgroups are only supported on Linux"))
+}
+
+#[cfg(all(test, target_os = "linux"))]
+mod tests {
+    use std::collections::HashMap;
+    use std::path::PathBuf;
+
+    use super::{BenchmarkCgroup, CgroupConfig};
+    use crate::runtime::tests::{create_script, echo_result};
+    use crate::runtime::{execute_runtime_benchmark_group, RuntimeBenchmarkFilter, RuntimeRunOpts};
+
+    #[test]
+    fn test_attach_to_cgroup() {
+        let name = format!("rustc-perf-test-{}", std::process::id());
+        let config = CgroupConfig::new(PathBuf::from(&name));
+        let cgroup = match BenchmarkCgroup::setup(&config) {
+            Ok(cgroup) => cgroup,
+            Err(error) => {
+                eprintln!("Skipping test, cannot create a cgroup: {error:?}");
+                return;
+            }
+        };
+        assert_eq!(
+            config.procs_path(),
+            PathBuf::from("/sys/fs/cgroup")
+                .join(&name)
+                .join("cgroup.procs")
+        );
+
+        let dir = tempfile::TempDir::new().unwrap();
+        let membership = dir.path().join("cgroup");
+        let binary = create_script(
+            dir.path(),
+            "isolated",
+            &format!(
+                "cat /proc/self/cgroup > {}\n{}",
+                membership.display(),
+                echo_result("bench", "0")
+            ),
+        );
+        let results = execute_runtime_benchmark_group(
+            &binary,
+            &HashMap::new(),
+            &RuntimeBenchmarkFilter::keep_all(),
+            1,
+            &RuntimeRunOpts::default().cgroup(config.clone()),
+        )
+        .unwrap();
+        assert_eq!(results.len(), 1);
+        assert_eq!(
+            std::fs::read_to_string(&membership).unwrap().trim(),
+            format!("0::/{name}")
+        );
+
+        drop(cgroup);
+        assert!(!PathBuf::from("/sys/fs/cgroup").join(&name).exists());
+    }
+}
@@ -0,0 +1,370 @@
+use std::collections::{BTreeMap, HashMap};
+
+use benchlib::comm::messages::BenchmarkResult;
+
+/// Values of metrics of a single benchmark, keyed by metric names (e.g. `wall-time`).
+/// Besides the metrics measured by `benchlib`, this can contain domain-specific metrics, such as
+/// throughput.
+pub type BenchmarkMetrics = BTreeMap<String, f64>;
+
+/// Describes which direction of change of a metric is an improvement.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, serde::Serialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum MetricDirection {
+    /// E.g. time or instruction counts.
+    #[default]
+    LowerIsBetter,
+    /// E.g. throughput or operations per second.
+    HigherIsBetter,
+}
+
+/// Configures how are the individual metrics compared.
+/// Metrics without an explicit direction are considered to be lower-is-better.
+#[derive(Clone, Debug, Default)]
+pub struct ComparisonConfig {
+    directions: HashMap<String, MetricDirection>,
+}
+
+impl ComparisonConfig {
+    pub fn direction(mut self, metric: &str, direction: MetricDirection) -> Self {
+        self.directions.insert(metric.to_string(), direction);
+        self
+    }
+
+    pub fn direction_of(&self, metric: &str) -> MetricDirection {
+        self.directions.get(metric).copied().unwrap_or_default()
+    }
+}
+
+/// Change of a single metric between a baseline and a current result.
+#[derive(Clone, Debug, PartialEq, serde::Serialize)]
+pub struct MetricDelta {
+    pub metric: String,
+    pub baseline: f64,
+    pub current: f64,
+    /// Relative change of the metric, e.g. `0.1` if the current value is 10% higher than the
+    /// baseline.
+    pub relative_change: f64,
+    pub direction: MetricDirection,
+}
+
+impl MetricDelta {
+    /// Returns true if the metric has changed in the direction of an improvement.
+    pub fn is_improvement(&self) -> bool {
+        match self.direction {
+            MetricDirection::LowerIsBetter => self.current < self.baseline,
+            MetricDirection::HigherIsBetter => self.current > self.baseline,
+        }
+    }
+
+    /// Returns true if the metric has changed in the direction of a regression.
+    pub fn is_regression(&self) -> bool {
+        self.current != self.baseline && !self.is_improvement()
+    }
+}
+
+/// Returns the mean value of each metric of the result that was measured in all iterations.
+pub fn benchmark_metrics(result: &BenchmarkResult) -> BenchmarkMetrics {
+    let mut metrics = BenchmarkMetrics::new();
+    let mut add = |name: &str, values: Vec<Option<u64>>| {
+        let values: Option<Vec<u64>> = values.into_iter().collect();
+        if let Some(values) = values.filter(|values| !values.is_empty()) {
+            let mean = values.iter().map(|&v| v as f64).sum::<f64>() / values.len() as f64;
+            metrics.insert(name.to_string(), mean);
+        }
+    };
+    let stats = &result.stats;
+    add(
+        "wall-time",
+        stats
+            .iter()
+            .map(|s| Some(s.wall_time.as_nanos() as u64))
+            .collect(),
+    );
+    add(
+        "instructions:u",
+        stats.iter().map(|s| s.instructions).collect(),
+    );
+    add("cycles:u", stats.iter().map(|s| s.cycles).collect());
+    add(
+        "branch-misses",
+        stats.iter().map(|s| s.branch_misses).collect(),
+    );
+    add(
+        "cache-misses",
+        stats.iter().map(|s| s.cache_misses).collect(),
+    );
+    add("max-rss", stats.iter().map(|s| s.max_rss).collect());
+    metrics
+}
+
+/// Compares all metrics that are present both in the baseline and in the current result, and
+/// returns their deltas, sorted by the metric name.
+pub fn compare_to_baseline(
+    baseline: &BenchmarkMetrics,
+    current: &BenchmarkMetrics,
+    config: &ComparisonConfig,
+) -> Vec<MetricDelta> {
+    baseline
+        .iter()
+        .filter_map(|(metric, &baseline)| {
+            let current = *current.get(metric)?;
+            let relative_change = if baseline == 0.0 {
+                if current == 0.0 {
+                    0.0
+                } else {
+                    f64::INFINITY.copysign(current)
+                }
+            } else {
+                (current - baseline) / baseline
+            };
+            Some(MetricDelta {
+                metric: metric.clone(),
+                baseline,
+                current,
+                relative_change,
+                direction: config.direction_of(metric),
+            })
+        })
+        .collect()
+}
+
+/// A benchmark that has reported different checksums of its work under two toolchains. Its
+/// timings are not comparable, because the code generated by one of the toolchains has changed
+/// the behavior of the benchmark.
+#[derive(Clone, Debug, PartialEq, Eq, serde::Serialize)]
+pub struct ChecksumMismatch {
+    pub benchmark: String,
+    pub baseline: u64,
+    pub current: u64,
+}
+
+/// Compares the checksums of benchmarks executed with a baseline and a current toolchain.
+/// Benchmarks that are missing from either side or that do not report a checksum are skipped.
+pub fn find_checksum_mismatches(
+    baseline: &[BenchmarkResult],
+    current: &[BenchmarkResult],
+) -> Vec<ChecksumMismatch> {
+    let baseline: HashMap<&str, u64> = baseline
+        .iter()
+        .filter_map(|result| Some((result.name.as_str(), result.checksum?)))
+        .collect();
+    let mut mismatches: Vec<ChecksumMismatch> = current
+        .iter()
+        .filter_map(|result| {
+            let current = result.checksum?;
+            let baseline = *baseline.get(result.name.as_str())?;
+            (baseline != current).then(|| ChecksumMismatch {
+                benchmark: result.name.clone(),
+                baseline,
+                current,
+            })
+        })
+        .collect();
+    mismatches.sort_by(|a, b| a.benchmark.cmp(&b.benchmark));
+    mismatches
+}
+
+/// Offset basis of the 64-bit FNV-1a hash.
+const FNV_OFFSET_BASIS: u64 = 0xcbf29ce484222325;
+/// Prime of the 64-bit FNV-1a hash.
+const FNV_PRIME: u64 = 0x100000001b3;
+
+/// Feeds `bytes` into a 64-bit FNV-1a `hash`.
+fn fnv1a(hash: u64, bytes: &[u8]) -> u64 {
+    bytes.iter().fold(hash, |hash, &byte| {
+        (hash ^ byte as u64).wrapping_mul(FNV_PRIME)
+    })
+}
+
+/// Computes a hash of the mean values of all metrics of the benchmarks (see `benchmark_metrics`),
+/// rounded to multiples of `round_to_ns` (nanoseconds for wall time, the metric's own unit for the
+/// other metrics), so that two result sets that only differ by noise hash equally.
+/// The hash does not depend on the order of the results. It uses FNV-1a, so it is stable across
+/// builds and versions of the collector.
+pub fn result_set_hash(results: &[BenchmarkResult], round_to_ns: u64) -> u64 {
+    let round_to_ns = round_to_ns.max(1);
+    let mut values: Vec<(&str, String, u64)> = results
+        .iter()
+        .flat_map(|result| {
+            benchmark_metrics(result)
+                .into_iter()
+                .map(move |(metric, mean)| {
+                    let rounded = (mean / round_to_ns as f64).round() as u64 * round_to_ns;
+                    (result.name.as_str(), metric, rounded)
+                })
+        })
+        .collect();
+    values.sort();
+
+    values
+        .iter()
+        .fold(FNV_OFFSET_BASIS, |hash, (name, metric, value)| {
+            // The separators keep the boundaries between the names unambiguous.
+            let hash = fnv1a(hash, name.as_bytes());
+            let hash = fnv1a(hash, &[0]);
+            let hash = fnv1a(hash, metric.as_bytes());
+            let hash = fnv1a(hash, &[0]);
+            fnv1a(hash, &value.to_le_bytes())
+        })
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use benchlib::comm::messages::{BenchmarkResult, BenchmarkStats};
+
+    use super::{
+        benchmark_metrics, compare_to_baseline, result_set_hash, BenchmarkMetrics,
+        ComparisonConfig, MetricDirection,
+    };
+
+    #[test]
+    fn test_benchmark_metrics() {
+        let stats = |instructions, wall_time| BenchmarkStats {
+            cycles: None,
+            instructions: Some(instructions),
+            branch_misses: None,
+            cache_misses: None,
+            cache_references: None,
+            wall_time: Duration::from_nanos(wall_time),
+            max_rss: None,
+        };
+        let result = BenchmarkResult {
+            name: "bench".to_string(),
+            stats: vec![stats(100, 10), stats(200, 30)],
+            checksum: None,
+        };
+        let metrics = benchmark_metrics(&result);
+        assert_eq!(metrics.len(), 2);
+        assert_eq!(metrics["instructions:u"], 150.0);
+        assert_eq!(metrics["wall-time"], 20.0);
+    }
+
+    #[test]
+    fn test_result_set_hash() {
+        let result = |name: &str, wall_time, instructions| BenchmarkResult {
+            name: name.to_string(),
+            stats: vec![BenchmarkStats {
+                cycles: None,
+                instructions: Some(instructions),
+                branch_misses: None,
+                cache_misses: None,
+                cache_references: None,
+                wall_time: Duration::from_nanos(wall_time),
+                max_rss: None,
+            }],
+            checksum: None,
+        };
+        let baseline = [result("a", 10_100, 5_000), result("b", 20_000, 7_000)];
+        // Same values within the rounding, in a different order.
+        let noisy = [result("b", 19_800, 7_200), result("a", 9_950, 4_900)];
+        let changed_wall_time = [result("a", 10_100, 5_000), result("b", 25_000, 7_000)];
+        let changed_instructions = [result("a", 10_100, 5_000), result("b", 20_000, 9_000)];
+
+        let hash = result_set_hash(&baseline, 1000);
+        assert_eq!(hash, result_set_hash(&noisy, 1000));
+        assert_ne!(hash, result_set_hash(&changed_wall_time, 1000));
+        assert_ne!(hash, result_set_hash(&changed_instructions, 1000));
+        assert_ne!(hash, result_set_hash(&noisy, 1));
+
+        // The hash must not change between builds of the collector.
+        assert_eq!(hash, 0x3fd722922a1673d4);
+    }
+
+    #[test]
+    fn test_compare_throughput() {
+        let metrics = |values: &[(&str, f64)]| -> BenchmarkMetrics {
+            values
+                .iter()
+                .map(|&(metric, value)| (metric.to_string(), value))
+                .collect()
+        };
+        let baseline = metrics(&[
+            ("throughput-mb-s", 100.0),
+            ("wall-time", 1000.0),
+            ("only-in-baseline", 1.0),
+        ]);
+        let current = metrics(&[
+            ("throughput-mb-s", 120.0),
+            ("wall-time", 1100.0),
+            ("only-in-current", 1.0),
+        ]);
+        let config = ComparisonConfig::default()
+            .direction("throughput-mb-s", MetricDirection::HigherIsBetter);
+
+        let deltas = compare_to_baseline(&baseline, &current, &config);
+        let metric_names: Vec<&str> = deltas.iter().map(|d| d.metric.as_str()).collect();
+        assert_eq!(metric_names, ["throughput-mb-s", "wall-time"]);
+
+        let throughput = &deltas[0];
+        assert!((throughput.relative_change - 0.2).abs() < 1e-9);
+        assert_eq!(throughput.direction, MetricDirection::HigherIsBetter);
+        assert!(throughput.is_improvement());
+        assert!(!throughput.is_regression());
+
+        let wall_time = &deltas[1];
+        assert!((wall_time.relative_change - 0.1).abs() < 1e-9);
+        assert_eq!(wall_time.direction, MetricDirection::LowerIsBetter);
+        assert!(!wall_time.is_improvement());
+        assert!(wall_time.is_regression());
+
+        // Without the configuration, higher throughput would be a regression.
+        let deltas = compare_to_baseline(&baseline, &current, &ComparisonConfig::default());
+        assert!(deltas[0].is_regression());
+    }
+
+    #[cfg(target_os = "linux")]
+    #[test]
+    fn test_checksum_mismatch() {
+        use std::collections::HashMap;
+
+        use crate::runtime::tests::create_script;
+        use crate::runtime::{
+            execute_runtime_benchmark_group, RuntimeBenchmarkFilter, RuntimeRunOpts,
+        };
+
+        use super::{find_checksum_mismatches, ChecksumMismatch};
+
+        // Stands in for the same benchmark group compiled by two different toolchains.
+        let dir = tempfile::TempDir::new().unwrap();
+        let toolchain = |name: &str, sum_checksum: u64| {
+            let message = |benchmark: &str, checksum: u64| {
+                format!(
+                    r#"echo '{{"Result":{{"name":"{benchmark}","stats":[{{"cycles":null,"instructions":null,"branch_misses":null,"cache_misses":null,"cache_references":null,"wall_time":{{"secs":0,"nanos":1}}}}],"checksum":{checksum}}}}}'"#
+                )
+            };
+            let binary = create_script(
+                dir.path(),
+                name,
+                &format!(
+                    "{}\n{}",
+                    message("product", 7),
+                    message("sum", sum_checksum)
+                ),
+            );
+            execute_runtime_benchmark_group(
+                &binary,
+                &HashMap::new(),
+                &RuntimeBenchmarkFilter::keep_all(),
+                1,
+                &RuntimeRunOpts::default(),
+            )
+            .unwrap()
+        };
+        let baseline = toolchain("baseline", 42);
+        let current = toolchain("current", 43);
+        assert_eq!(current[1].checksum, Some(43));
+
+        assert_eq!(
+            find_checksum_mismatches(&baseline, &current),
+            vec![ChecksumMismatch {
+                benchmark: "sum".to_string(),
+                baseline: 42,
+                current: 43,
+            }]
+        );
+        assert!(find_checksum_mismatches(&baseline, &baseline).is_empty());
+    }
+}
@@ -0,0 +1,318 @@
+use std::io::Write;
+use std::path::Path;
+use std::time::Duration;
+
+use anyhow::Context;
+
+use crate::runtime::{
+    BenchmarkGroupFilter, CargoIsolationMode, RuntimeBenchmarkFilter, RuntimeCompilationOpts,
+    RuntimeRunOpts, TagExpr, DEFAULT_RUNTIME_ITERATIONS,
+};
+
+/// Effective configuration of a runtime benchmark run, after all defaults and command-line
+/// options have been resolved.
+#[derive(Debug, serde::Serialize)]
+pub struct RunConfig {
+    /// Identifier of the benchmarked toolchain.
+    pub toolchain: String,
+    /// If set, only the benchmark group with this name is compiled and executed.
+    pub group: Option<String>,
+    pub isolation_mode: CargoIsolationMode,
+    /// Selects the executed benchmark groups by their names, before `filter` is applied.
+    pub group_filter: BenchmarkGroupFilter,
+    pub filter: RuntimeBenchmarkFilter,
+    /// If set, only benchmark groups whose tags match this expression are executed.
+    pub tags: Option<TagExpr>,
+    /// If set, only a random sample of `(count, seed)` benchmarks that pass the filters is
+    /// executed.
+    pub sample: Option<(usize, u64)>,
+    pub iterations: u32,
+    /// Options with which the benchmarks are compiled, see `CompileOverrides::apply`.
+    pub compile: CompileOverrides,
+    pub opts: RuntimeRunOpts,
+}
+
+impl RunConfig {
+    /// Resolves the configuration of a run from `layer`, using the defaults for the options that
+    /// it does not set. The options of `opts` that are covered by the layer are overwritten.
+    pub fn resolve(
+        toolchain: &str,
+        group: Option<String>,
+        isolation_mode: CargoIsolationMode,
+        sample: Option<(usize, u64)>,
+        layer: RunConfigLayer,
+        mut opts: RuntimeRunOpts,
+    ) -> anyhow::Result<Self> {
+        let filter = match layer.filter_syntax.unwrap_or_default() {
+            FilterSyntax::Prefix => RuntimeBenchmarkFilter::new(layer.exclude, layer.include),
+            FilterSyntax::Regex => RuntimeBenchmarkFilter::regex(layer.exclude, layer.include)?,
+            FilterSyntax::Glob => RuntimeBenchmarkFilter::glob(layer.exclude, layer.include)?,
+        };
+        opts = opts.input_sizes(layer.input_sizes);
+        if let Some(seed) = layer.seed {
+            opts = opts.seed(seed);
+        }
+        if let Some(budget) = layer.memory_budget {
+            opts = opts.memory_budget(budget);
+        }
+        if let Some(timeout) = layer.total_timeout {
+            opts = opts.total_timeout(Duration::from_secs(timeout));
+        }
+        Ok(Self {
+            toolchain: toolchain.to_string(),
+            group,
+            isolation_mode,
+            group_filter: BenchmarkGroupFilter::new(layer.exclude_group, layer.include_group),
+            filter,
+            tags: layer.tags.map(|tags| tags.parse()).transpose()?,
+            sample,
+            iterations: layer.iterations.unwrap_or(DEFAULT_RUNTIME_ITERATIONS),
+            compile: layer.compile,
+            opts,
+        })
+    }
+}
+
+/// Options of the Cargo profile (and the target) with which the runtime benchmarks are
+/// compiled. Unset options keep the values of the benchmark crates.
+#[derive(Debug, Default, Clone, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+#[serde(default, deny_unknown_fields, rename_all = "kebab-case")]
+pub struct CompileOverrides {
+    pub opt_level: Option<String>,
+    pub lto: Option<String>,
+    pub codegen_units: Option<u32>,
+    pub panic: Option<String>,
+    pub overflow_checks: Option<bool>,
+    pub split_debuginfo: Option<String>,
+    /// Cargo profile with which the benchmarks are compiled instead of `release`.
+    pub cargo_profile: Option<String>,
+    pub target: Option<String>,
+}
+
+impl CompileOverrides {
+    /// Sets the overridden options in `opts`.
+    pub fn apply(&self, mut opts: RuntimeCompilationOpts) -> RuntimeCompilationOpts {
+        if let Some(ref opt_level) = self.opt_level {
+            opts = opts.opt_level(opt_level);
+        }
+        if let Some(ref lto) = self.lto {
+            opts = opts.lto(lto);
+        }
+        if let Some(codegen_units) = self.codegen_units {
+            opts = opts.codegen_units(codegen_units);
+        }
+        if let Some(ref panic) = self.panic {
+            opts = opts.panic(panic);
+        }
+        if let Some(overflow_checks) = self.overflow_checks {
+            opts = opts.overflow_checks(overflow_checks);
+        }
+        if let Some(ref split_debuginfo) = self.split_debuginfo {
+            opts = opts.split_debuginfo(split_debuginfo);
+        }
+        if let Some(ref profile) = self.cargo_profile {
+            opts = opts.profile(profile);
+        }
+        if let Some(ref target) = self.target {
+            opts = opts.target(target);
+        }
+        opts
+    }
+
+    fn or(self, lower: CompileOverrides) -> Self {
+        Self {
+            opt_level: self.opt_level.or(lower.opt_level),
+            lto: self.lto.or(lower.lto),
+            codegen_units: self.codegen_units.or(lower.codegen_units),
+            panic: self.panic.or(lower.panic),
+            overflow_checks: self.overflow_checks.or(lower.overflow_checks),
+            split_debuginfo: self.split_debuginfo.or(lower.split_debuginfo),
+            cargo_profile: self.cargo_profile.or(lower.cargo_profile),
+            target: self.target.or(lower.target),
+        }
+    }
+}
+
+/// Syntax of the benchmark name patterns of a `RunConfigLayer`.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, serde::Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum FilterSyntax {
+    /// Prefixes of benchmark names (see `RuntimeBenchmarkFilter::new`).
+    #[default]
+    Prefix,
+    /// Regular expressions (see `RuntimeBenchmarkFilter::regex`).
+    Regex,
+    /// Globs (see `RuntimeBenchmarkFilter::glob`).
+    Glob,
+}
+
+/// Options of a run that can be set by several sources, i.e. by a configuration file and by
+/// the command line. Unset options are `None` or empty.
+///
+/// The sources are combined with `or`, from the one with the highest priority to the one with the
+/// lowest, and the defaults are applied by `RunConfig::resolve`.
+#[derive(Debug, Default, Clone, PartialEq, Eq, serde::Deserialize)]
+#[serde(default, deny_unknown_fields, rename_all = "kebab-case")]
+pub struct RunConfigLayer {
+    pub iterations: Option<u32>,
+    pub include: Vec<String>,
+    pub exclude: Vec<String>,
+    pub filter_syntax: Option<FilterSyntax>,
+    pub include_group: Vec<String>,
+    pub exclude_group: Vec<String>,
+    pub tags: Option<String>,
+    pub input_sizes: Vec<u64>,
+    pub seed: Option<u64>,
+    pub memory_budget: Option<u64>,
+    /// Total timeout of the run, in seconds.
+    pub total_timeout: Option<u64>,
+    /// Read from the `[compile]` table of a configuration file.
+    pub compile: CompileOverrides,
+}
+
+impl RunConfigLayer {
+    /// Reads the options from a TOML configuration file, e.g. `iterations = 10`. The keys are
+    /// named like the corresponding command-line options.
+    pub fn load(path: &Path) -> anyhow::Result<Self> {
+        let contents = std::fs::read_to_string(path)
+            .with_context(|| format!("Cannot read run configuration {}", path.display()))?;
+        toml::from_str(&contents)
+            .with_context(|| format!("Cannot parse run configuration {}", path.display()))
+    }
+
+    /// Fills the options that are not set by this layer from `lower`, which has a lower priority.
+    pub fn or(self, lower: RunConfigLayer) -> Self {
+        fn or_vec<T>(higher: Vec<T>, lower: Vec<T>) -> Vec<T> {
+            if higher.is_empty() {
+                lower
+            } else {
+                higher
+            }
+        }
+
+        Self {
+            iterations: self.iterations.or(lower.iterations),
+            include: or_vec(self.include, lower.include),
+            exclude: or_vec(self.exclude, lower.exclude),
+            filter_syntax: self.filter_syntax.or(lower.filter_syntax),
+            include_group: or_vec(self.include_group, lower.include_group),
+            exclude_group: or_vec(self.exclude_group, lower.exclude_group),
+            tags: self.tags.or(lower.tags),
+            input_sizes: or_vec(self.input_sizes, lower.input_sizes),
+            seed: self.seed.or(lower.seed),
+            memory_budget: self.memory_budget.or(lower.memory_budget),
+            total_timeout: self.total_timeout.or(lower.total_timeout),
+            compile: self.compile.or(lower.compile),
+        }
+    }
+}
+
+/// Prints the configuration as JSON to stdout.
+pub fn print_effective_config(config: &RunConfig) -> anyhow::Result<()> {
+    write_effective_config(config, std::io::stdout().lock())
+}
+
+fn write_effective_config<W: Write>(config: &RunConfig, mut out: W) -> anyhow::Result<()> {
+    serde_json::to_writer_pretty(&mut out, config)?;
+    writeln!(out)?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use crate::runtime::{CargoIsolationMode, RuntimeRunOpts, DEFAULT_RUNTIME_ITERATIONS};
+
+    use super::{
+        write_effective_config, CompileOverrides, FilterSyntax, RunConfig, RunConfigLayer,
+    };
+
+    fn resolve_to_json(layer: RunConfigLayer) -> serde_json::Value {
+        let config = RunConfig::resolve(
+            "nightly",
+            Some("hashmap".to_string()),
+            CargoIsolationMode::Cached,
+            Some((10, 42)),
+            layer,
+            RuntimeRunOpts::default().max_load_average(2.0, Duration::from_secs(30)),
+        )
+        .unwrap();
+
+        let mut out = Vec::new();
+        write_effective_config(&config, &mut out).unwrap();
+        serde_json::from_slice(&out).unwrap()
+    }
+
+    #[test]
+    fn test_write_effective_config() {
+        let dir = tempfile::TempDir::new().unwrap();
+        let path = dir.path().join("run.toml");
+        std::fs::write(
+            &path,
+            r#"
+iterations = 20
+exclude = ["slow"]
+include-group = ["hash"]
+tags = "simd && !slow"
+input-sizes = [10, 100]
+seed = 1
+
+[compile]
+lto = "thin"
+codegen-units = 1
+"#,
+        )
+        .unwrap();
+        let file = RunConfigLayer::load(&path).unwrap();
+        let cli = RunConfigLayer {
+            include: vec!["insert".to_string()],
+            filter_syntax: Some(FilterSyntax::Glob),
+            seed: Some(42),
+            compile: CompileOverrides {
+                lto: Some("fat".to_string()),
+                ..CompileOverrides::default()
+            },
+            ..RunConfigLayer::default()
+        };
+
+        let json = resolve_to_json(cli.or(file));
+        assert_eq!(json["toolchain"], "nightly");
+        assert_eq!(json["group"], "hashmap");
+        assert_eq!(json["isolation_mode"], "cached");
+        assert_eq!(json["sample"], serde_json::json!([10, 42]));
+        assert_eq!(json["opts"]["max_load_average"][0], 2.0);
+        assert_eq!(json["opts"]["max_load_average"][1]["secs"], 30);
+        assert_eq!(json["opts"]["pin_core"], serde_json::Value::Null);
+        // The configuration file overrides the defaults.
+        assert_eq!(json["iterations"], 20);
+        assert_eq!(json["filter"]["exclude"], serde_json::json!(["slow"]));
+        assert_eq!(json["group_filter"]["include"], serde_json::json!(["hash"]));
+        assert_eq!(json["tags"], "(simd && !slow)");
+        assert_eq!(json["opts"]["input_sizes"], serde_json::json!([10, 100]));
+        assert_eq!(json["compile"]["codegen-units"], 1);
+        // The command line overrides the configuration file.
+        assert_eq!(json["filter"]["include"], serde_json::json!(["insert"]));
+        assert_eq!(json["filter"]["syntax"], "glob");
+        assert_eq!(json["opts"]["seed"], 42);
+        assert_eq!(json["compile"]["lto"], "fat");
+
+        // Without any source, the defaults are used.
+        let json = resolve_to_json(RunConfigLayer::default());
+        assert_eq!(json["iterations"], DEFAULT_RUNTIME_ITERATIONS);
+        assert_eq!(json["filter"]["include"], serde_json::json!([]));
+        assert!(json["filter"].get("syntax").is_none());
+        assert_eq!(json["tags"], serde_json::Value::Null);
+        assert_eq!(json["opts"]["seed"], serde_json::Value::Null);
+        assert_eq!(json["compile"]["lto"], serde_json::Value::Null);
+    }
+
+    #[test]
+    fn test_load_unknown_option() {
+        let dir = tempfile::TempDir::new().unwrap();
+        let path = dir.path().join("run.toml");
+        std::fs::write(&path, "iteration = 20").unwrap();
+        let error = format!("{:?}", RunConfigLayer::load(&path).unwrap_err());
+        assert!(error.contains("unknown field `iteration`"), "{error}");
+    }
+}
@@ -0,0 +1,149 @@
+use std::collections::{BTreeMap, BTreeSet};
+use std::path::Path;
+
+use anyhow::Context;
+
+/// Categories of language and compiler features, and source code patterns that indicate their
+/// usage.
+const FEATURE_PATTERNS: &[(&str, &[&str])] = &[
+    (
+        "simd",
+        &[
+            "std::arch",
+            "core::arch",
+            "std::simd",
+            "core::simd",
+            "_mm_",
+            "_mm256_",
+        ],
+    ),
+    ("async", &["async fn", "async move", ".await"]),
+    ("generics", &["impl<", "where\n", "where "]),
+    ("trait-objects", &["dyn "]),
+    ("unsafe", &["unsafe "]),
+    ("inline-asm", &["asm!(", "global_asm!("]),
+    ("threads", &["thread::spawn", "thread::scope", "rayon::"]),
+];
+
+/// Usage of a single feature category by the benchmarks.
+#[derive(Debug, Default, PartialEq, Eq, serde::Serialize)]
+pub struct FeatureCoverage {
+    /// Total number of pattern matches in the sources.
+    pub occurrences: usize,
+    /// Benchmark groups whose sources contain at least one match.
+    pub groups: BTreeSet<String>,
+}
+
+/// Estimate of which features are exercised by the runtime benchmarks, keyed by the feature
+/// category. Categories that are not exercised at all are included with no occurrences.
+#[derive(Debug, Default, serde::Serialize)]
+pub struct CoverageReport {
+    pub categories: BTreeMap<&'static str, FeatureCoverage>,
+}
+
+impl CoverageReport {
+    /// Returns the categories that are not exercised by any benchmark.
+    pub fn uncovered(&self) -> Vec<&'static str> {
+        self.categories
+            .iter()
+            .filter(|(_, coverage)| coverage.occurrences == 0)
+            .map(|(category, _)| *category)
+            .collect()
+    }
+}
+
+/// Scans the Rust sources of the runtime benchmark groups in `dir` (each group being
+/// a subdirectory) and tallies which feature categories they use.
+///
+/// This is only a heuristic: the sources are searched for textual patterns, so e.g. occurrences
+/// in comments are counted as well, and code generated by macros is not seen at all. It is meant
+/// to give a rough overview of what the benchmark set covers, not a precise answer.
+pub fn feature_coverage(dir: &Path) -> anyhow::Result<CoverageReport> {
+    let mut report = CoverageReport {
+        categories: FEATURE_PATTERNS
+            .iter()
+            .map(|(category, _)| (*category, FeatureCoverage::default()))
+            .collect(),
+    };
+    for entry in walkdir::WalkDir::new(dir)
+        .into_iter()
+        .filter_entry(|entry| entry.file_name() != "target")
+    {
+        let entry = entry?;
+        let path = entry.path();
+        if !entry.file_type().is_file() || path.extension().is_none_or(|ext| ext != "rs") {
+            continue;
+        }
+        let Some(group) = path
+            .strip_prefix(dir)?
+            .components()
+            .next()
+            .filter(|_| entry.depth() > 1)
+        else {
+            continue;
+        };
+        let group = group.as_os_str().to_string_lossy();
+        let source = std::fs::read_to_string(path)
+            .with_context(|| format!("Cannot read {}", path.display()))?;
+
+        for (category, patterns) in FEATURE_PATTERNS {
+            let occurrences: usize = patterns
+                .iter()
+                .map(|pattern| source.matches(pattern).count())
+                .sum();
+            if occurrences > 0 {
+                let coverage = report.categories.get_mut(category).unwrap();
+                coverage.occurrences += occurrences;
+                coverage.groups.insert(group.to_string());
+            }
+        }
+    }
+    Ok(report)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::Path;
+
+    use super::feature_coverage;
+
+    fn write_source(dir: &Path, path: &str, source: &str) {
+        let path = dir.join(path);
+        std::fs::create_dir_all(path.parent().unwrap()).unwrap();
+        std::fs::write(path, source).unwrap();
+    }
+
+    #[test]
+    fn test_feature_coverage() {
+        let dir = tempfile::TempDir::new().unwrap();
+        write_source(
+            dir.path(),
+            "vector/src/main.rs",
+            "use std::arch::x86_64::_mm_add_ps;\nfn main() {}",
+        );
+        write_source(
+            dir.path(),
+            "server/src/main.rs",
+            "async fn handle() {}\nasync fn run() { handle().await; }\nfn main() {}",
+        );
+        // Build artifacts are not scanned.
+        write_source(
+            dir.path(),
+            "vector/target/gen.rs",
+            "async fn generated() {}",
+        );
+
+        let report = feature_coverage(dir.path()).unwrap();
+        let simd = &report.categories["simd"];
+        assert_eq!(simd.occurrences, 2);
+        assert_eq!(simd.groups.iter().collect::<Vec<_>>(), ["vector"]);
+
+        let asynchronous = &report.categories["async"];
+        assert_eq!(asynchronous.occurrences, 3);
+        assert_eq!(asynchronous.groups.iter().collect::<Vec<_>>(), ["server"]);
+
+        let uncovered = report.uncovered();
+        assert!(uncovered.contains(&"inline-asm"));
+        assert!(!uncovered.contains(&"simd"));
+    }
+}
@@ -0,0 +1,168 @@
+use std::collections::BTreeSet;
+use std::path::Path;
+
+use anyhow::Context;
+
+use crate::runtime::{BenchmarkGroupCrate, BenchmarkSuite};
+
+/// Methods of `benchlib`'s `BenchmarkGroup` that register a benchmark, taking its name as the
+/// first argument.
+const REGISTER_METHODS: &[&str] = &["register_benchmark", "register_benchmark_with_checksum"];
+
+/// Differences between the benchmarks that the sources of a group appear to declare and the
+/// benchmarks that its compiled binary lists.
+#[derive(Debug, PartialEq, Eq, serde::Serialize)]
+pub struct DeclarationMismatch {
+    pub group: String,
+    /// Benchmarks found in the sources, but not listed by the binary (e.g. because they are
+    /// excluded by `#[cfg]`).
+    pub declared_not_listed: Vec<String>,
+    /// Benchmarks listed by the binary, but not found in the sources (e.g. because their names
+    /// are not string literals).
+    pub listed_not_declared: Vec<String>,
+}
+
+/// Compares the benchmarks declared in the sources of each benchmark crate with the benchmarks
+/// listed by its compiled group in the suite, and returns the groups where they differ.
+/// Crates without a compiled group are skipped.
+///
+/// Declarations are found by searching the sources for registrations of benchmarks whose name is
+/// a string literal, so this is only a heuristic. The suite should not be sampled, otherwise the
+/// benchmarks that were not selected are reported as not listed.
+pub fn validate_declared_benchmarks(
+    crates: &[BenchmarkGroupCrate],
+    suite: &BenchmarkSuite,
+) -> anyhow::Result<Vec<DeclarationMismatch>> {
+    let mut mismatches = vec![];
+    for benchmark_crate in crates {
+        let Some(group) = suite
+            .groups
+            .iter()
+            .find(|group| group.name == benchmark_crate.name)
+        else {
+            continue;
+        };
+        let declared = declared_benchmarks(&benchmark_crate.path)?;
+        let listed: BTreeSet<String> = group
+            .benchmark_names
+            .iter()
+            .map(|name| group.original_names.get(name).unwrap_or(name).clone())
+            .collect();
+
+        let declared_not_listed: Vec<String> = declared.difference(&listed).cloned().collect();
+        let listed_not_declared: Vec<String> = listed.difference(&declared).cloned().collect();
+        if !declared_not_listed.is_empty() || !listed_not_declared.is_empty() {
+            mismatches.push(DeclarationMismatch {
+                group: group.name.clone(),
+                declared_not_listed,
+                listed_not_declared,
+            });
+        }
+    }
+    Ok(mismatches)
+}
+
+/// Returns the names of the benchmarks registered in the Rust sources of the crate in `dir`.
+fn declared_benchmarks(dir: &Path) -> anyhow::Result<BTreeSet<String>> {
+    let mut benchmarks = BTreeSet::new();
+    for entry in walkdir::WalkDir::new(dir)
+        .into_iter()
+        .filter_entry(|entry| entry.file_name() != "target")
+    {
+        let entry = entry?;
+        let path = entry.path();
+        if !entry.file_type().is_file() || path.extension().is_none_or(|ext| ext != "rs") {
+            continue;
+        }
+        let source = std::fs::read_to_string(path)
+            .with_context(|| format!("Cannot read {}", path.display()))?;
+        benchmarks.extend(registered_names(&source));
+    }
+    Ok(benchmarks)
+}
+
+/// Finds calls like `register_benchmark("name", ...)` in the source and returns their names.
+fn registered_names(source: &str) -> Vec<String> {
+    let mut names = vec![];
+    for method in REGISTER_METHODS {
+        let call = format!("{method}(");
+        for (start, _) in source.match_indices(&call) {
+            let arguments = source[start + call.len()..].trim_start();
+            let Some(name) = arguments.strip_prefix('"') else {
+                continue;
+            };
+            if let Some(end) = name.find('"') {
+                names.push(name[..end].to_string());
+            }
+        }
+    }
+    names
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::runtime::{BenchmarkGroup, BenchmarkGroupCrate, BenchmarkSuite};
+    use crate::toolchain::Toolchain;
+
+    use super::{validate_declared_benchmarks, DeclarationMismatch};
+
+    #[test]
+    fn test_validate_declared_benchmarks() {
+        let dir = tempfile::TempDir::new().unwrap();
+        let path = dir.path().join("hashmap");
+        std::fs::create_dir_all(path.join("src")).unwrap();
+        std::fs::write(
+            path.join("src/main.rs"),
+            r#"fn main() {
+    run_benchmark_group(|group| {
+        group.register_benchmark("insert", || || {});
+        #[cfg(feature = "nightly")]
+        group.register_benchmark_with_checksum(
+            "remove",
+            || || 0,
+        );
+        for size in [1, 2] {
+            group.register_benchmark(format!("lookup_{size}").leak(), || || {});
+        }
+    });
+}"#,
+        )
+        .unwrap();
+        let crates = vec![BenchmarkGroupCrate {
+            name: "hashmap".to_string(),
+            path,
+            tags: vec![],
+        }];
+        let group = BenchmarkGroup {
+            binary: "hashmap".into(),
+            name: "hashmap".to_string(),
+            benchmark_names: vec![
+                "insert".to_string(),
+                "lookup_1".to_string(),
+                "lookup_2".to_string(),
+            ],
+            features: vec![],
+            cached: false,
+            binary_size: 0,
+            original_names: Default::default(),
+            tags: vec![],
+            llvm_ir_size: None,
+            protocol_version: None,
+        };
+        let toolchain = Toolchain {
+            components: Default::default(),
+            id: "nightly".to_string(),
+            triple: "x86_64-unknown-linux-gnu".to_string(),
+        };
+        let suite = BenchmarkSuite::from_groups(toolchain, vec![group]);
+
+        assert_eq!(
+            validate_declared_benchmarks(&crates, &suite).unwrap(),
+            vec![DeclarationMismatch {
+                group: "hashmap".to_string(),
+                declared_not_listed: vec!["remove".to_string()],
+                listed_not_declared: vec!["lookup_1".to_string(), "lookup_2".to_string()],
+            }]
+        );
+    }
+}
@@ -0,0 +1,185 @@
+use std::fmt;
+use std::str::FromStr;
+
+use chrono::{DateTime, Utc};
+
+use crate::runtime::RunMeta;
+
+/// Template of a file name with `{placeholder}`s that are resolved from the metadata of a run,
+/// e.g. `{toolchain}-{date}.json`.
+///
+/// Supported placeholders:
+/// - `{toolchain}`: identifier of the benchmarked toolchain.
+/// - `{commit}`: commit of `rustc-perf` that executed the benchmarks.
+/// - `{date}`: date of the run (`YYYY-MM-DD`, UTC).
+/// - `{seed}`: seed passed to the benchmarks, or `none`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FilenameTemplate {
+    template: String,
+    segments: Vec<Segment>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Segment {
+    Literal(String),
+    Placeholder(Placeholder),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Placeholder {
+    Toolchain,
+    Commit,
+    Date,
+    Seed,
+}
+
+const PLACEHOLDERS: &[(&str, Placeholder)] = &[
+    ("toolchain", Placeholder::Toolchain),
+    ("commit", Placeholder::Commit),
+    ("date", Placeholder::Date),
+    ("seed", Placeholder::Seed),
+];
+
+impl FilenameTemplate {
+    /// Resolves the placeholders of the template. Path separators in the resolved values are
+    /// replaced, so that they cannot change the directory of the file.
+    pub fn resolve(&self, meta: &RunMeta, date: DateTime<Utc>) -> String {
+        let mut filename = String::new();
+        for segment in &self.segments {
+            match segment {
+                Segment::Literal(literal) => filename.push_str(literal),
+                Segment::Placeholder(placeholder) => {
+                    let value = match placeholder {
+                        Placeholder::Toolchain => meta.toolchain.clone(),
+                        Placeholder::Commit => meta.collector_version.trim().to_string(),
+                        Placeholder::Date => date.format("%Y-%m-%d").to_string(),
+                        Placeholder::Seed => meta
+                            .seed
+                            .map(|seed| seed.to_string())
+                            .unwrap_or_else(|| "none".to_string()),
+                    };
+                    filename.push_str(&value.replace(['/', '\\'], "-"));
+                }
+            }
+        }
+        filename
+    }
+}
+
+impl fmt::Display for FilenameTemplate {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.template)
+    }
+}
+
+impl serde::Serialize for FilenameTemplate {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.collect_str(self)
+    }
+}
+
+impl FromStr for FilenameTemplate {
+    type Err = anyhow::Error;
+
+    fn from_str(template: &str) -> Result<Self, Self::Err> {
+        let mut segments = vec![];
+        let mut rest = template;
+        while let Some(start) = rest.find('{') {
+            if start > 0 {
+                segments.push(Segment::Literal(rest[..start].to_string()));
+            }
+            let Some(end) = rest[start..].find('}') else {
+                return Err(anyhow::anyhow!(
+                    "Unclosed `{{` in filename template `{template}`"
+                ));
+            };
+            let name = &rest[start + 1..start + end];
+            let Some(&(_, placeholder)) = PLACEHOLDERS.iter().find(|(n, _)| *n == name) else {
+                return Err(anyhow::anyhow!(
+                    "Unknown placeholder `{{{name}}}` in filename template `{template}`, \
+expected one of: {}",
+                    PLACEHOLDERS
+                        .iter()
+                        .map(|(name, _)| format!("{{{name}}}"))
+                        .collect::<Vec<_>>()
+                        .join(", ")
+                ));
+            };
+            segments.push(Segment::Placeholder(placeholder));
+            rest = &rest[start + end + 1..];
+        }
+        if !rest.is_empty() {
+            segments.push(Segment::Literal(rest.to_string()));
+        }
+        Ok(Self {
+            template: template.to_string(),
+            segments,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::{TimeZone, Utc};
+
+    use super::FilenameTemplate;
+    use crate::runtime::RunMeta;
+
+    #[test]
+    fn test_resolve_filename_template() {
+        let meta = RunMeta {
+            toolchain: "nightly-2024-01-01".to_string(),
+            collector_version: "abc123\n".to_string(),
+            cpu_model: None,
+            cpu_cores: Some(8),
+            cpu_governor: None,
+            seed: Some(42),
+            thp_disabled: false,
+            nice: None,
+            io_priority: None,
+            lto: None,
+            overflow_checks: None,
+            run_id: None,
+        };
+        let date = Utc.with_ymd_and_hms(2024, 3, 15, 12, 0, 0).unwrap();
+        let resolve = |template: &str| {
+            template
+                .parse::<FilenameTemplate>()
+                .unwrap()
+                .resolve(&meta, date)
+        };
+
+        assert_eq!(
+            resolve("{toolchain}-{date}.json"),
+            "nightly-2024-01-01-2024-03-15.json"
+        );
+        assert_eq!(resolve("results/{commit}_{seed}"), "results/abc123_42");
+        assert_eq!(resolve("results.json"), "results.json");
+
+        let meta = RunMeta {
+            toolchain: "/path/to/stage1".to_string(),
+            seed: None,
+            ..meta
+        };
+        assert_eq!(
+            "{toolchain}-{seed}.json"
+                .parse::<FilenameTemplate>()
+                .unwrap()
+                .resolve(&meta, date),
+            "-path-to-stage1-none.json"
+        );
+    }
+
+    #[test]
+    fn test_invalid_filename_template() {
+        let error = "{toolchain}-{profile}.json"
+            .parse::<FilenameTemplate>()
+            .unwrap_err()
+            .to_string();
+        assert!(error.contains("Unknown placeholder `{profile}`"), "{error}");
+        assert!(error.contains("{toolchain}, {commit}, {date}, {seed}"));
+
+        let error = "{date".parse::<FilenameTemplate>().unwrap_err().to_string();
+        assert!(error.contains("Unclosed `{`"), "{error}");
+    }
+}
@@ -0,0 +1,80 @@
+use std::collections::HashMap;
+use std::path::Path;
+use std::time::Duration;
+
+use anyhow::Context;
+
+use crate::runtime::BenchmarkSuiteCompilation;
+
+/// Compile times of runtime benchmark groups observed by previous compilations, keyed by the
+/// group name. It can be used to skip groups that are slow to build, see
+/// `RuntimeCompilationOpts::max_compile_time`.
+#[derive(Debug, Default, Clone, serde::Serialize, serde::Deserialize)]
+pub struct CompileTimeHistory {
+    groups: HashMap<String, Duration>,
+}
+
+impl CompileTimeHistory {
+    /// Loads the history from `path`. If the file does not exist, the history is empty.
+    pub fn load(path: &Path) -> anyhow::Result<Self> {
+        if !path.is_file() {
+            return Ok(Self::default());
+        }
+        let contents = std::fs::read(path)
+            .with_context(|| format!("Cannot read compile time history {}", path.display()))?;
+        serde_json::from_slice(&contents)
+            .with_context(|| format!("Cannot parse compile time history {}", path.display()))
+    }
+
+    pub fn save(&self, path: &Path) -> anyhow::Result<()> {
+        let contents = serde_json::to_vec_pretty(self)?;
+        std::fs::write(path, contents)
+            .with_context(|| format!("Cannot write compile time history {}", path.display()))
+    }
+
+    /// Records the build times of the groups that were built by the given compilation. Groups
+    /// that were reused from a previous build are not updated, as their build time says nothing
+    /// about how long it takes to compile them.
+    pub fn record(&mut self, compilation: &BenchmarkSuiteCompilation) {
+        for timing in &compilation.build_timings {
+            self.groups.insert(timing.group.clone(), timing.duration());
+        }
+    }
+
+    pub fn set_compile_time(&mut self, group: &str, duration: Duration) {
+        self.groups.insert(group.to_string(), duration);
+    }
+
+    /// Returns the last recorded compile time of the group, if it is known.
+    pub fn compile_time(&self, group: &str) -> Option<Duration> {
+        self.groups.get(group).copied()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use super::CompileTimeHistory;
+
+    #[test]
+    fn test_compile_time_history_roundtrip() {
+        let dir = tempfile::TempDir::new().unwrap();
+        let path = dir.path().join("history.json");
+        assert!(CompileTimeHistory::load(&path)
+            .unwrap()
+            .compile_time("hashmap")
+            .is_none());
+
+        let mut history = CompileTimeHistory::default();
+        history.set_compile_time("hashmap", Duration::from_millis(1500));
+        history.save(&path).unwrap();
+
+        let history = CompileTimeHistory::load(&path).unwrap();
+        assert_eq!(
+            history.compile_time("hashmap"),
+            Some(Duration::from_millis(1500))
+        );
+        assert!(history.compile_time("bufreader").is_none());
+    }
+}
@@ -0,0 +1,126 @@
+use std::time::{Duration, Instant};
+
+/// How often is the system load checked while waiting for it to drop.
+const LOAD_POLL_INTERVAL: Duration = Duration::from_secs(5);
+
+/// Source of the system load average.
+pub trait LoadProbe {
+    /// Returns the 1-minute load average of the system.
+    fn load_average(&self) -> anyhow::Result<f64>;
+}
+
+/// Reads the load average of the current machine.
+pub struct SystemLoadProbe;
+
+impl LoadProbe for SystemLoadProbe {
+    #[cfg(unix)]
+    fn load_average(&self) -> anyhow::Result<f64> {
+        let mut loads = [0.0f64; 1];
+        let samples = unsafe { libc::getloadavg(loads.as_mut_ptr(), 1) };
+        if samples < 1 {
+            return Err(anyhow::anyhow!("Cannot read system load average"));
+        }
+        Ok(loads[0])
+    }
+
+    #[cfg(not(unix))]
+    fn load_average(&self) -> anyhow::Result<f64> {
+        Err(anyhow::anyhow!(
+            "Reading system load average is only supported on Unix"
+        ))
+    }
+}
+
+/// Makes sure that the system load is at most `max_load` before a benchmark is executed.
+/// If the load is higher, waits for up to `max_wait` for it to drop, and returns an error if it
+/// does not.
+pub fn wait_for_low_load(
+    probe: &dyn LoadProbe,
+    max_load: f64,
+    max_wait: Duration,
+) -> anyhow::Result<()> {
+    wait_for_low_load_with_interval(probe, max_load, max_wait, LOAD_POLL_INTERVAL)
+}
+
+fn wait_for_low_load_with_interval(
+    probe: &dyn LoadProbe,
+    max_load: f64,
+    max_wait: Duration,
+    poll_interval: Duration,
+) -> anyhow::Result<()> {
+    let start = Instant::now();
+    loop {
+        let load = probe.load_average()?;
+        if load <= max_load {
+            return Ok(());
+        }
+        if start.elapsed() >= max_wait {
+            return Err(anyhow::anyhow!(
+                "System load average {load:.2} exceeds the maximum of {max_load:.2}, refusing \
+to execute runtime benchmarks"
+            ));
+        }
+        log::warn!("System load average {load:.2} exceeds {max_load:.2}, waiting");
+        std::thread::sleep(poll_interval.min(max_wait.saturating_sub(start.elapsed())));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::cell::Cell;
+    use std::time::Duration;
+
+    use super::{wait_for_low_load_with_interval, LoadProbe};
+
+    struct MockLoadProbe {
+        loads: Vec<f64>,
+        index: Cell<usize>,
+    }
+
+    impl MockLoadProbe {
+        fn new(loads: Vec<f64>) -> Self {
+            Self {
+                loads,
+                index: Cell::new(0),
+            }
+        }
+    }
+
+    impl LoadProbe for MockLoadProbe {
+        fn load_average(&self) -> anyhow::Result<f64> {
+            let index = self.index.get();
+            self.index.set(index + 1);
+            Ok(self.loads[index.min(self.loads.len() - 1)])
+        }
+    }
+
+    #[test]
+    fn test_low_load_passes() {
+        let probe = MockLoadProbe::new(vec![0.5]);
+        assert!(
+            wait_for_low_load_with_interval(&probe, 1.0, Duration::ZERO, Duration::ZERO).is_ok()
+        );
+    }
+
+    #[test]
+    fn test_high_load_fails_without_waiting() {
+        let probe = MockLoadProbe::new(vec![4.0, 0.5]);
+        assert!(
+            wait_for_low_load_with_interval(&probe, 1.0, Duration::ZERO, Duration::ZERO).is_err()
+        );
+        assert_eq!(probe.index.get(), 1);
+    }
+
+    #[test]
+    fn test_wait_for_load_to_drop() {
+        let probe = MockLoadProbe::new(vec![4.0, 3.0, 0.5]);
+        assert!(wait_for_low_load_with_interval(
+            &probe,
+            1.0,
+            Duration::from_secs(60),
+            Duration::ZERO
+        )
+        .is_ok());
+        assert_eq!(probe.index.get(), 3);
+    }
+}
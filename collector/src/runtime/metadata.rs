@@ -0,0 +1,287 @@
+use std::fmt::Display;
+use std::path::Path;
+
+/// Path to the CPU frequency scaling governor of the first core on Linux.
+const CPU_GOVERNOR_PATH: &str = "/sys/devices/system/cpu/cpu0/cpufreq/scaling_governor";
+
+/// Information about the environment in which runtime benchmarks are executed.
+/// It is useful for checking whether results from different runs are comparable.
+#[derive(Debug, Clone, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+pub struct RunMeta {
+    /// Identifier of the toolchain used to compile the benchmarks.
+    pub toolchain: String,
+    /// Commit of `rustc-perf` that executed the benchmarks.
+    pub collector_version: String,
+    /// CPU model name. Only available on Linux.
+    pub cpu_model: Option<String>,
+    /// Number of logical CPU cores available to the collector.
+    pub cpu_cores: Option<usize>,
+    /// Active CPU frequency scaling governor. Only available on Linux.
+    pub cpu_governor: Option<String>,
+    /// Seed passed to the benchmarks for their random number generators.
+    pub seed: Option<u64>,
+    /// True if transparent huge pages were disabled for the benchmark processes.
+    #[serde(default)]
+    pub thp_disabled: bool,
+    /// Niceness with which the benchmark processes were executed, if it was set explicitly.
+    #[serde(default)]
+    pub nice: Option<i32>,
+    /// I/O scheduling class of the benchmark processes, if it was set explicitly.
+    #[serde(default)]
+    pub io_priority: Option<String>,
+    /// LTO setting with which the benchmarks were compiled, if it was overridden.
+    #[serde(default)]
+    pub lto: Option<String>,
+    /// Whether the benchmarks were compiled with overflow checks, if it was overridden.
+    #[serde(default)]
+    pub overflow_checks: Option<bool>,
+    /// Identifier of the run, either supplied by the user or generated randomly.
+    #[serde(default)]
+    pub run_id: Option<String>,
+}
+
+impl RunMeta {
+    /// Gathers metadata about the current machine.
+    /// Warns if the CPU frequency scaling governor could introduce measurement noise.
+    pub fn gather(toolchain: &str, collector_version: &str, seed: Option<u64>) -> Self {
+        Self::gather_with(
+            toolchain,
+            collector_version,
+            seed,
+            Path::new(CPU_GOVERNOR_PATH),
+        )
+    }
+
+    /// Gathers metadata like `gather`, but reads the CPU frequency scaling governor from
+    /// `governor_path`.
+    fn gather_with(
+        toolchain: &str,
+        collector_version: &str,
+        seed: Option<u64>,
+        governor_path: &Path,
+    ) -> Self {
+        let meta = Self {
+            toolchain: toolchain.to_string(),
+            collector_version: collector_version.to_string(),
+            cpu_model: read_cpu_model(),
+            cpu_cores: std::thread::available_parallelism()
+                .ok()
+                .map(|cores| cores.get()),
+            cpu_governor: read_cpu_governor(governor_path),
+            seed,
+            thp_disabled: false,
+            nice: None,
+            io_priority: None,
+            lto: None,
+            overflow_checks: None,
+            run_id: None,
+        };
+        if let Some(warning) = meta.governor_warning() {
+            log::warn!("{warning}");
+        }
+        meta
+    }
+
+    /// Returns a warning if the CPU frequency scaling governor is known and is not
+    /// `performance`, because other governors can introduce measurement noise.
+    pub fn governor_warning(&self) -> Option<String> {
+        let governor = self.cpu_governor.as_ref()?;
+        (governor != "performance").then(|| {
+            format!(
+                "CPU frequency scaling governor is `{governor}`, not `performance`. \
+Runtime benchmark results might be noisy."
+            )
+        })
+    }
+}
+
+/// A single field that differs between the metadata of two runs.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct MetadataDifference {
+    pub field: &'static str,
+    pub a: String,
+    pub b: String,
+}
+
+/// Lists all metadata fields that differ between two runs.
+/// Differences in the environment can explain result changes that are not caused by the compiler.
+/// The identifiers of the runs are not compared, as they differ by design.
+pub fn diff_run_metadata(a: &RunMeta, b: &RunMeta) -> Vec<MetadataDifference> {
+    fn display<T: Display>(value: &Option<T>) -> String {
+        value
+            .as_ref()
+            .map(|v| v.to_string())
+            .unwrap_or_else(|| "unknown".to_string())
+    }
+
+    let fields = [
+        ("toolchain", a.toolchain.clone(), b.toolchain.clone()),
+        (
+            "collector_version",
+            a.collector_version.clone(),
+            b.collector_version.clone(),
+        ),
+        ("cpu_model", display(&a.cpu_model), display(&b.cpu_model)),
+        ("cpu_cores", display(&a.cpu_cores), display(&b.cpu_cores)),
+        (
+            "cpu_governor",
+            display(&a.cpu_governor),
+            display(&b.cpu_governor),
+        ),
+        ("seed", display(&a.seed), display(&b.seed)),
+        (
+            "thp_disabled",
+            a.thp_disabled.to_string(),
+            b.thp_disabled.to_string(),
+        ),
+        ("nice", display(&a.nice), display(&b.nice)),
+        (
+            "io_priority",
+            display(&a.io_priority),
+            display(&b.io_priority),
+        ),
+        ("lto", display(&a.lto), display(&b.lto)),
+        (
+            "overflow_checks",
+            display(&a.overflow_checks),
+            display(&b.overflow_checks),
+        ),
+    ];
+    fields
+        .into_iter()
+        .filter(|(_, a, b)| a != b)
+        .map(|(field, a, b)| MetadataDifference { field, a, b })
+        .collect()
+}
+
+fn read_cpu_model() -> Option<String> {
+    if !cfg!(target_os = "linux") {
+        return None;
+    }
+    let cpuinfo = std::fs::read_to_string("/proc/cpuinfo").ok()?;
+    cpuinfo
+        .lines()
+        .find(|line| line.starts_with("model name"))
+        .and_then(|line| line.split_once(':'))
+        .map(|(_, model)| model.trim().to_string())
+}
+
+fn read_cpu_governor(path: &Path) -> Option<String> {
+    if !cfg!(target_os = "linux") {
+        return None;
+    }
+    std::fs::read_to_string(path)
+        .ok()
+        .map(|governor| governor.trim().to_string())
+        .filter(|governor| !governor.is_empty())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{diff_run_metadata, read_cpu_governor, MetadataDifference, RunMeta};
+
+    #[test]
+    fn test_gather_cpu_cores() {
+        assert!(RunMeta::gather("test", "abc", None).cpu_cores.unwrap() > 0);
+    }
+
+    #[test]
+    fn test_gather_seed() {
+        assert_eq!(RunMeta::gather("test", "abc", Some(42)).seed, Some(42));
+    }
+
+    #[test]
+    fn test_diff_run_metadata() {
+        let a = RunMeta {
+            toolchain: "nightly-2024-01-01".to_string(),
+            collector_version: "abc".to_string(),
+            cpu_model: Some("cpu".to_string()),
+            cpu_cores: Some(8),
+            cpu_governor: Some("performance".to_string()),
+            seed: Some(1),
+            thp_disabled: false,
+            nice: None,
+            io_priority: None,
+            lto: None,
+            overflow_checks: None,
+            run_id: Some("a".to_string()),
+        };
+        let b = RunMeta {
+            toolchain: "nightly-2024-01-02".to_string(),
+            cpu_governor: None,
+            thp_disabled: true,
+            nice: Some(10),
+            lto: Some("fat".to_string()),
+            run_id: Some("b".to_string()),
+            ..a.clone()
+        };
+        assert!(diff_run_metadata(&a, &a).is_empty());
+        assert_eq!(
+            diff_run_metadata(&a, &b),
+            vec![
+                MetadataDifference {
+                    field: "toolchain",
+                    a: "nightly-2024-01-01".to_string(),
+                    b: "nightly-2024-01-02".to_string(),
+                },
+                MetadataDifference {
+                    field: "cpu_governor",
+                    a: "performance".to_string(),
+                    b: "unknown".to_string(),
+                },
+                MetadataDifference {
+                    field: "thp_disabled",
+                    a: "false".to_string(),
+                    b: "true".to_string(),
+                },
+                MetadataDifference {
+                    field: "nice",
+                    a: "unknown".to_string(),
+                    b: "10".to_string(),
+                },
+                MetadataDifference {
+                    field: "lto",
+                    a: "unknown".to_string(),
+                    b: "fat".to_string(),
+                },
+            ]
+        );
+    }
+
+    #[cfg(target_os = "linux")]
+    #[test]
+    fn test_read_cpu_governor() {
+        let dir = tempfile::TempDir::new().unwrap();
+        let path = dir.path().join("scaling_governor");
+        assert_eq!(read_cpu_governor(&path), None);
+
+        std::fs::write(&path, "performance\n").unwrap();
+        assert_eq!(read_cpu_governor(&path), Some("performance".to_string()));
+    }
+
+    #[cfg(target_os = "linux")]
+    #[test]
+    fn test_gather_cpu_governor() {
+        let dir = tempfile::TempDir::new().unwrap();
+        let path = dir.path().join("scaling_governor");
+        let gather = || RunMeta::gather_with("test", "abc", None, &path);
+
+        let meta = gather();
+        assert_eq!(meta.cpu_governor, None);
+        assert_eq!(meta.governor_warning(), None);
+
+        std::fs::write(&path, "performance\n").unwrap();
+        let meta = gather();
+        assert_eq!(meta.cpu_governor.as_deref(), Some("performance"));
+        assert_eq!(meta.governor_warning(), None);
+
+        std::fs::write(&path, "powersave\n").unwrap();
+        let meta = gather();
+        assert_eq!(meta.cpu_governor.as_deref(), Some("powersave"));
+        let warning = meta.governor_warning().unwrap();
+        assert!(
+            warning.starts_with("CPU frequency scaling governor is `powersave`"),
+            "{warning}"
+        );
+    }
+}
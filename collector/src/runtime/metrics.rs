@@ -0,0 +1,210 @@
+use std::collections::{BTreeSet, HashMap};
+
+use benchlib::comm::messages::BenchmarkResult;
+
+/// A metric that can be collected for a runtime benchmark.
+#[derive(
+    Clone, Copy, Debug, PartialEq, Eq, Hash, PartialOrd, Ord, serde::Serialize, serde::Deserialize,
+)]
+#[serde(rename_all = "kebab-case")]
+pub enum RuntimeMetric {
+    WallTime,
+    Instructions,
+    Cycles,
+    BranchMisses,
+    CacheMisses,
+    CacheReferences,
+    MaxRss,
+}
+
+/// Maps benchmark names to the metrics that should be collected for them.
+/// Benchmarks that are not listed only collect wall time.
+///
+/// Wall time is always measured, because it is needed to execute the benchmark.
+#[derive(Clone, Debug, Default, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+#[serde(transparent)]
+pub struct MetricSelection {
+    benchmarks: HashMap<String, BTreeSet<RuntimeMetric>>,
+}
+
+impl MetricSelection {
+    /// Collects the given metrics for the benchmark with the given name.
+    pub fn select<I: IntoIterator<Item = RuntimeMetric>>(
+        mut self,
+        benchmark: &str,
+        metrics: I,
+    ) -> Self {
+        self.benchmarks
+            .insert(benchmark.to_string(), metrics.into_iter().collect());
+        self
+    }
+
+    /// Returns the metrics that should be collected for the given benchmark.
+    pub fn metrics_for(&self, benchmark: &str) -> BTreeSet<RuntimeMetric> {
+        let mut metrics = self.benchmarks.get(benchmark).cloned().unwrap_or_default();
+        metrics.insert(RuntimeMetric::WallTime);
+        metrics
+    }
+
+    /// Encodes the selection in the form expected by benchlib's `METRICS_ENV`, so that the
+    /// benchmark binary does not measure the metrics that were not requested.
+    ///
+    /// `reported_names` maps the names used by the binary to the names of the benchmarks in the
+    /// selection. If it is empty, the names are used as they are.
+    pub(super) fn to_env(&self, reported_names: &HashMap<String, String>) -> String {
+        let benchmarks: HashMap<&str, BTreeSet<RuntimeMetric>> = if reported_names.is_empty() {
+            self.benchmarks
+                .keys()
+                .map(|name| (name.as_str(), self.metrics_for(name)))
+                .collect()
+        } else {
+            reported_names
+                .iter()
+                .map(|(original, name)| (original.as_str(), self.metrics_for(name)))
+                .collect()
+        };
+        serde_json::to_string(&benchmarks).expect("cannot serialize metric selection")
+    }
+
+    /// Removes all metrics that were not requested from the result, so that they are neither
+    /// printed nor stored. This also covers binaries built with an older benchlib, which ignore
+    /// the selection passed to them.
+    pub(super) fn apply(&self, result: &mut BenchmarkResult) {
+        let metrics = self.metrics_for(&result.name);
+        let keep = |metric: RuntimeMetric, value: &mut Option<u64>| {
+            if !metrics.contains(&metric) {
+                *value = None;
+            }
+        };
+        for stats in &mut result.stats {
+            keep(RuntimeMetric::Instructions, &mut stats.instructions);
+            keep(RuntimeMetric::Cycles, &mut stats.cycles);
+            keep(RuntimeMetric::BranchMisses, &mut stats.branch_misses);
+            keep(RuntimeMetric::CacheMisses, &mut stats.cache_misses);
+            keep(RuntimeMetric::CacheReferences, &mut stats.cache_references);
+            keep(RuntimeMetric::MaxRss, &mut stats.max_rss);
+        }
+    }
+}
+
+/// Checks that the peak RSS of every benchmark stays within `budget` bytes, and returns an error
+/// listing all benchmarks that exceed it. Benchmarks without a measured peak RSS are ignored.
+pub fn assert_within_memory_budget(results: &[BenchmarkResult], budget: u64) -> anyhow::Result<()> {
+    let offenders: Vec<(&str, u64)> = results
+        .iter()
+        .filter_map(|result| {
+            let max_rss = result
+                .stats
+                .iter()
+                .filter_map(|stats| stats.max_rss)
+                .max()?;
+            (max_rss > budget).then_some((result.name.as_str(), max_rss))
+        })
+        .collect();
+    if offenders.is_empty() {
+        return Ok(());
+    }
+
+    let offenders = offenders
+        .into_iter()
+        .map(|(name, max_rss)| format!("  {name}: {max_rss} bytes"))
+        .collect::<Vec<_>>()
+        .join("\n");
+    Err(anyhow::anyhow!(
+        "Benchmarks exceeded the memory budget of {budget} bytes:\n{offenders}"
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+    use std::time::Duration;
+
+    use benchlib::comm::messages::{BenchmarkResult, BenchmarkStats};
+
+    use super::{assert_within_memory_budget, MetricSelection, RuntimeMetric};
+
+    fn result(name: &str) -> BenchmarkResult {
+        BenchmarkResult {
+            name: name.to_string(),
+            stats: vec![BenchmarkStats {
+                cycles: Some(1),
+                instructions: Some(2),
+                branch_misses: Some(3),
+                cache_misses: Some(4),
+                cache_references: Some(5),
+                wall_time: Duration::from_millis(6),
+                max_rss: Some(7),
+            }],
+            checksum: None,
+        }
+    }
+
+    #[test]
+    fn test_metric_selection() {
+        let selection = MetricSelection::default()
+            .select("hashmap", [RuntimeMetric::Instructions])
+            .select("nbody", [RuntimeMetric::WallTime]);
+
+        let mut hashmap = result("hashmap");
+        selection.apply(&mut hashmap);
+        let stats = &hashmap.stats[0];
+        assert_eq!(stats.instructions, Some(2));
+        assert_eq!(stats.cycles, None);
+        assert_eq!(stats.wall_time, Duration::from_millis(6));
+
+        for name in ["nbody", "unlisted"] {
+            let mut result = result(name);
+            selection.apply(&mut result);
+            let stats = &result.stats[0];
+            assert_eq!(stats.instructions, None);
+            assert_eq!(stats.cycles, None);
+            assert_eq!(stats.branch_misses, None);
+            assert_eq!(stats.cache_misses, None);
+            assert_eq!(stats.cache_references, None);
+            assert_eq!(stats.max_rss, None);
+            assert_eq!(stats.wall_time, Duration::from_millis(6));
+        }
+    }
+
+    #[test]
+    fn test_metric_selection_env() {
+        let selection = MetricSelection::default().select("hashmap", [RuntimeMetric::Instructions]);
+        let parse =
+            |env: String| -> HashMap<String, Vec<String>> { serde_json::from_str(&env).unwrap() };
+
+        let env = parse(selection.to_env(&HashMap::new()));
+        assert_eq!(
+            env,
+            HashMap::from([(
+                "hashmap".to_string(),
+                vec!["wall-time".to_string(), "instructions".to_string()]
+            )])
+        );
+
+        let reported_names = HashMap::from([
+            ("map".to_string(), "hashmap".to_string()),
+            ("body".to_string(), "nbody".to_string()),
+        ]);
+        let env = parse(selection.to_env(&reported_names));
+        assert_eq!(env["map"], vec!["wall-time", "instructions"]);
+        assert_eq!(env["body"], vec!["wall-time"]);
+    }
+
+    #[test]
+    fn test_memory_budget() {
+        let mut large = result("large");
+        large.stats[0].max_rss = Some(2048);
+        let mut unmeasured = result("unmeasured");
+        unmeasured.stats[0].max_rss = None;
+        let results = vec![result("small"), large, unmeasured];
+
+        assert!(assert_within_memory_budget(&results, 2048).is_ok());
+
+        let error = assert_within_memory_budget(&results, 1024).unwrap_err();
+        let message = error.to_string();
+        assert!(message.contains("large: 2048 bytes"), "{message}");
+        assert!(!message.contains("small"), "{message}");
+        assert!(!message.contains("unmeasured"), "{message}");
+    }
+}
@@ -1,47 +1,450 @@
+use std::collections::{BTreeMap, HashMap};
 use std::ffi::OsStr;
+use std::fmt;
 use std::io::{BufRead, BufReader, Cursor};
-use std::path::Path;
-use std::process::{Command, Stdio};
+use std::path::{Path, PathBuf};
+use std::process::{Command, Output, Stdio};
+use std::time::{Duration, Instant};
 
 use anyhow::Context;
 use thousands::Separable;
 
+use benchlib::benchmark::{INPUT_SIZE_ENV, METRICS_ENV, SEED_ENV};
 use benchlib::comm::messages::{BenchmarkMessage, BenchmarkResult, BenchmarkStats};
 pub use benchmark::{
-    get_runtime_benchmark_groups, prepare_runtime_benchmark_suite, runtime_benchmark_dir,
-    BenchmarkGroup, BenchmarkGroupCrate, BenchmarkSuite, BenchmarkSuiteCompilation,
-    CargoIsolationMode, RuntimeBenchmarkFilter,
+    benchmarks_changed_in_diff, binary_is_current, get_nested_runtime_benchmark_groups,
+    get_runtime_benchmark_groups, prepare_runtime_benchmark_suite, qualified_name,
+    runtime_benchmark_dir, suite_from_target_dir, BenchmarkGroup, BenchmarkGroupCrate,
+    BenchmarkGroupFilter, BenchmarkSuite, BenchmarkSuiteCompilation, CargoIsolationMode,
+    DiscoveryError, GroupBuildTiming, GroupSelection, RuntimeBenchmarkFilter,
 };
 use database::{ArtifactIdNumber, CollectionId, Connection};
 
 use crate::utils::git::get_rustc_perf_commit;
 use crate::{run_command_with_output, CollectorCtx};
 
+mod ab;
 mod benchmark;
+mod binary_cache;
+mod cgroup;
+mod compare;
+mod config;
+mod coverage;
+mod declared;
+mod filename;
+mod history;
+mod load;
+mod metadata;
+mod metrics;
+mod preflight;
 mod profile;
+mod report;
+mod repro;
+mod resume;
+mod shard;
+mod stream;
+mod tags;
 
-pub use benchmark::RuntimeCompilationOpts;
-pub use profile::{profile_runtime, RuntimeProfiler};
+pub use ab::{ab_compare, BenchmarkComparison};
+pub use benchmark::{CargoNetworkMode, RuntimeCompilationOpts, DEFAULT_LIST_COMMAND};
+pub use binary_cache::BinaryCache;
+pub use cgroup::{BenchmarkCgroup, CgroupConfig};
+pub use compare::{
+    benchmark_metrics, compare_to_baseline, find_checksum_mismatches, result_set_hash,
+    BenchmarkMetrics, ChecksumMismatch, ComparisonConfig, MetricDelta, MetricDirection,
+};
+pub use config::{
+    print_effective_config, CompileOverrides, FilterSyntax, RunConfig, RunConfigLayer,
+};
+pub use coverage::{feature_coverage, CoverageReport, FeatureCoverage};
+pub use declared::{validate_declared_benchmarks, DeclarationMismatch};
+pub use filename::FilenameTemplate;
+pub use history::CompileTimeHistory;
+pub use load::{wait_for_low_load, LoadProbe, SystemLoadProbe};
+pub use metadata::{diff_run_metadata, MetadataDifference, RunMeta};
+pub use metrics::{assert_within_memory_budget, MetricSelection, RuntimeMetric};
+pub use preflight::{preflight, PreflightIssue, PreflightIssueKind};
+pub use profile::{measure_dhat, profile_runtime, DhatStats, RuntimeProfiler};
+pub use report::{
+    validate_results, write_combined_report, write_junit, write_prometheus, CombinedReport,
+    CompileBenchmarkResult, PhaseTimings, RunReport, RuntimeBenchmarkFailure,
+    RuntimeBenchmarkResult, MIN_TOTAL_MEASURED_TIME,
+};
+pub use repro::{reproducibility_manifest, BenchmarkDirIdentity, ReproManifest, ToolchainIdentity};
+pub use resume::{fingerprint_crate, CompilationResume};
+pub use shard::merge_shard_results;
+pub use stream::{ResultStream, StreamAddress};
+pub use tags::TagExpr;
 
 pub const DEFAULT_RUNTIME_ITERATIONS: u32 = 5;
 
+/// Name of the `benchlib` subcommand that executes the benchmarks of a group.
+pub const DEFAULT_RUN_COMMAND: &str = "run";
+
+/// Options that affect how are the runtime benchmark binaries executed.
+#[derive(Clone, Debug, Default, serde::Serialize)]
+pub struct RuntimeRunOpts {
+    input_sizes: Vec<u64>,
+    pin_core: Option<usize>,
+    disable_thp: bool,
+    nice: Option<i32>,
+    io_priority: Option<IoPriorityClass>,
+    run_until: RunUntil,
+    runner: Option<Vec<String>>,
+    max_load_average: Option<(f64, Duration)>,
+    junit_report: Option<PathBuf>,
+    prometheus_report: Option<PathBuf>,
+    results_file: Option<FilenameTemplate>,
+    result_stream: Option<StreamAddress>,
+    print_phase_timings: bool,
+    run_command: Option<String>,
+    metrics: Option<MetricSelection>,
+    seed: Option<u64>,
+    memory_budget: Option<u64>,
+    cgroup: Option<CgroupConfig>,
+    result_validator: Option<Vec<String>>,
+    stdin_file: Option<PathBuf>,
+    run_stderr: StdioMode,
+    group_stdin_files: BTreeMap<String, PathBuf>,
+    total_timeout: Option<Duration>,
+    run_id: Option<String>,
+    /// Point in time at which the run is stopped, computed from `total_timeout` when the run
+    /// starts.
+    #[serde(skip)]
+    deadline: Option<Instant>,
+}
+
+impl RuntimeRunOpts {
+    /// Executes each benchmark once per input size. The size is passed to the benchmark process
+    /// in the `RUSTC_PERF_INPUT_SIZE` environment variable, and each result is labeled
+    /// `<benchmark>[size=<size>]`.
+    pub fn input_sizes(mut self, input_sizes: Vec<u64>) -> Self {
+        self.input_sizes = input_sizes;
+        self
+    }
+
+    /// Pins the benchmark processes to a single CPU core (e.g. one isolated with `isolcpus`).
+    /// Only supported on Linux.
+    pub fn pin_core(mut self, core: usize) -> Self {
+        self.pin_core = Some(core);
+        self
+    }
+
+    /// Disables transparent huge pages for the benchmark processes (using
+    /// `prctl(PR_SET_THP_DISABLE)`), which can otherwise cause measurement noise. Only supported
+    /// on Linux, ignored elsewhere.
+    pub fn disable_thp(mut self, disable_thp: bool) -> Self {
+        self.disable_thp = disable_thp;
+        self
+    }
+
+    /// Executes the benchmark processes with the given niceness, instead of inheriting the
+    /// niceness of the collector. Lowering the niceness below the current one usually requires
+    /// elevated privileges. Only supported on Unix.
+    pub fn nice(mut self, nice: i32) -> Self {
+        self.nice = Some(nice);
+        self
+    }
+
+    /// Executes the benchmark processes in the given I/O scheduling class (like `ionice -c`).
+    /// Only supported on Linux.
+    pub fn io_priority(mut self, class: IoPriorityClass) -> Self {
+        self.io_priority = Some(class);
+        self
+    }
+
+    /// Determines how many times is the benchmark process of each group executed. By default,
+    /// it is executed once.
+    pub fn run_until(mut self, policy: RunUntil) -> Self {
+        self.run_until = policy;
+        self
+    }
+
+    /// Executes the benchmark binaries through the given command (a program followed by its
+    /// arguments), e.g. an emulator for binaries compiled for another target. Defaults to the
+    /// runner with which the suite was compiled.
+    pub fn runner(mut self, runner: Vec<String>) -> Self {
+        self.runner = Some(runner);
+        self
+    }
+
+    /// Checks the system load average before executing each benchmark group. If it exceeds
+    /// `max_load`, the collector waits for up to `max_wait` for the load to drop, and aborts the
+    /// run otherwise.
+    pub fn max_load_average(mut self, max_load: f64, max_wait: Duration) -> Self {
+        self.max_load_average = Some((max_load, max_wait));
+        self
+    }
+
+    /// Writes a JUnit XML report with the outcome of each benchmark to the given path after
+    /// the run finishes.
+    pub fn junit_report(mut self, path: PathBuf) -> Self {
+        self.junit_report = Some(path);
+        self
+    }
+
+    /// Writes the results in the Prometheus text exposition format to the given path after the
+    /// run finishes, e.g. to push them to a Pushgateway.
+    pub fn prometheus_report(mut self, path: PathBuf) -> Self {
+        self.prometheus_report = Some(path);
+        self
+    }
+
+    /// Writes the report of the run as JSON after the run finishes, to a path created from the
+    /// template by resolving its placeholders from the metadata of the run.
+    pub fn results_file(mut self, template: FilenameTemplate) -> Self {
+        self.results_file = Some(template);
+        self
+    }
+
+    /// Sends each result as a JSON line to the given socket as soon as its group finishes, e.g.
+    /// to feed a live dashboard. Results that cannot be delivered (even after reconnecting) are
+    /// dropped, they do not fail the run.
+    pub fn stream_results(mut self, address: StreamAddress) -> Self {
+        self.result_stream = Some(address);
+        self
+    }
+
+    /// Prints how long did the discovery, the compilation and the execution of the benchmarks
+    /// take as a JSON object after the run finishes.
+    pub fn print_phase_timings(mut self, print_phase_timings: bool) -> Self {
+        self.print_phase_timings = print_phase_timings;
+        self
+    }
+
+    /// Sets the subcommand used to execute the benchmark binaries, for binaries whose CLI differs
+    /// from the one of the current `benchlib`. Defaults to `run`.
+    pub fn run_command(mut self, run_command: &str) -> Self {
+        self.run_command = Some(run_command.to_string());
+        self
+    }
+
+    /// Only collects the selected metrics for each benchmark. By default, all metrics are
+    /// collected.
+    pub fn metrics(mut self, metrics: MetricSelection) -> Self {
+        self.metrics = Some(metrics);
+        self
+    }
+
+    /// Passes a seed for random number generators to the benchmark processes in the
+    /// `RUSTC_PERF_SEED` environment variable, so that benchmarks that use randomness are
+    /// reproducible.
+    pub fn seed(mut self, seed: u64) -> Self {
+        self.seed = Some(seed);
+        self
+    }
+
+    /// Fails a benchmark group if the peak RSS of any of its benchmarks exceeds `budget` bytes.
+    /// The results of the group are still recorded, together with its error.
+    pub fn memory_budget(mut self, budget: u64) -> Self {
+        self.memory_budget = Some(budget);
+        self
+    }
+
+    /// Executes the benchmark processes inside the given cgroup (v2). The cgroup is created by
+    /// `bench_runtime` if it does not exist, and removed after the run. Only supported on Linux.
+    pub fn cgroup(mut self, cgroup: CgroupConfig) -> Self {
+        self.cgroup = Some(cgroup);
+        self
+    }
+
+    /// Executes the given command (program followed by its arguments) after the run, with the
+    /// path to a JSON file with the results appended. The run fails if the command exits
+    /// unsuccessfully.
+    pub fn result_validator(mut self, command: Vec<String>) -> Self {
+        self.result_validator = Some(command);
+        self
+    }
+
+    /// Connects the standard input of the benchmark processes to the given file, for benchmarks
+    /// that read their input from stdin. The file is opened anew for each benchmark process.
+    pub fn stdin_file(mut self, path: PathBuf) -> Self {
+        self.stdin_file = Some(path);
+        self
+    }
+
+    /// Connects the standard input of the benchmark process of `group` to the given file,
+    /// overriding `stdin_file`. All benchmarks of a group are executed by a single process, so
+    /// they share its stdin.
+    pub fn group_stdin_file(mut self, group: &str, path: PathBuf) -> Self {
+        self.group_stdin_files.insert(group.to_string(), path);
+        self
+    }
+
+    /// Determines what happens with the stderr of the benchmark processes. By default, it is
+    /// captured and included in the error of a failed group.
+    pub fn run_stderr(mut self, mode: StdioMode) -> Self {
+        self.run_stderr = mode;
+        self
+    }
+
+    /// Stops the run once it takes longer than `timeout`. The benchmark group that is executing
+    /// at that moment is terminated, no further groups are started, and the report is marked as
+    /// timed out.
+    pub fn total_timeout(mut self, timeout: Duration) -> Self {
+        self.total_timeout = Some(timeout);
+        self
+    }
+
+    /// Identifies the run in all of its outputs (the results file, the result stream and the
+    /// database), e.g. with a CI build id, so that results uploaded to a shared store can be
+    /// told apart. By default, a random UUID is generated for each run.
+    pub fn run_id(mut self, run_id: &str) -> Self {
+        self.run_id = Some(run_id.to_string());
+        self
+    }
+}
+
+/// Policy that determines how many times is the benchmark process of a group executed. The
+/// statistics of all executions are merged into a single result per benchmark.
+#[derive(Clone, Copy, Debug, Default, PartialEq, serde::Serialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum RunUntil {
+    /// Executes the benchmark process once.
+    #[default]
+    Once,
+    /// Executes the benchmark process repeatedly, until the mean wall time of each benchmark
+    /// (over all executions so far) changes by less than `rel_tolerance` (e.g. `0.01` for 1%)
+    /// between two successive executions, or until it has been executed `max_runs` times.
+    Converged { rel_tolerance: f64, max_runs: u32 },
+}
+
+/// How is an output stream of the benchmark processes handled.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, clap::ValueEnum, serde::Serialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum StdioMode {
+    /// The stream is shown live in the output of the collector, e.g. for interactive debugging.
+    Inherit,
+    /// The stream is captured, and included in the error if the process fails.
+    #[default]
+    Capture,
+    /// The stream is discarded.
+    Null,
+}
+
+impl StdioMode {
+    fn stdio(&self) -> Stdio {
+        match self {
+            StdioMode::Inherit => Stdio::inherit(),
+            StdioMode::Capture => Stdio::piped(),
+            StdioMode::Null => Stdio::null(),
+        }
+    }
+}
+
+/// I/O scheduling class of the benchmark processes.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, clap::ValueEnum, serde::Serialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum IoPriorityClass {
+    /// Gets I/O access first, regardless of other processes. Usually requires elevated
+    /// privileges.
+    RealTime,
+    /// The default class of processes.
+    BestEffort,
+    /// Only gets I/O access when no other process needs it.
+    Idle,
+}
+
+impl IoPriorityClass {
+    fn as_str(&self) -> &'static str {
+        match self {
+            IoPriorityClass::RealTime => "real-time",
+            IoPriorityClass::BestEffort => "best-effort",
+            IoPriorityClass::Idle => "idle",
+        }
+    }
+}
+
+/// Error returned when the total timeout of a run is exceeded.
+#[derive(Debug)]
+struct RunTimedOut;
+
+impl fmt::Display for RunTimedOut {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("The total timeout of the run was exceeded")
+    }
+}
+
+impl std::error::Error for RunTimedOut {}
+
 /// Perform a series of runtime benchmarks using the provided `rustc` compiler.
 /// The runtime benchmarks are looked up in `benchmark_dir`, which is expected to be a path
 /// to a Cargo crate. All binaries built by that crate are expected to be runtime benchmark
 /// groups that use `benchlib`.
+///
+/// Failures of individual benchmark groups are recorded into the database and into the returned
+/// report, without aborting the rest of the run. If the total timeout is exceeded, the partial
+/// report is returned.
 pub async fn bench_runtime(
     conn: &mut dyn Connection,
     suite: BenchmarkSuite,
     collector: &CollectorCtx,
     filter: RuntimeBenchmarkFilter,
     iterations: u32,
-) -> anyhow::Result<()> {
-    let filtered = suite.filtered_benchmark_count(&filter);
+    opts: &RuntimeRunOpts,
+) -> anyhow::Result<RunReport> {
+    let start = Instant::now();
+    let filtered = suite.filtered_benchmark_count(&filter) * opts.input_sizes.len().max(1) as u64;
     println!("Executing {} benchmarks\n", filtered);
 
     let rustc_perf_version = get_rustc_perf_commit();
+    let mut meta = RunMeta::gather(&suite.toolchain.id, &rustc_perf_version, opts.seed);
+    meta.thp_disabled = opts.disable_thp && cfg!(target_os = "linux");
+    meta.nice = opts.nice;
+    meta.io_priority = opts.io_priority.map(|class| class.as_str().to_string());
+    meta.lto = suite.compile_settings.lto.clone();
+    meta.overflow_checks = suite.compile_settings.overflow_checks;
+    let run_id = opts
+        .run_id
+        .clone()
+        .unwrap_or_else(|| uuid::Uuid::new_v4().to_string());
+    meta.run_id = Some(run_id.clone());
+    log::info!("Runtime benchmark environment: {meta:?}");
+    log::info!("Runtime benchmark filter: `{filter}`");
+
+    // If the cgroup cannot be set up, the benchmarks are executed without it.
+    let mut opts = opts.clone();
+    let _cgroup = match opts.cgroup.take() {
+        Some(config) => match BenchmarkCgroup::setup(&config) {
+            Ok(cgroup) => {
+                opts.cgroup = Some(config);
+                Some(cgroup)
+            }
+            Err(error) => {
+                log::warn!(
+                    "Cannot set up cgroup {}, benchmarks will not be isolated: {error:?}",
+                    config.path.display()
+                );
+                None
+            }
+        },
+        None => None,
+    };
+    opts.deadline = opts.total_timeout.map(|timeout| Instant::now() + timeout);
+    if opts.runner.is_none() {
+        opts.runner = suite.compile_settings.runner.clone();
+    }
+    let opts = &opts;
+
+    let mut report = RunReport {
+        run_id: Some(run_id.clone()),
+        phase_timings: suite.phase_timings,
+        ..RunReport::default()
+    };
+    let mut stream = opts.result_stream.clone().map(ResultStream::new);
     let mut benchmark_index = 0;
     for group in suite.groups {
+        if opts
+            .deadline
+            .is_some_and(|deadline| Instant::now() >= deadline)
+        {
+            report.timed_out = true;
+            break;
+        }
+        if let Some((max_load, max_wait)) = opts.max_load_average {
+            wait_for_low_load(&SystemLoadProbe, max_load, max_wait)?;
+        }
+
         let Some(step_name) = collector.start_runtime_step(conn, &group).await else {
             eprintln!("skipping {} -- already benchmarked", group.name);
             continue;
@@ -52,65 +455,175 @@ pub async fn bench_runtime(
         // Async block is used to easily capture all results, it basically simulates a `try` block.
         // Extracting this into a separate function would be annoying, as there would be many
         // parameters.
+        let group_opts;
+        let opts = match opts.group_stdin_files.get(&group.name) {
+            Some(path) => {
+                group_opts = RuntimeRunOpts {
+                    stdin_file: Some(path.clone()),
+                    ..opts.clone()
+                };
+                &group_opts
+            }
+            None => opts,
+        };
         let result = async {
-            let messages = execute_runtime_benchmark_binary(&group.binary, &filter, iterations)?;
-            for message in messages {
-                let message = message.map_err(|err| {
-                    anyhow::anyhow!(
-                        "Cannot parse BenchmarkMessage from benchmark {}: {err:?}",
-                        group.binary.display()
-                    )
-                })?;
-                match message {
-                    BenchmarkMessage::Result(result) => {
-                        benchmark_index += 1;
-                        println!(
-                            "Finished {}/{} ({}/{})",
-                            group.name, result.name, benchmark_index, filtered
-                        );
-
-                        print_stats(&result);
-                        record_stats(
-                            tx.conn(),
-                            collector.artifact_row_id,
-                            &rustc_perf_version,
-                            result,
-                        )
-                        .await;
-                    }
-                }
+            let (results, runs) = execute_runtime_benchmark_group_runs(
+                &group.binary,
+                &group.original_names,
+                &filter,
+                iterations,
+                opts,
+            )?;
+            for result in &results {
+                benchmark_index += 1;
+                println!(
+                    "Finished {}/{} ({}/{})",
+                    group.name, result.name, benchmark_index, filtered
+                );
+
+                print_stats(result);
+                record_stats(
+                    tx.conn(),
+                    collector.artifact_row_id,
+                    &rustc_perf_version,
+                    &run_id,
+                    result,
+                )
+                .await;
+            }
+            // The results of a group that exceeds the budget are kept, but the group fails.
+            if let Some(budget) = opts.memory_budget {
+                assert_within_memory_budget(&results, budget)?;
             }
 
-            Ok::<_, anyhow::Error>(())
+            Ok::<_, anyhow::Error>((results, runs))
         }
         .await
         .with_context(|| format!("Failed to execute runtime benchmark group {}", group.name));
 
-        if let Err(error) = result {
-            eprintln!("collector error: {:#}", error);
-            tx.conn()
-                .record_error(
-                    collector.artifact_row_id,
-                    &step_name,
-                    &format!("{:?}", error),
-                )
-                .await;
+        let selected_benchmarks = || {
+            group
+                .benchmark_names
+                .iter()
+                .filter(|benchmark| filter.matches(benchmark))
+                .cloned()
+                .collect()
         };
 
+        if let Err(ref error) = result {
+            if error.downcast_ref::<RunTimedOut>().is_some() {
+                // Do not record anything about the interrupted group into the database, so that
+                // it can be benchmarked again later.
+                tx.finish()
+                    .await
+                    .expect("Cannot roll back runtime benchmark group results");
+                report.add_interrupted(&group.name, selected_benchmarks(), error);
+                break;
+            }
+        }
+
+        match result {
+            Ok((results, runs)) => {
+                for result in results {
+                    let runs = runs.get(&result.name).copied().unwrap_or(1);
+                    report.add_repeated_success(&group.name, result, runs);
+                    let success = report.successes.last().unwrap();
+                    if let Some(ref warning) = success.warning {
+                        log::warn!("{warning}");
+                    }
+                    if let Some(ref mut stream) = stream {
+                        stream.send(&StreamedResult {
+                            run_id: &run_id,
+                            result: success,
+                        });
+                    }
+                }
+            }
+            Err(error) => {
+                eprintln!("collector error: {:#}", error);
+                tx.conn()
+                    .record_error(
+                        collector.artifact_row_id,
+                        &step_name,
+                        &format!("{:?}", error),
+                    )
+                    .await;
+
+                report.add_failure(&group.name, selected_benchmarks(), &error);
+            }
+        }
+
         collector.end_runtime_step(tx.conn(), &group).await;
         tx.commit()
             .await
             .expect("Cannot commit runtime benchmark group results");
     }
 
-    Ok(())
+    report.phase_timings.running = start.elapsed();
+
+    if report.timed_out {
+        eprintln!(
+            "Runtime benchmark run exceeded the total timeout of {:?}, {} benchmark(s) completed",
+            opts.total_timeout.unwrap_or_default(),
+            report.successes.len()
+        );
+    }
+    for warning in report.identical_results_warnings() {
+        log::warn!("{warning}");
+    }
+    if !report.is_success() {
+        eprintln!(
+            "{} runtime benchmark group(s) failed:",
+            report.failures.len()
+        );
+        for failure in &report.failures {
+            eprintln!("  {} ({})", failure.group, failure.benchmarks.join(", "));
+        }
+    }
+    if let Some(ref path) = opts.junit_report {
+        let file = std::fs::File::create(path)
+            .with_context(|| format!("Cannot create JUnit report at {}", path.display()))?;
+        write_junit(&report, std::io::BufWriter::new(file))?;
+    }
+    if let Some(ref path) = opts.prometheus_report {
+        let file = std::fs::File::create(path)
+            .with_context(|| format!("Cannot create Prometheus report at {}", path.display()))?;
+        write_prometheus(&report.successes, std::io::BufWriter::new(file))?;
+    }
+    if let Some(ref template) = opts.results_file {
+        let path = template.resolve(&meta, chrono::Utc::now());
+        let file = std::fs::File::create(&path)
+            .with_context(|| format!("Cannot create results file at {path}"))?;
+        serde_json::to_writer_pretty(std::io::BufWriter::new(file), &report)?;
+    }
+    if let Some(ref validator) = opts.result_validator {
+        validate_results(&report, validator)?;
+    }
+    if opts.print_phase_timings {
+        println!("{}", serde_json::to_string(&report.phase_timings())?);
+    }
+
+    Ok(report)
+}
+
+/// A result sent to the result stream, labeled with the identifier of its run.
+#[derive(serde::Serialize)]
+struct StreamedResult<'a> {
+    run_id: &'a str,
+    #[serde(flatten)]
+    result: &'a RuntimeBenchmarkResult,
 }
 
-/// Prepares a command for execution, adding some shared flags.
-fn prepare_command<S: AsRef<OsStr>>(binary: S) -> Command {
+/// Prepares a command for execution, adding some shared flags. If `runner` is not empty,
+/// the binary is executed through it.
+fn prepare_command<S: AsRef<OsStr>>(binary: S, runner: &[String]) -> Command {
     // Turn off ASLR
     let mut command = Command::new("setarch");
-    command.arg(std::env::consts::ARCH).arg("-R").arg(binary);
+    command
+        .arg(std::env::consts::ARCH)
+        .arg("-R")
+        .args(runner)
+        .arg(binary);
 
     // We want to see a backtrace if the program panics
     command.env("RUST_BACKTRACE", "1");
@@ -120,12 +633,179 @@ fn prepare_command<S: AsRef<OsStr>>(binary: S) -> Command {
     command
 }
 
+/// Executes the command and gathers its output. If it is still running at the deadline, it is
+/// killed together with its child processes, and `RunTimedOut` is returned.
+#[cfg(unix)]
+fn run_command_until(command: &mut Command, deadline: Instant) -> anyhow::Result<Output> {
+    use std::os::unix::process::CommandExt;
+    use std::sync::atomic::{AtomicBool, Ordering};
+    use std::sync::{mpsc, Arc};
+
+    let remaining = deadline.saturating_duration_since(Instant::now());
+    if remaining.is_zero() {
+        return Err(RunTimedOut.into());
+    }
+
+    // Start the process in a new process group, so that its children can be killed with it.
+    command.process_group(0);
+    let child = command
+        .spawn()
+        .with_context(|| format!("failed to spawn process for cmd: {command:?}"))?;
+    let pid = child.id() as libc::pid_t;
+
+    let killed = Arc::new(AtomicBool::new(false));
+    let (done, wait_for_done) = mpsc::channel::<()>();
+    let watchdog = {
+        let killed = killed.clone();
+        std::thread::spawn(move || {
+            if let Err(mpsc::RecvTimeoutError::Timeout) = wait_for_done.recv_timeout(remaining) {
+                killed.store(true, Ordering::SeqCst);
+                unsafe {
+                    libc::kill(-pid, libc::SIGKILL);
+                }
+            }
+        })
+    };
+    let output = child.wait_with_output();
+    // Disconnecting the channel stops the watchdog.
+    drop(done);
+    watchdog.join().expect("Watchdog thread has panicked");
+
+    if killed.load(Ordering::SeqCst) {
+        return Err(RunTimedOut.into());
+    }
+    output.context("failed to wait on child process")
+}
+
+#[cfg(not(unix))]
+fn run_command_until(command: &mut Command, deadline: Instant) -> anyhow::Result<Output> {
+    if Instant::now() >= deadline {
+        return Err(RunTimedOut.into());
+    }
+    run_command_with_output(command)
+}
+
+/// Makes sure that the process spawned by `command` will only run on the given CPU core.
+#[cfg(target_os = "linux")]
+fn pin_to_core(command: &mut Command, core: usize) -> anyhow::Result<()> {
+    use std::os::unix::process::CommandExt;
+
+    // Isolated cores are not a part of the affinity mask of the collector, so we check against
+    // all configured cores rather than the available ones.
+    let cores = unsafe { libc::sysconf(libc::_SC_NPROCESSORS_CONF) };
+    if cores < 1 || core >= cores as usize {
+        return Err(anyhow::anyhow!(
+            "Cannot pin benchmarks to core {core}, the machine has {cores} core(s)"
+        ));
+    }
+
+    // SAFETY: the closure only calls async-signal-safe functions.
+    unsafe {
+        command.pre_exec(move || {
+            let mut set: libc::cpu_set_t = std::mem::zeroed();
+            libc::CPU_SET(core, &mut set);
+            if libc::sched_setaffinity(0, std::mem::size_of::<libc::cpu_set_t>(), &set) != 0 {
+                return Err(std::io::Error::last_os_error());
+            }
+            Ok(())
+        });
+    }
+    Ok(())
+}
+
+#[cfg(not(target_os = "linux"))]
+fn pin_to_core(_command: &mut Command, _core: usize) -> anyhow::Result<()> {
+    Err(anyhow::anyhow!(
+        "Pinning benchmarks to a CPU core is only supported on Linux"
+    ))
+}
+
+/// Disables transparent huge pages for the process spawned by `command`. The setting is inherited
+/// by its children.
+#[cfg(target_os = "linux")]
+fn disable_thp(command: &mut Command) {
+    use std::os::unix::process::CommandExt;
+
+    // SAFETY: the closure only calls async-signal-safe functions.
+    unsafe {
+        command.pre_exec(|| {
+            let (enable, unused): (libc::c_ulong, libc::c_ulong) = (1, 0);
+            if libc::prctl(libc::PR_SET_THP_DISABLE, enable, unused, unused, unused) != 0 {
+                return Err(std::io::Error::last_os_error());
+            }
+            Ok(())
+        });
+    }
+}
+
+#[cfg(not(target_os = "linux"))]
+fn disable_thp(_command: &mut Command) {}
+
+/// Sets the niceness of the process spawned by `command`.
+#[cfg(unix)]
+fn set_nice(command: &mut Command, nice: i32) -> anyhow::Result<()> {
+    use std::os::unix::process::CommandExt;
+
+    // SAFETY: the closure only calls async-signal-safe functions.
+    unsafe {
+        command.pre_exec(move || {
+            if libc::setpriority(libc::PRIO_PROCESS, 0, nice) != 0 {
+                return Err(std::io::Error::last_os_error());
+            }
+            Ok(())
+        });
+    }
+    Ok(())
+}
+
+#[cfg(not(unix))]
+fn set_nice(_command: &mut Command, _nice: i32) -> anyhow::Result<()> {
+    Err(anyhow::anyhow!(
+        "Setting the niceness of benchmarks is only supported on Unix"
+    ))
+}
+
+/// Sets the I/O scheduling class of the process spawned by `command`, with the default priority
+/// within the class.
+#[cfg(target_os = "linux")]
+fn set_io_priority(command: &mut Command, class: IoPriorityClass) -> anyhow::Result<()> {
+    use std::os::unix::process::CommandExt;
+
+    const IOPRIO_WHO_PROCESS: libc::c_int = 1;
+    const IOPRIO_CLASS_SHIFT: libc::c_int = 13;
+    let (class, data) = match class {
+        IoPriorityClass::RealTime => (1, 4),
+        IoPriorityClass::BestEffort => (2, 4),
+        IoPriorityClass::Idle => (3, 0),
+    };
+    let priority: libc::c_int = (class << IOPRIO_CLASS_SHIFT) | data;
+
+    // SAFETY: the closure only calls async-signal-safe functions.
+    unsafe {
+        command.pre_exec(move || {
+            if libc::syscall(libc::SYS_ioprio_set, IOPRIO_WHO_PROCESS, 0, priority) != 0 {
+                return Err(std::io::Error::last_os_error());
+            }
+            Ok(())
+        });
+    }
+    Ok(())
+}
+
+#[cfg(not(target_os = "linux"))]
+fn set_io_priority(_command: &mut Command, _class: IoPriorityClass) -> anyhow::Result<()> {
+    Err(anyhow::anyhow!(
+        "Setting the I/O priority of benchmarks is only supported on Linux"
+    ))
+}
+
 /// Records the results (stats) of a benchmark into the database.
 async fn record_stats(
     conn: &dyn Connection,
     artifact_id: ArtifactIdNumber,
     rustc_perf_version: &str,
-    result: BenchmarkResult,
+    run_id: &str,
+    result: &BenchmarkResult,
 ) {
     async fn record<'a>(
         conn: &'a dyn Connection,
@@ -148,13 +828,13 @@ async fn record_stats(
     }
 
     for stat in &result.stats {
-        let collection_id = conn.collection_id(rustc_perf_version).await;
+        let collection_id = conn.collection_id(rustc_perf_version, Some(run_id)).await;
 
         record(
             conn,
             artifact_id,
             collection_id,
-            &result,
+            result,
             stat.instructions,
             "instructions:u",
         )
@@ -163,7 +843,7 @@ async fn record_stats(
             conn,
             artifact_id,
             collection_id,
-            &result,
+            result,
             stat.cycles,
             "cycles:u",
         )
@@ -172,7 +852,7 @@ async fn record_stats(
             conn,
             artifact_id,
             collection_id,
-            &result,
+            result,
             stat.branch_misses,
             "branch-misses",
         )
@@ -181,7 +861,7 @@ async fn record_stats(
             conn,
             artifact_id,
             collection_id,
-            &result,
+            result,
             stat.cache_misses,
             "cache-misses",
         )
@@ -190,14 +870,196 @@ async fn record_stats(
             conn,
             artifact_id,
             collection_id,
-            &result,
+            result,
             Some(stat.wall_time.as_nanos() as u64),
             "wall-time",
         )
         .await;
+        record(
+            conn,
+            artifact_id,
+            collection_id,
+            result,
+            stat.max_rss,
+            "max-rss",
+        )
+        .await;
     }
 }
 
+/// Executes all benchmarks of a single runtime benchmark group that pass the filter, and returns
+/// their results.
+/// If input sizes are configured, the group is executed once per input size.
+///
+/// `original_names` maps the (transformed) benchmark names, to which the filter is applied and
+/// which are reported in the results, to the names used by the binary. If it is empty, the names
+/// are used as they are.
+fn execute_runtime_benchmark_group(
+    binary: &Path,
+    original_names: &HashMap<String, String>,
+    filter: &RuntimeBenchmarkFilter,
+    iterations: u32,
+    opts: &RuntimeRunOpts,
+) -> anyhow::Result<Vec<BenchmarkResult>> {
+    execute_runtime_benchmark_group_runs(binary, original_names, filter, iterations, opts)
+        .map(|(results, _)| results)
+}
+
+/// Executes the benchmarks of a group like `execute_runtime_benchmark_group`, and also returns
+/// how many times was the benchmark process executed to gather each result.
+fn execute_runtime_benchmark_group_runs(
+    binary: &Path,
+    original_names: &HashMap<String, String>,
+    filter: &RuntimeBenchmarkFilter,
+    iterations: u32,
+    opts: &RuntimeRunOpts,
+) -> anyhow::Result<(Vec<BenchmarkResult>, HashMap<String, u32>)> {
+    // The binary only understands its original names, so we select the benchmarks by their full
+    // original names and rename the results back.
+    let mut reported_names = HashMap::new();
+    let binary_filter = if original_names.is_empty() {
+        if !filter.is_prefix() {
+            // The binary only understands prefixes, so the results are filtered below instead.
+            RuntimeBenchmarkFilter::keep_all()
+        } else {
+            filter.clone()
+        }
+    } else {
+        reported_names = original_names
+            .iter()
+            .filter(|(name, _)| filter.matches(name))
+            .map(|(name, original)| (original.clone(), name.clone()))
+            .collect();
+        if reported_names.is_empty() {
+            return Ok((vec![], HashMap::new()));
+        }
+        RuntimeBenchmarkFilter::new(vec![], reported_names.keys().cloned().collect())
+    };
+
+    let input_sizes: Vec<Option<u64>> = if opts.input_sizes.is_empty() {
+        vec![None]
+    } else {
+        opts.input_sizes.iter().copied().map(Some).collect()
+    };
+
+    let metrics_env = opts
+        .metrics
+        .as_ref()
+        .map(|metrics| metrics.to_env(&reported_names));
+
+    let run_once = |input_size: Option<u64>| -> anyhow::Result<Vec<BenchmarkResult>> {
+        let mut results = Vec::new();
+        let messages = execute_runtime_benchmark_binary(
+            binary,
+            &binary_filter,
+            iterations,
+            input_size,
+            metrics_env.as_deref(),
+            opts,
+        )?;
+        for message in messages {
+            let message = message.map_err(|err| {
+                anyhow::anyhow!(
+                    "Cannot parse BenchmarkMessage from benchmark {}: {err:?}",
+                    binary.display()
+                )
+            })?;
+            match message {
+                BenchmarkMessage::Result(mut result) => {
+                    if !original_names.is_empty() {
+                        // Include filters are prefixes, so the binary could have also executed
+                        // benchmarks that were not selected.
+                        let Some(name) = reported_names.get(&result.name) else {
+                            continue;
+                        };
+                        result.name = name.clone();
+                    } else if !filter.matches(&result.name) {
+                        continue;
+                    }
+                    if let Some(ref metrics) = opts.metrics {
+                        metrics.apply(&mut result);
+                    }
+                    if let Some(input_size) = input_size {
+                        result.name = format!("{}[size={input_size}]", result.name);
+                    }
+                    results.push(result);
+                }
+            }
+        }
+        Ok(results)
+    };
+
+    let mut results = Vec::new();
+    let mut run_counts = HashMap::new();
+    for input_size in input_sizes {
+        let mut merged: Vec<BenchmarkResult> = Vec::new();
+        let mut runs = 0;
+        let mut previous_means: Option<HashMap<String, f64>> = None;
+        loop {
+            merge_results(&mut merged, run_once(input_size)?);
+            runs += 1;
+
+            let RunUntil::Converged {
+                rel_tolerance,
+                max_runs,
+            } = opts.run_until
+            else {
+                break;
+            };
+            let means = mean_wall_times(&merged);
+            let converged = previous_means
+                .is_some_and(|previous| means_converged(&previous, &means, rel_tolerance));
+            if converged || runs >= max_runs {
+                break;
+            }
+            previous_means = Some(means);
+        }
+        for result in merged {
+            run_counts.insert(result.name.clone(), runs);
+            results.push(result);
+        }
+    }
+    Ok((results, run_counts))
+}
+
+/// Appends the statistics of results from another execution of a benchmark process to the
+/// results of the same benchmarks.
+fn merge_results(merged: &mut Vec<BenchmarkResult>, results: Vec<BenchmarkResult>) {
+    for result in results {
+        match merged.iter_mut().find(|r| r.name == result.name) {
+            Some(existing) => existing.stats.extend(result.stats),
+            None => merged.push(result),
+        }
+    }
+}
+
+/// Returns the mean wall time (in nanoseconds) of each benchmark.
+fn mean_wall_times(results: &[BenchmarkResult]) -> HashMap<String, f64> {
+    results
+        .iter()
+        .map(|result| {
+            let mean = calculate_mean(result.stats.iter().map(|s| s.wall_time.as_nanos() as f64));
+            (result.name.clone(), mean)
+        })
+        .collect()
+}
+
+/// Returns true if the mean of each benchmark differs from its previous mean by at most
+/// `rel_tolerance`.
+fn means_converged(
+    previous: &HashMap<String, f64>,
+    current: &HashMap<String, f64>,
+    rel_tolerance: f64,
+) -> bool {
+    current
+        .iter()
+        .all(|(name, &mean)| match previous.get(name) {
+            Some(&previous) if previous == 0.0 => mean == 0.0,
+            Some(&previous) => ((mean - previous) / previous).abs() <= rel_tolerance,
+            None => false,
+        })
+}
+
 /// Starts executing a single runtime benchmark group defined in a binary crate located in
 /// `runtime-benchmarks`. The binary is expected to use benchlib's `BenchmarkGroup` to execute
 /// a set of runtime benchmarks and print `BenchmarkMessage`s encoded as JSON, one per line.
@@ -205,9 +1067,42 @@ fn execute_runtime_benchmark_binary(
     binary: &Path,
     filter: &RuntimeBenchmarkFilter,
     iterations: u32,
+    input_size: Option<u64>,
+    metrics: Option<&str>,
+    opts: &RuntimeRunOpts,
 ) -> anyhow::Result<impl Iterator<Item = anyhow::Result<BenchmarkMessage>>> {
-    let mut command = prepare_command(binary);
-    command.arg("run");
+    let mut command = prepare_command(binary, opts.runner.as_deref().unwrap_or_default());
+    if let Some(input_size) = input_size {
+        command.env(INPUT_SIZE_ENV, input_size.to_string());
+    }
+    if let Some(metrics) = metrics {
+        command.env(METRICS_ENV, metrics);
+    }
+    if let Some(seed) = opts.seed {
+        command.env(SEED_ENV, seed.to_string());
+    }
+    if let Some(core) = opts.pin_core {
+        pin_to_core(&mut command, core)?;
+    }
+    if opts.disable_thp {
+        disable_thp(&mut command);
+    }
+    if let Some(nice) = opts.nice {
+        set_nice(&mut command, nice)?;
+    }
+    if let Some(class) = opts.io_priority {
+        set_io_priority(&mut command, class)?;
+    }
+    if let Some(ref cgroup) = opts.cgroup {
+        cgroup::attach_to_cgroup(&mut command, &cgroup.procs_path())?;
+    }
+    if let Some(ref path) = opts.stdin_file {
+        let file = std::fs::File::open(path)
+            .with_context(|| format!("Cannot open stdin file {}", path.display()))?;
+        command.stdin(file);
+    }
+    command.stderr(opts.run_stderr.stdio());
+    command.arg(opts.run_command.as_deref().unwrap_or(DEFAULT_RUN_COMMAND));
     command.arg("--iterations");
     command.arg(iterations.to_string());
 
@@ -218,7 +1113,16 @@ fn execute_runtime_benchmark_binary(
         command.args(["--include", &filter.include.join(",")]);
     }
 
-    let output = run_command_with_output(&mut command)?;
+    // The whole output is gathered before parsing it, so the process is reaped and its pipes are
+    // closed here, and long runs do not accumulate file descriptors of finished groups.
+    let output = match opts.deadline {
+        Some(deadline) => run_command_until(&mut command, deadline)?,
+        // `run_command_with_output` always captures stderr.
+        None if opts.run_stderr != StdioMode::Capture => command
+            .output()
+            .with_context(|| format!("failed to spawn process for cmd: {command:?}"))?,
+        None => run_command_with_output(&mut command)?,
+    };
     if !output.status.success() {
         return Err(anyhow::anyhow!(
             "Process finished with exit code {}\n{}",
@@ -278,5 +1182,1011 @@ fn print_stats(result: &BenchmarkResult) {
     });
     print_metric(result, "Branch misses", |m| m.branch_misses);
     print_metric(result, "Cache misses", |m| m.cache_misses);
+    print_metric(result, "Max RSS [B]", |m| m.max_rss);
     println!();
 }
+
+#[cfg(all(test, target_os = "linux"))]
+mod tests {
+    use std::collections::HashMap;
+    use std::path::{Path, PathBuf};
+    use std::time::Duration;
+
+    use database::tests::sqlite_runtime_run_ids;
+    use database::{ArtifactId, Pool};
+
+    use super::{
+        bench_runtime, execute_runtime_benchmark_group, BenchmarkGroup, BenchmarkSuite, RunReport,
+        RuntimeBenchmarkFilter, RuntimeRunOpts,
+    };
+    use crate::toolchain::{Toolchain, ToolchainComponents};
+    use crate::utils::wait_for_future;
+    use crate::CollectorStepBuilder;
+
+    /// Creates an executable shell script that stands in for a benchmark group binary.
+    pub(super) fn create_script(dir: &Path, name: &str, body: &str) -> PathBuf {
+        use std::os::unix::fs::PermissionsExt;
+
+        let path = dir.join(name);
+        std::fs::write(&path, format!("#!/bin/sh\n{body}\n")).unwrap();
+        std::fs::set_permissions(&path, std::fs::Permissions::from_mode(0o755)).unwrap();
+        path
+    }
+
+    /// Returns a shell command that prints a single benchmark result message, with the given
+    /// (shell-expanded) wall time in nanoseconds.
+    pub(super) fn echo_result(name: &str, wall_time_nanos: &str) -> String {
+        format!(
+            r#"echo "{{\"Result\":{{\"name\":\"{name}\",\"stats\":[{{\"cycles\":null,\"instructions\":null,\"branch_misses\":null,\"cache_misses\":null,\"cache_references\":null,\"wall_time\":{{\"secs\":0,\"nanos\":{wall_time_nanos}}}}}]}}}}""#
+        )
+    }
+
+    /// Returns a Cargo message that reports `binary` as the compiled executable of a benchmark
+    /// crate, for fake `cargo` scripts.
+    pub(super) fn compiler_artifact(binary: &Path) -> serde_json::Value {
+        let name = binary.file_name().unwrap().to_str().unwrap();
+        serde_json::json!({
+            "reason": "compiler-artifact",
+            "package_id": format!("{name} 0.1.0 (path+file:///{name})"),
+            "manifest_path": format!("/{name}/Cargo.toml"),
+            "target": {
+                "kind": ["bin"],
+                "crate_types": ["bin"],
+                "name": name,
+                "src_path": format!("/{name}/src/main.rs"),
+                "edition": "2021",
+                "doctest": false,
+                "test": true,
+                "doc": true
+            },
+            "profile": {
+                "opt_level": "3",
+                "debuginfo": 0,
+                "debug_assertions": false,
+                "overflow_checks": false,
+                "test": false
+            },
+            "features": [],
+            "filenames": [binary],
+            "executable": binary,
+            "fresh": false
+        })
+    }
+
+    #[test]
+    fn test_input_size_sweep() {
+        let dir = tempfile::TempDir::new().unwrap();
+        let binary = create_script(
+            dir.path(),
+            "sized",
+            &echo_result("bench", "$RUSTC_PERF_INPUT_SIZE"),
+        );
+
+        let results = execute_runtime_benchmark_group(
+            &binary,
+            &HashMap::new(),
+            &RuntimeBenchmarkFilter::keep_all(),
+            1,
+            &RuntimeRunOpts::default().input_sizes(vec![10, 200]),
+        )
+        .unwrap();
+        let results: Vec<(&str, u32)> = results
+            .iter()
+            .map(|r| (r.name.as_str(), r.stats[0].wall_time.subsec_nanos()))
+            .collect();
+        assert_eq!(
+            results,
+            vec![("bench[size=10]", 10), ("bench[size=200]", 200)]
+        );
+    }
+
+    #[test]
+    fn test_pin_core() {
+        let dir = tempfile::TempDir::new().unwrap();
+        let affinity = dir.path().join("affinity");
+        let binary = create_script(
+            dir.path(),
+            "pinned",
+            &format!(
+                "grep Cpus_allowed_list /proc/self/status > {}\n{}",
+                affinity.display(),
+                echo_result("bench", "0")
+            ),
+        );
+
+        let results = execute_runtime_benchmark_group(
+            &binary,
+            &HashMap::new(),
+            &RuntimeBenchmarkFilter::keep_all(),
+            1,
+            &RuntimeRunOpts::default().pin_core(0),
+        )
+        .unwrap();
+        assert_eq!(results.len(), 1);
+        assert_eq!(
+            std::fs::read_to_string(&affinity).unwrap().trim(),
+            "Cpus_allowed_list:\t0"
+        );
+
+        assert!(execute_runtime_benchmark_group(
+            &binary,
+            &HashMap::new(),
+            &RuntimeBenchmarkFilter::keep_all(),
+            1,
+            &RuntimeRunOpts::default().pin_core(usize::MAX),
+        )
+        .is_err());
+    }
+
+    #[test]
+    fn test_failing_group_is_quarantined() {
+        let dir = tempfile::TempDir::new().unwrap();
+        let failing = create_script(dir.path(), "failing", "echo crashed >&2\nexit 1");
+        let passing = create_script(dir.path(), "passing", &echo_result("bench", "0"));
+
+        let mut report = RunReport::default();
+        for (group, binary) in [("failing", &failing), ("passing", &passing)] {
+            match execute_runtime_benchmark_group(
+                binary,
+                &HashMap::new(),
+                &RuntimeBenchmarkFilter::keep_all(),
+                1,
+                &RuntimeRunOpts::default(),
+            ) {
+                Ok(results) => {
+                    for result in results {
+                        report.add_success(group, result);
+                    }
+                }
+                Err(error) => report.add_failure(group, vec!["bench".to_string()], &error),
+            }
+        }
+
+        assert!(!report.is_success());
+        assert_eq!(report.successes.len(), 1);
+        assert_eq!(report.successes[0].group, "passing");
+        assert_eq!(report.failures.len(), 1);
+        assert_eq!(report.failures[0].group, "failing");
+        assert_eq!(report.failures[0].benchmarks, vec!["bench".to_string()]);
+    }
+
+    #[test]
+    fn test_memory_budget_exceeded() {
+        let dir = tempfile::TempDir::new().unwrap();
+        let binary = create_script(
+            dir.path(),
+            "group",
+            r#"echo '{"Result":{"name":"bench","stats":[{"cycles":null,"instructions":null,"branch_misses":null,"cache_misses":null,"cache_references":null,"wall_time":{"secs":0,"nanos":1},"max_rss":4096}]}}'"#,
+        );
+        let suite = BenchmarkSuite::from_groups(
+            Toolchain {
+                components: ToolchainComponents::default(),
+                id: "test".to_string(),
+                triple: "x86_64-unknown-linux-gnu".to_string(),
+            },
+            vec![BenchmarkGroup {
+                binary,
+                name: "group".to_string(),
+                benchmark_names: vec!["bench".to_string()],
+                features: vec![],
+                cached: false,
+                binary_size: 0,
+                original_names: Default::default(),
+                tags: vec![],
+                llvm_ir_size: None,
+                protocol_version: None,
+            }],
+        );
+
+        let db = dir.path().join("results.db");
+        let (report, artifact, errors) = wait_for_future(async {
+            let pool = Pool::open(db.to_str().unwrap());
+            let mut conn = pool.connection().await;
+            let collector = CollectorStepBuilder::default()
+                .record_runtime_benchmarks(&suite)
+                .start_collection(conn.as_mut(), &ArtifactId::Tag("test".to_string()))
+                .await;
+            let report = bench_runtime(
+                conn.as_mut(),
+                suite,
+                &collector,
+                RuntimeBenchmarkFilter::keep_all(),
+                1,
+                &RuntimeRunOpts::default().memory_budget(1024),
+            )
+            .await
+            .unwrap();
+            (
+                report,
+                collector.artifact_row_id,
+                conn.get_error(collector.artifact_row_id).await,
+            )
+        });
+        let run_ids = sqlite_runtime_run_ids(&db, artifact);
+        assert!(report.successes.is_empty());
+        assert_eq!(report.failures.len(), 1);
+        // The statistics of the group are stored together with its error.
+        assert_eq!(run_ids.len(), 1, "{run_ids:?}");
+        assert_eq!(errors.len(), 1);
+        let error = errors.values().next().unwrap();
+        assert!(error.contains("exceeded the memory budget"), "{error}");
+    }
+
+    #[test]
+    fn test_custom_run_command() {
+        let dir = tempfile::TempDir::new().unwrap();
+        let binary = create_script(
+            dir.path(),
+            "group",
+            &format!(
+                "if [ \"$1\" != \"execute\" ]; then exit 1; fi\n{}",
+                echo_result("bench", "0")
+            ),
+        );
+
+        let run = |opts: &RuntimeRunOpts| {
+            execute_runtime_benchmark_group(
+                &binary,
+                &HashMap::new(),
+                &RuntimeBenchmarkFilter::keep_all(),
+                1,
+                opts,
+            )
+        };
+        assert_eq!(
+            run(&RuntimeRunOpts::default().run_command("execute"))
+                .unwrap()
+                .len(),
+            1
+        );
+        assert!(run(&RuntimeRunOpts::default()).is_err());
+    }
+
+    #[test]
+    fn test_transformed_names() {
+        let dir = tempfile::TempDir::new().unwrap();
+        // The binary ignores the filter, to check that unselected results are dropped.
+        let binary = create_script(
+            dir.path(),
+            "group",
+            &format!(
+                "{}\n{}",
+                echo_result("bench-insert", "0"),
+                echo_result("bench-insert-many", "0")
+            ),
+        );
+        let original_names: HashMap<String, String> = [
+            ("insert", "bench-insert"),
+            ("insert-many", "bench-insert-many"),
+        ]
+        .into_iter()
+        .map(|(name, original)| (name.to_string(), original.to_string()))
+        .collect();
+
+        let results = execute_runtime_benchmark_group(
+            &binary,
+            &original_names,
+            &RuntimeBenchmarkFilter::new(vec!["insert-many".to_string()], vec![]),
+            1,
+            &RuntimeRunOpts::default(),
+        )
+        .unwrap();
+        let names: Vec<&str> = results.iter().map(|r| r.name.as_str()).collect();
+        assert_eq!(names, ["insert"]);
+    }
+
+    #[test]
+    fn test_seed() {
+        let dir = tempfile::TempDir::new().unwrap();
+        let binary = create_script(
+            dir.path(),
+            "seeded",
+            &echo_result("bench", "${RUSTC_PERF_SEED:-0}"),
+        );
+
+        let run = |opts: &RuntimeRunOpts| {
+            let results = execute_runtime_benchmark_group(
+                &binary,
+                &HashMap::new(),
+                &RuntimeBenchmarkFilter::keep_all(),
+                1,
+                opts,
+            )
+            .unwrap();
+            results[0].stats[0].wall_time.subsec_nanos()
+        };
+        assert_eq!(run(&RuntimeRunOpts::default().seed(42)), 42);
+        assert_eq!(run(&RuntimeRunOpts::default()), 0);
+    }
+
+    #[test]
+    fn test_metric_selection_is_passed_to_binary() {
+        use crate::runtime::metrics::{MetricSelection, RuntimeMetric};
+
+        let dir = tempfile::TempDir::new().unwrap();
+        let env_file = dir.path().join("metrics");
+        let binary = create_script(
+            dir.path(),
+            "metrics",
+            &format!(
+                "printf '%s' \"${{RUSTC_PERF_METRICS-unset}}\" > {}\n{}",
+                env_file.display(),
+                echo_result("bench", "1")
+            ),
+        );
+
+        let run = |opts: &RuntimeRunOpts| {
+            execute_runtime_benchmark_group(
+                &binary,
+                &HashMap::new(),
+                &RuntimeBenchmarkFilter::keep_all(),
+                1,
+                opts,
+            )
+            .unwrap();
+            std::fs::read_to_string(&env_file).unwrap()
+        };
+
+        assert_eq!(run(&RuntimeRunOpts::default()), "unset");
+
+        let selection = MetricSelection::default().select("bench", [RuntimeMetric::Cycles]);
+        let env: HashMap<String, Vec<String>> =
+            serde_json::from_str(&run(&RuntimeRunOpts::default().metrics(selection))).unwrap();
+        assert_eq!(
+            env,
+            HashMap::from([(
+                "bench".to_string(),
+                vec!["wall-time".to_string(), "cycles".to_string()]
+            )])
+        );
+    }
+
+    #[test]
+    fn test_disable_thp() {
+        if !std::fs::read_to_string("/proc/self/status")
+            .unwrap()
+            .contains("THP_enabled:")
+        {
+            eprintln!("Skipping test, the kernel does not report THP_enabled");
+            return;
+        }
+        let dir = tempfile::TempDir::new().unwrap();
+        let binary = create_script(
+            dir.path(),
+            "thp",
+            &echo_result(
+                "bench",
+                "$(awk '/^THP_enabled:/ { print $2 }' /proc/$$/status)",
+            ),
+        );
+
+        let run = |opts: &RuntimeRunOpts| {
+            let results = execute_runtime_benchmark_group(
+                &binary,
+                &HashMap::new(),
+                &RuntimeBenchmarkFilter::keep_all(),
+                1,
+                opts,
+            )
+            .unwrap();
+            results[0].stats[0].wall_time.subsec_nanos()
+        };
+        assert_eq!(run(&RuntimeRunOpts::default().disable_thp(true)), 0);
+    }
+
+    #[test]
+    fn test_nice() {
+        use super::IoPriorityClass;
+
+        let dir = tempfile::TempDir::new().unwrap();
+        // The 19th field of `/proc/<pid>/stat` is the niceness of the process.
+        let binary = create_script(
+            dir.path(),
+            "nice",
+            &echo_result("bench", "$(awk '{ print $19 }' /proc/$$/stat)"),
+        );
+
+        let run = |opts: &RuntimeRunOpts| {
+            let results = execute_runtime_benchmark_group(
+                &binary,
+                &HashMap::new(),
+                &RuntimeBenchmarkFilter::keep_all(),
+                1,
+                opts,
+            )
+            .unwrap();
+            results[0].stats[0].wall_time.subsec_nanos()
+        };
+        // Raising the niceness to the maximum does not need any privileges.
+        assert_eq!(run(&RuntimeRunOpts::default().nice(19)), 19);
+        assert_eq!(
+            run(&RuntimeRunOpts::default()
+                .nice(19)
+                .io_priority(IoPriorityClass::Idle)),
+            19
+        );
+    }
+
+    #[test]
+    fn test_phase_timings() {
+        use crate::runtime::{prepare_runtime_benchmark_suite, CargoIsolationMode, GroupSelection};
+        use crate::runtime::{RuntimeCompilationOpts, DEFAULT_LIST_COMMAND};
+
+        let dir = tempfile::TempDir::new().unwrap();
+        let binary = create_script(
+            dir.path(),
+            "group",
+            &format!(
+                r#"if [ "$1" = "{DEFAULT_LIST_COMMAND}" ]; then echo '["bench"]'; exit 0; fi
+sleep 0.1
+{}"#,
+                echo_result("bench", "1")
+            ),
+        );
+        // Pretends to compile the benchmark crate into `binary`.
+        let artifact = compiler_artifact(&binary);
+        let cargo = create_script(dir.path(), "cargo", &format!("echo '{artifact}'"));
+        let benchmark_dir = dir.path().join("benchmarks");
+        std::fs::create_dir_all(benchmark_dir.join("group")).unwrap();
+        std::fs::write(
+            benchmark_dir.join("group/Cargo.toml"),
+            "[package]\nname = \"group\"\nversion = \"0.1.0\"\n",
+        )
+        .unwrap();
+
+        let toolchain = Toolchain {
+            components: ToolchainComponents {
+                cargo,
+                ..Default::default()
+            },
+            id: "test".to_string(),
+            triple: "x86_64-unknown-linux-gnu".to_string(),
+        };
+        let compilation = prepare_runtime_benchmark_suite(
+            &toolchain,
+            &benchmark_dir,
+            CargoIsolationMode::Cached,
+            GroupSelection::all(),
+            RuntimeCompilationOpts::default(),
+        )
+        .unwrap();
+        assert!(compilation.failed_to_compile.is_empty());
+        let suite = compilation.extract_suite();
+
+        let report = wait_for_future(async {
+            let pool = Pool::open(":memory:");
+            let mut conn = pool.connection().await;
+            let collector = CollectorStepBuilder::default()
+                .record_runtime_benchmarks(&suite)
+                .start_collection(conn.as_mut(), &ArtifactId::Tag("test".to_string()))
+                .await;
+            bench_runtime(
+                conn.as_mut(),
+                suite,
+                &collector,
+                RuntimeBenchmarkFilter::keep_all(),
+                1,
+                &RuntimeRunOpts::default(),
+            )
+            .await
+            .unwrap()
+        });
+        assert_eq!(report.successes.len(), 1);
+
+        let timings = report.phase_timings();
+        assert!(!timings.discovery.is_zero());
+        assert!(!timings.compilation.is_zero());
+        assert!(timings.running >= Duration::from_millis(100));
+        let json = serde_json::to_value(timings).unwrap();
+        for phase in ["discovery", "compilation", "running"] {
+            assert!(json.get(phase).is_some(), "missing phase {phase}");
+        }
+    }
+
+    #[test]
+    fn test_file_descriptors_are_released() {
+        let open_fds = || std::fs::read_dir("/proc/self/fd").unwrap().count();
+
+        let dir = tempfile::TempDir::new().unwrap();
+        let groups = (0..100)
+            .map(|index| {
+                let name = format!("group{index}");
+                let benchmark = format!("{name}-bench");
+                let binary = create_script(dir.path(), &name, &echo_result(&benchmark, "1000"));
+                BenchmarkGroup {
+                    binary,
+                    name,
+                    benchmark_names: vec![benchmark],
+                    features: vec![],
+                    cached: false,
+                    binary_size: 0,
+                    original_names: Default::default(),
+                    tags: vec![],
+                    llvm_ir_size: None,
+                    protocol_version: None,
+                }
+            })
+            .collect();
+        let toolchain = Toolchain {
+            components: ToolchainComponents::default(),
+            id: "test".to_string(),
+            triple: "x86_64-unknown-linux-gnu".to_string(),
+        };
+        let suite = BenchmarkSuite::from_groups(toolchain, groups);
+
+        let (before, report) = wait_for_future(async {
+            let pool = Pool::open(":memory:");
+            let mut conn = pool.connection().await;
+            let collector = CollectorStepBuilder::default()
+                .record_runtime_benchmarks(&suite)
+                .start_collection(conn.as_mut(), &ArtifactId::Tag("test".to_string()))
+                .await;
+            let before = open_fds();
+            let report = bench_runtime(
+                conn.as_mut(),
+                suite,
+                &collector,
+                RuntimeBenchmarkFilter::keep_all(),
+                1,
+                &RuntimeRunOpts::default(),
+            )
+            .await
+            .unwrap();
+            (before, report)
+        });
+        assert_eq!(report.successes.len(), 100);
+
+        // Leaking even a single descriptor per group would add 100 of them, which is more than
+        // the descriptors that other tests running concurrently can keep open.
+        let after = open_fds();
+        assert!(
+            after < before + 100,
+            "{before} fds before the run, {after} after"
+        );
+    }
+
+    #[test]
+    fn test_stream_results() {
+        use std::io::BufRead;
+
+        use super::StreamAddress;
+
+        let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+        let address = listener.local_addr().unwrap().to_string();
+        let receiver = std::thread::spawn(move || {
+            let (connection, _) = listener.accept().unwrap();
+            std::io::BufReader::new(connection)
+                .lines()
+                .map(|line| serde_json::from_str::<serde_json::Value>(&line.unwrap()).unwrap())
+                .collect::<Vec<_>>()
+        });
+
+        let dir = tempfile::TempDir::new().unwrap();
+        let group = |name: &str, benchmarks: &[&str]| BenchmarkGroup {
+            binary: create_script(
+                dir.path(),
+                name,
+                &benchmarks
+                    .iter()
+                    .map(|benchmark| echo_result(benchmark, "1"))
+                    .collect::<Vec<_>>()
+                    .join("\n"),
+            ),
+            name: name.to_string(),
+            benchmark_names: benchmarks.iter().map(|b| b.to_string()).collect(),
+            features: vec![],
+            cached: false,
+            binary_size: 0,
+            original_names: Default::default(),
+            tags: vec![],
+            llvm_ir_size: None,
+            protocol_version: None,
+        };
+        let toolchain = Toolchain {
+            components: ToolchainComponents::default(),
+            id: "test".to_string(),
+            triple: "x86_64-unknown-linux-gnu".to_string(),
+        };
+        let suite = BenchmarkSuite::from_groups(
+            toolchain,
+            vec![
+                group("hashmap", &["insert", "remove"]),
+                group("nbody", &["nbody"]),
+            ],
+        );
+
+        let report = wait_for_future(async {
+            let pool = Pool::open(":memory:");
+            let mut conn = pool.connection().await;
+            let collector = CollectorStepBuilder::default()
+                .record_runtime_benchmarks(&suite)
+                .start_collection(conn.as_mut(), &ArtifactId::Tag("test".to_string()))
+                .await;
+            bench_runtime(
+                conn.as_mut(),
+                suite,
+                &collector,
+                RuntimeBenchmarkFilter::keep_all(),
+                1,
+                &RuntimeRunOpts::default()
+                    .stream_results(address.parse::<StreamAddress>().unwrap()),
+            )
+            .await
+            .unwrap()
+        });
+        assert_eq!(report.successes.len(), 3);
+
+        // The connection is closed when the run finishes.
+        let messages = receiver.join().unwrap();
+        let names: Vec<&str> = messages
+            .iter()
+            .map(|message| message["result"]["name"].as_str().unwrap())
+            .collect();
+        assert_eq!(names, ["insert", "remove", "nbody"]);
+    }
+
+    #[test]
+    fn test_run_id() {
+        use std::io::BufRead;
+
+        use super::{FilenameTemplate, StreamAddress};
+
+        let dir = tempfile::TempDir::new().unwrap();
+        let suite = || {
+            BenchmarkSuite::from_groups(
+                Toolchain {
+                    components: ToolchainComponents::default(),
+                    id: "test".to_string(),
+                    triple: "x86_64-unknown-linux-gnu".to_string(),
+                },
+                vec![BenchmarkGroup {
+                    binary: create_script(dir.path(), "group", &echo_result("bench", "1")),
+                    name: "group".to_string(),
+                    benchmark_names: vec!["bench".to_string()],
+                    features: vec![],
+                    cached: false,
+                    binary_size: 0,
+                    original_names: Default::default(),
+                    tags: vec![],
+                    llvm_ir_size: None,
+                    protocol_version: None,
+                }],
+            )
+        };
+        // Executes the suite, and returns the run ids from the report, the results file, the
+        // result stream and the database.
+        let run = |opts: RuntimeRunOpts| {
+            let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+            let address = listener.local_addr().unwrap().to_string();
+            let receiver = std::thread::spawn(move || {
+                let (connection, _) = listener.accept().unwrap();
+                std::io::BufReader::new(connection)
+                    .lines()
+                    .map(|line| serde_json::from_str::<serde_json::Value>(&line.unwrap()).unwrap())
+                    .collect::<Vec<_>>()
+            });
+            let results_file = dir.path().join("results.json");
+            let opts = opts
+                .stream_results(address.parse::<StreamAddress>().unwrap())
+                .results_file(
+                    results_file
+                        .display()
+                        .to_string()
+                        .parse::<FilenameTemplate>()
+                        .unwrap(),
+                );
+
+            let db_dir = tempfile::TempDir::new().unwrap();
+            let db = db_dir.path().join("results.db");
+            let (report, artifact) = wait_for_future(async {
+                let pool = Pool::open(db.to_str().unwrap());
+                let mut conn = pool.connection().await;
+                let collector = CollectorStepBuilder::default()
+                    .record_runtime_benchmarks(&suite())
+                    .start_collection(conn.as_mut(), &ArtifactId::Tag("test".to_string()))
+                    .await;
+                let report = bench_runtime(
+                    conn.as_mut(),
+                    suite(),
+                    &collector,
+                    RuntimeBenchmarkFilter::keep_all(),
+                    1,
+                    &opts,
+                )
+                .await
+                .unwrap();
+                (report, collector.artifact_row_id)
+            });
+            let db_run_ids = sqlite_runtime_run_ids(&db, artifact);
+            let results: serde_json::Value =
+                serde_json::from_str(&std::fs::read_to_string(&results_file).unwrap()).unwrap();
+            let messages = receiver.join().unwrap();
+            assert_eq!(messages.len(), 1);
+            assert_eq!(messages[0]["result"]["name"], "bench");
+            [
+                report.run_id.unwrap(),
+                results["run_id"].as_str().unwrap().to_string(),
+                messages[0]["run_id"].as_str().unwrap().to_string(),
+                db_run_ids.into_iter().next().unwrap(),
+            ]
+        };
+
+        assert_eq!(
+            run(RuntimeRunOpts::default().run_id("ci-1234")),
+            ["ci-1234"; 4]
+        );
+
+        let generated = run(RuntimeRunOpts::default());
+        assert!(generated.iter().all(|run_id| *run_id == generated[0]));
+        assert!(
+            uuid::Uuid::parse_str(&generated[0]).is_ok(),
+            "{generated:?}"
+        );
+        let regenerated = run(RuntimeRunOpts::default());
+        assert_ne!(generated[0], regenerated[0]);
+    }
+
+    #[test]
+    fn test_runner() {
+        let dir = tempfile::TempDir::new().unwrap();
+        let log = dir.path().join("runner.log");
+        let runner = create_script(
+            dir.path(),
+            "runner",
+            &format!("echo \"$1\" > {}\nexec \"$@\"", log.display()),
+        );
+        let binary = create_script(dir.path(), "group", &echo_result("bench", "1"));
+
+        let results = execute_runtime_benchmark_group(
+            &binary,
+            &HashMap::new(),
+            &RuntimeBenchmarkFilter::keep_all(),
+            1,
+            &RuntimeRunOpts::default().runner(vec![runner.display().to_string()]),
+        )
+        .unwrap();
+        assert_eq!(results.len(), 1);
+        assert_eq!(
+            std::fs::read_to_string(&log).unwrap().trim(),
+            binary.display().to_string()
+        );
+    }
+
+    #[test]
+    fn test_run_until_converged() {
+        use super::{execute_runtime_benchmark_group_runs, RunUntil};
+
+        let dir = tempfile::TempDir::new().unwrap();
+        let stable = create_script(dir.path(), "stable", &echo_result("bench", "1000"));
+        // Each execution reports a quickly growing wall time, so the mean never settles.
+        let counter = dir.path().join("counter");
+        let noisy = create_script(
+            dir.path(),
+            "noisy",
+            &format!(
+                "echo >> {counter}\nn=$(wc -l < {counter})\n{}",
+                echo_result("bench", "$((n * n * 1000))"),
+                counter = counter.display()
+            ),
+        );
+
+        let run = |binary: &Path| {
+            let (results, runs) = execute_runtime_benchmark_group_runs(
+                binary,
+                &HashMap::new(),
+                &RuntimeBenchmarkFilter::keep_all(),
+                1,
+                &RuntimeRunOpts::default().run_until(RunUntil::Converged {
+                    rel_tolerance: 0.01,
+                    max_runs: 5,
+                }),
+            )
+            .unwrap();
+            assert_eq!(results.len(), 1);
+            // The statistics of all executions are merged.
+            assert_eq!(results[0].stats.len() as u32, runs["bench"]);
+            runs["bench"]
+        };
+        assert_eq!(run(&stable), 2);
+        assert_eq!(run(&noisy), 5);
+
+        let (_, runs) = execute_runtime_benchmark_group_runs(
+            &stable,
+            &HashMap::new(),
+            &RuntimeBenchmarkFilter::keep_all(),
+            1,
+            &RuntimeRunOpts::default(),
+        )
+        .unwrap();
+        assert_eq!(runs["bench"], 1);
+    }
+
+    #[test]
+    fn test_run_stderr() {
+        use super::StdioMode;
+
+        let dir = tempfile::TempDir::new().unwrap();
+        let binary = create_script(dir.path(), "failing", "echo 'assertion failed' >&2\nexit 1");
+        let error = |mode| {
+            execute_runtime_benchmark_group(
+                &binary,
+                &HashMap::new(),
+                &RuntimeBenchmarkFilter::keep_all(),
+                1,
+                &RuntimeRunOpts::default().run_stderr(mode),
+            )
+            .unwrap_err()
+            .to_string()
+        };
+        assert!(error(StdioMode::Capture).contains("assertion failed"));
+        for mode in [StdioMode::Inherit, StdioMode::Null] {
+            let error = error(mode);
+            assert!(error.contains("exit code 1"), "{error}");
+            assert!(!error.contains("assertion failed"), "{error}");
+        }
+
+        // The mode is also respected when the process is killed at a deadline.
+        let error = execute_runtime_benchmark_group(
+            &binary,
+            &HashMap::new(),
+            &RuntimeBenchmarkFilter::keep_all(),
+            1,
+            &RuntimeRunOpts {
+                deadline: Some(std::time::Instant::now() + Duration::from_secs(60)),
+                ..RuntimeRunOpts::default().run_stderr(StdioMode::Null)
+            },
+        )
+        .unwrap_err()
+        .to_string();
+        assert!(!error.contains("assertion failed"), "{error}");
+    }
+
+    #[test]
+    fn test_stdin_file() {
+        let dir = tempfile::TempDir::new().unwrap();
+        let input = dir.path().join("input");
+        std::fs::write(&input, "7\n").unwrap();
+        let override_input = dir.path().join("override");
+        std::fs::write(&override_input, "9\n").unwrap();
+
+        // Reports the value read from stdin as its wall time.
+        let groups = ["a", "b"]
+            .into_iter()
+            .map(|name| BenchmarkGroup {
+                binary: create_script(
+                    dir.path(),
+                    name,
+                    &format!("read value\n{}", echo_result(name, "$value")),
+                ),
+                name: name.to_string(),
+                benchmark_names: vec![name.to_string()],
+                features: vec![],
+                cached: false,
+                binary_size: 0,
+                original_names: Default::default(),
+                tags: vec![],
+                llvm_ir_size: None,
+                protocol_version: None,
+            })
+            .collect();
+        let toolchain = Toolchain {
+            components: ToolchainComponents::default(),
+            id: "test".to_string(),
+            triple: "x86_64-unknown-linux-gnu".to_string(),
+        };
+        let suite = BenchmarkSuite::from_groups(toolchain, groups);
+
+        let report = wait_for_future(async {
+            let pool = Pool::open(":memory:");
+            let mut conn = pool.connection().await;
+            let collector = CollectorStepBuilder::default()
+                .record_runtime_benchmarks(&suite)
+                .start_collection(conn.as_mut(), &ArtifactId::Tag("test".to_string()))
+                .await;
+            bench_runtime(
+                conn.as_mut(),
+                suite,
+                &collector,
+                RuntimeBenchmarkFilter::keep_all(),
+                1,
+                &RuntimeRunOpts::default()
+                    .stdin_file(input)
+                    .group_stdin_file("b", override_input),
+            )
+            .await
+            .unwrap()
+        });
+        let values: Vec<(&str, u32)> = report
+            .successes
+            .iter()
+            .map(|success| {
+                (
+                    success.group.as_str(),
+                    success.result.stats[0].wall_time.subsec_nanos(),
+                )
+            })
+            .collect();
+        assert_eq!(values, [("a", 7), ("b", 9)]);
+
+        let error = execute_runtime_benchmark_group(
+            &dir.path().join("a"),
+            &HashMap::new(),
+            &RuntimeBenchmarkFilter::keep_all(),
+            1,
+            &RuntimeRunOpts::default().stdin_file(dir.path().join("missing")),
+        )
+        .unwrap_err()
+        .to_string();
+        assert!(error.contains("Cannot open stdin file"), "{error}");
+    }
+
+    #[test]
+    fn test_total_timeout() {
+        let dir = tempfile::TempDir::new().unwrap();
+        let groups = (0..4)
+            .map(|index| {
+                let name = format!("slow{index}");
+                let benchmark = format!("{name}-bench");
+                let binary = create_script(
+                    dir.path(),
+                    &name,
+                    &format!("sleep 1\n{}", echo_result(&benchmark, "0")),
+                );
+                BenchmarkGroup {
+                    binary,
+                    name,
+                    benchmark_names: vec![benchmark],
+                    features: vec![],
+                    cached: false,
+                    binary_size: 0,
+                    original_names: Default::default(),
+                    tags: vec![],
+                    llvm_ir_size: None,
+                    protocol_version: None,
+                }
+            })
+            .collect();
+        let toolchain = Toolchain {
+            components: ToolchainComponents::default(),
+            id: "test".to_string(),
+            triple: "x86_64-unknown-linux-gnu".to_string(),
+        };
+        let suite = BenchmarkSuite::from_groups(toolchain, groups);
+
+        let report = wait_for_future(async {
+            let pool = Pool::open(":memory:");
+            let mut conn = pool.connection().await;
+            let collector = CollectorStepBuilder::default()
+                .record_runtime_benchmarks(&suite)
+                .start_collection(conn.as_mut(), &ArtifactId::Tag("test".to_string()))
+                .await;
+            bench_runtime(
+                conn.as_mut(),
+                suite,
+                &collector,
+                RuntimeBenchmarkFilter::keep_all(),
+                1,
+                &RuntimeRunOpts::default().total_timeout(Duration::from_millis(2500)),
+            )
+            .await
+            .unwrap()
+        });
+
+        // Each group takes about a second, so only some of them have finished.
+        assert!(report.timed_out);
+        assert!(!report.successes.is_empty());
+        assert!(report.successes.len() < 4);
+        assert!(report.failures.is_empty());
+
+        // The timeout has struck while the next group was executing.
+        let interrupted = report.interrupted.as_ref().unwrap();
+        let index = report.successes.len();
+        assert_eq!(interrupted.group, format!("slow{index}"));
+        assert_eq!(interrupted.benchmarks, [format!("slow{index}-bench")]);
+        assert!(
+            interrupted.error.contains("total timeout"),
+            "{}",
+            interrupted.error
+        );
+    }
+}
@@ -0,0 +1,231 @@
+use std::fmt;
+use std::path::{Path, PathBuf};
+
+use crate::runtime::benchmark::{discover_groups, find_sources_without_manifest};
+use crate::runtime::{CargoIsolationMode, GroupSelection, RuntimeCompilationOpts};
+
+/// A problem with the layout of a runtime benchmark crate, found without compiling it.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PreflightIssue {
+    /// Directory of the offending benchmark crate.
+    pub path: PathBuf,
+    pub kind: PreflightIssueKind,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum PreflightIssueKind {
+    /// The benchmark groups could not be discovered, e.g. because the benchmark directory could
+    /// not be read, or because two groups have the same name.
+    Discovery(String),
+    /// The directory contains sources, but no `Cargo.toml`.
+    MissingCargoToml,
+    /// `Cargo.toml` exists, but it cannot be parsed.
+    InvalidCargoToml(String),
+    /// The crate does not declare any binary target.
+    MissingBinary,
+    /// `Cargo.lock` is required when compiling in isolated mode.
+    MissingCargoLock,
+}
+
+impl fmt::Display for PreflightIssue {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let path = self.path.display();
+        match &self.kind {
+            PreflightIssueKind::Discovery(error) => {
+                write!(f, "groups in `{path}` cannot be discovered: {error}")
+            }
+            PreflightIssueKind::MissingCargoToml => {
+                write!(f, "`{path}` contains sources, but no Cargo.toml")
+            }
+            PreflightIssueKind::InvalidCargoToml(error) => {
+                write!(f, "`{path}` has an invalid Cargo.toml: {error}")
+            }
+            PreflightIssueKind::MissingBinary => {
+                write!(f, "`{path}` does not declare a binary target")
+            }
+            PreflightIssueKind::MissingCargoLock => write!(
+                f,
+                "`{path}` does not contain Cargo.lock, which is required in isolated mode"
+            ),
+        }
+    }
+}
+
+/// Checks that every runtime benchmark crate in `dir` looks like a valid benchmark group, without
+/// compiling anything.
+///
+/// The crates are found the same way as during compilation with `opts`, including the nested
+/// crates with `recursive_discovery`. Directories without a `Cargo.toml` and without a `src`
+/// directory (e.g. shared `data`) are ignored. With `workspace`, a single `Cargo.lock` of the
+/// workspace in `dir` is required instead of one in each crate.
+pub fn preflight(
+    dir: &Path,
+    isolation: &CargoIsolationMode,
+    opts: &RuntimeCompilationOpts,
+) -> Vec<PreflightIssue> {
+    let discovered = discover_groups(dir, GroupSelection::all(), opts.recursive_discovery)
+        .and_then(|groups| {
+            let without_manifest = find_sources_without_manifest(dir, opts.recursive_discovery)?;
+            Ok((groups, without_manifest))
+        });
+    let (groups, without_manifest) = match discovered {
+        Ok(discovered) => discovered,
+        Err(error) => {
+            return vec![PreflightIssue {
+                path: dir.to_path_buf(),
+                kind: PreflightIssueKind::Discovery(format!("{:#}", anyhow::Error::from(error))),
+            }];
+        }
+    };
+
+    let mut issues = Vec::new();
+    let require_lock = matches!(isolation, CargoIsolationMode::Isolated);
+    if require_lock && opts.workspace && !dir.join("Cargo.lock").is_file() {
+        issues.push(PreflightIssue {
+            path: dir.to_path_buf(),
+            kind: PreflightIssueKind::MissingCargoLock,
+        });
+    }
+
+    let mut paths: Vec<PathBuf> = groups
+        .into_iter()
+        .map(|group| group.path)
+        .chain(without_manifest)
+        .collect();
+    paths.sort();
+
+    for path in paths {
+        let mut report = |kind| {
+            issues.push(PreflightIssue {
+                path: path.clone(),
+                kind,
+            })
+        };
+
+        let manifest_path = path.join("Cargo.toml");
+        if !manifest_path.is_file() {
+            report(PreflightIssueKind::MissingCargoToml);
+            continue;
+        }
+
+        let manifest = std::fs::read_to_string(&manifest_path)
+            .map_err(|error| error.to_string())
+            .and_then(|contents| {
+                contents
+                    .parse::<toml::Table>()
+                    .map_err(|error| error.to_string())
+            });
+        match manifest {
+            Ok(manifest) => {
+                if !declares_binary(&path, &manifest) {
+                    report(PreflightIssueKind::MissingBinary);
+                }
+            }
+            Err(error) => report(PreflightIssueKind::InvalidCargoToml(error)),
+        }
+
+        if require_lock && !opts.workspace && !path.join("Cargo.lock").is_file() {
+            report(PreflightIssueKind::MissingCargoLock);
+        }
+    }
+    issues
+}
+
+/// Returns true if the crate has either an explicit `[[bin]]` target or an auto-discovered one.
+fn declares_binary(path: &Path, manifest: &toml::Table) -> bool {
+    let explicit = manifest
+        .get("bin")
+        .and_then(|bins| bins.as_array())
+        .is_some_and(|bins| !bins.is_empty());
+    explicit || path.join("src/main.rs").is_file() || path.join("src/bin").is_dir()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{preflight, PreflightIssue, PreflightIssueKind};
+    use crate::runtime::{CargoIsolationMode, RuntimeCompilationOpts};
+
+    #[test]
+    fn test_preflight_reports_malformed_crate() {
+        let dir = tempfile::TempDir::new().unwrap();
+        let root = dir.path();
+
+        let valid = root.join("valid");
+        std::fs::create_dir_all(valid.join("src")).unwrap();
+        std::fs::write(
+            valid.join("Cargo.toml"),
+            "[package]\nname = \"valid-bench\"\nversion = \"0.1.0\"\n",
+        )
+        .unwrap();
+        std::fs::write(valid.join("Cargo.lock"), "").unwrap();
+        std::fs::write(valid.join("src/main.rs"), "fn main() {}").unwrap();
+
+        let malformed = root.join("malformed");
+        std::fs::create_dir_all(malformed.join("src")).unwrap();
+        std::fs::write(
+            malformed.join("Cargo.toml"),
+            "[package]\nname = \"malformed-bench\"\nversion = \"0.1.0\"\n",
+        )
+        .unwrap();
+        std::fs::write(malformed.join("src/lib.rs"), "").unwrap();
+
+        // Shared data directories are not benchmark crates.
+        std::fs::create_dir_all(root.join("data")).unwrap();
+
+        let opts = RuntimeCompilationOpts::default();
+        assert_eq!(
+            preflight(root, &CargoIsolationMode::Isolated, &opts),
+            vec![
+                PreflightIssue {
+                    path: malformed.clone(),
+                    kind: PreflightIssueKind::MissingBinary
+                },
+                PreflightIssue {
+                    path: malformed.clone(),
+                    kind: PreflightIssueKind::MissingCargoLock
+                },
+            ]
+        );
+        assert_eq!(
+            preflight(root, &CargoIsolationMode::Cached, &opts),
+            vec![PreflightIssue {
+                path: malformed.clone(),
+                kind: PreflightIssueKind::MissingBinary
+            }]
+        );
+
+        // Nested crates are only checked with recursive discovery.
+        let nested = root.join("numeric/fft");
+        std::fs::create_dir_all(nested.join("src")).unwrap();
+        let opts = RuntimeCompilationOpts::default().recursive_discovery(true);
+        assert_eq!(
+            preflight(root, &CargoIsolationMode::Cached, &opts),
+            vec![
+                PreflightIssue {
+                    path: malformed.clone(),
+                    kind: PreflightIssueKind::MissingBinary
+                },
+                PreflightIssue {
+                    path: nested,
+                    kind: PreflightIssueKind::MissingCargoToml
+                },
+            ]
+        );
+
+        // A workspace needs a single `Cargo.lock` in its root.
+        let opts = RuntimeCompilationOpts::default().workspace(true);
+        assert_eq!(
+            preflight(root, &CargoIsolationMode::Isolated, &opts),
+            vec![
+                PreflightIssue {
+                    path: root.to_path_buf(),
+                    kind: PreflightIssueKind::MissingCargoLock
+                },
+                PreflightIssue {
+                    path: malformed,
+                    kind: PreflightIssueKind::MissingBinary
+                },
+            ]
+        );
+    }
+}
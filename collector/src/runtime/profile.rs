@@ -1,3 +1,4 @@
+use std::collections::HashMap;
 use std::fs::create_dir_all;
 use std::path::{Path, PathBuf};
 use std::process::{Command, Stdio};
@@ -8,10 +9,28 @@ use crate::command_output;
 use crate::runtime::BenchmarkSuite;
 use crate::toolchain::Toolchain;
 use crate::utils::cachegrind::cachegrind_annotate;
+use crate::utils::is_installed;
 
 #[derive(Clone, Debug, clap::ValueEnum)]
 pub enum RuntimeProfiler {
     Cachegrind,
+    /// Records the benchmark with `perf record` and stores its stacks in the folded format, which
+    /// can be rendered e.g. with `inferno-flamegraph`. Only available on Linux.
+    Flamegraph,
+    /// Measures heap allocations of the benchmark with Valgrind's DHAT and stores the DHAT
+    /// profile, which can be viewed with `dh_view.html`.
+    Dhat,
+}
+
+/// Heap usage of a benchmark, as measured by DHAT.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct DhatStats {
+    /// Total number of bytes allocated during the whole execution.
+    pub total_bytes: u64,
+    /// Total number of allocated heap blocks.
+    pub total_blocks: u64,
+    /// Maximum size of the heap, in bytes.
+    pub max_bytes: u64,
 }
 
 /// Profiles a single runtime benchmark and returns a path to the result.
@@ -54,7 +73,254 @@ pub fn profile_runtime(
                 .context("Cannot annotate result")?;
             cgout_file
         }
+        RuntimeProfiler::Flamegraph => {
+            if !cfg!(target_os = "linux") {
+                return Err(anyhow::anyhow!(
+                    "The flamegraph profiler is only available on Linux"
+                ));
+            }
+            if !is_installed("perf") {
+                return Err(anyhow::anyhow!("`perf` does not seem to be installed"));
+            }
+
+            let perf_tmp = tempfile::NamedTempFile::new()?.into_temp_path();
+            let folded_file = result_dir.join(format!("perf-folded-{}-{benchmark}", toolchain.id));
+
+            let mut cmd = perf_record_command(&group.binary, benchmark, &perf_tmp);
+            command_output(&mut cmd).context("Cannot run profiler")?;
+
+            let mut cmd = Command::new("perf");
+            cmd.arg("script").arg("--input").arg(&perf_tmp);
+            let output = command_output(&mut cmd).context("Cannot run perf script")?;
+            let folded = collapse_perf_script(&String::from_utf8_lossy(&output.stdout));
+            std::fs::write(&folded_file, folded).with_context(|| {
+                format!("Cannot write folded stacks to {}", folded_file.display())
+            })?;
+            folded_file
+        }
+        RuntimeProfiler::Dhat => {
+            let dhat_file = result_dir.join(format!("dhat-{}-{benchmark}.json", toolchain.id));
+            let stats = measure_dhat(&group.binary, benchmark, &dhat_file)?;
+            println!(
+                "`{benchmark}` allocated {} bytes in {} blocks, maximum heap size was {} bytes",
+                stats.total_bytes, stats.total_blocks, stats.max_bytes
+            );
+            dhat_file
+        }
     };
 
     Ok(out_file)
 }
+
+/// Prepares a command that records call stacks of a single benchmark execution into `output`.
+fn perf_record_command(binary: &Path, benchmark: &str, output: &Path) -> Command {
+    let mut cmd = Command::new("perf");
+    cmd.arg("record")
+        .arg("--call-graph=dwarf")
+        .arg("--output")
+        .arg(output)
+        .arg(binary)
+        .arg("profile")
+        .arg(benchmark)
+        .stdin(Stdio::null());
+    cmd
+}
+
+/// Executes a single benchmark under DHAT, stores its profile into `output` and returns a summary
+/// of its heap usage.
+pub fn measure_dhat(binary: &Path, benchmark: &str, output: &Path) -> anyhow::Result<DhatStats> {
+    if !is_installed("valgrind") {
+        return Err(anyhow::anyhow!("`valgrind` does not seem to be installed"));
+    }
+
+    let mut cmd = Command::new("valgrind");
+    cmd.arg("--tool=dhat")
+        .arg(format!("--dhat-out-file={}", output.display()))
+        .arg(binary)
+        .arg("profile")
+        .arg(benchmark)
+        .stdin(Stdio::null());
+    let output = command_output(&mut cmd).context("Cannot run DHAT")?;
+    parse_dhat_summary(&String::from_utf8_lossy(&output.stderr))
+}
+
+/// Parses the summary that DHAT prints to stderr at the end of the execution:
+/// ```text
+/// ==1234== Total:     1,024 bytes in 2 blocks
+/// ==1234== At t-gmax: 512 bytes in 1 blocks
+/// ```
+fn parse_dhat_summary(stderr: &str) -> anyhow::Result<DhatStats> {
+    fn parse_line(stderr: &str, label: &str) -> anyhow::Result<(u64, u64)> {
+        let line = stderr
+            .lines()
+            .find_map(|line| line.split_once(label).map(|(_, rest)| rest))
+            .ok_or_else(|| anyhow::anyhow!("Cannot find `{label}` in DHAT output"))?;
+        let parse = |value: &str| -> anyhow::Result<u64> {
+            value
+                .trim()
+                .replace(',', "")
+                .parse()
+                .with_context(|| format!("Cannot parse `{label}` in DHAT output"))
+        };
+        let (bytes, blocks) = line
+            .split_once("bytes in")
+            .ok_or_else(|| anyhow::anyhow!("Unexpected format of `{label}` in DHAT output"))?;
+        Ok((
+            parse(bytes)?,
+            parse(blocks.trim().trim_end_matches("blocks"))?,
+        ))
+    }
+
+    let (total_bytes, total_blocks) = parse_line(stderr, "Total:")?;
+    let (max_bytes, _) = parse_line(stderr, "At t-gmax:")?;
+    Ok(DhatStats {
+        total_bytes,
+        total_blocks,
+        max_bytes,
+    })
+}
+
+/// Converts the output of `perf script` into folded stacks (`root;...;leaf <count>`), one line
+/// per unique stack, sorted by the stack.
+fn collapse_perf_script(script: &str) -> String {
+    let mut stacks: HashMap<String, u64> = HashMap::new();
+    let mut frames: Vec<&str> = vec![];
+
+    let mut finish_sample = |frames: &mut Vec<&str>| {
+        if !frames.is_empty() {
+            frames.reverse();
+            *stacks.entry(frames.join(";")).or_default() += 1;
+            frames.clear();
+        }
+    };
+
+    for line in script.lines() {
+        if line.trim().is_empty() {
+            finish_sample(&mut frames);
+        } else if line.starts_with(char::is_whitespace) {
+            // Stack frame line: `<address> <symbol>+<offset> (<module>)`
+            let line = line.trim();
+            let symbol = line
+                .split_once(char::is_whitespace)
+                .map(|(_, rest)| rest)
+                .unwrap_or("[unknown]");
+            let symbol = match symbol.rfind(" (") {
+                Some(index) => &symbol[..index],
+                None => symbol,
+            };
+            let symbol = match symbol.rfind("+0x") {
+                Some(index) => &symbol[..index],
+                None => symbol,
+            };
+            frames.push(symbol);
+        }
+        // Lines without indentation are sample headers, which we ignore.
+    }
+    finish_sample(&mut frames);
+
+    let mut stacks: Vec<(String, u64)> = stacks.into_iter().collect();
+    stacks.sort();
+    stacks
+        .into_iter()
+        .map(|(stack, count)| format!("{stack} {count}\n"))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::Path;
+
+    use super::{collapse_perf_script, parse_dhat_summary, perf_record_command, DhatStats};
+
+    #[test]
+    fn test_perf_record_command() {
+        let cmd = perf_record_command(Path::new("bench"), "foo", Path::new("/tmp/out.data"));
+        assert_eq!(cmd.get_program(), "perf");
+        let args: Vec<_> = cmd.get_args().collect();
+        assert_eq!(
+            args,
+            [
+                "record",
+                "--call-graph=dwarf",
+                "--output",
+                "/tmp/out.data",
+                "bench",
+                "profile",
+                "foo"
+            ]
+        );
+    }
+
+    #[test]
+    fn test_collapse_perf_script() {
+        let script = "bench 1234 1.0: 100 cycles:u:
+\t    55d4c0e1a2b3 leaf+0x1f (/bench)
+\t    55d4c0e1a2c4 main+0x2 (/bench)
+
+bench 1234 2.0: 100 cycles:u:
+\t    55d4c0e1a2b3 leaf+0x10 (/bench)
+\t    55d4c0e1a2c4 main+0x2 (/bench)
+
+bench 1234 3.0: 100 cycles:u:
+\t    55d4c0e1a2c4 main+0x5 (/bench)
+";
+        assert_eq!(collapse_perf_script(script), "main 1\nmain;leaf 2\n");
+    }
+
+    #[test]
+    fn test_parse_dhat_summary() {
+        let stderr = "==1234== DHAT, a dynamic heap analysis tool
+==1234== Total:     1,049,600 bytes in 3 blocks
+==1234== At t-gmax: 1,048,576 bytes in 1 blocks
+==1234== At t-end:  0 bytes in 0 blocks
+==1234== Reads:     192 bytes
+";
+        assert_eq!(
+            parse_dhat_summary(stderr).unwrap(),
+            DhatStats {
+                total_bytes: 1049600,
+                total_blocks: 3,
+                max_bytes: 1048576,
+            }
+        );
+        assert!(parse_dhat_summary("==1234== Reads: 192 bytes").is_err());
+    }
+
+    #[cfg(target_os = "linux")]
+    #[test]
+    fn test_measure_dhat() {
+        if !crate::utils::is_installed("valgrind") {
+            eprintln!("Skipping test, `valgrind` is not installed");
+            return;
+        }
+        let dir = tempfile::TempDir::new().unwrap();
+        // The fixture allocates 1 MiB and then fails, if it did not receive the benchmark name.
+        let binary = crate::runtime::tests::create_script(
+            dir.path(),
+            "alloc",
+            r#"[ "$1 $2" = "profile foo" ] && x=$(head -c 1048576 /dev/zero | tr '\0' 'a') && echo ${#x}"#,
+        );
+        let output = dir.path().join("dhat.json");
+        let stats = super::measure_dhat(&binary, "foo", &output).unwrap();
+        assert!(stats.total_bytes >= 1048576);
+        assert!(stats.max_bytes >= 1048576);
+        assert!(stats.total_blocks > 0);
+        assert!(output.is_file());
+    }
+
+    #[cfg(target_os = "linux")]
+    #[test]
+    fn test_perf_record_output() {
+        if !crate::utils::is_installed("perf") {
+            eprintln!("Skipping test, `perf` is not installed");
+            return;
+        }
+        let dir = tempfile::TempDir::new().unwrap();
+        let output = dir.path().join("perf.data");
+        let mut cmd = perf_record_command(Path::new("true"), "foo", &output);
+        // `perf record` can be forbidden by `perf_event_paranoid`.
+        if cmd.status().map(|status| status.success()).unwrap_or(false) {
+            assert!(output.is_file());
+        }
+    }
+}
@@ -0,0 +1,710 @@
+use std::collections::BTreeMap;
+use std::io::Write;
+use std::process::Command;
+use std::time::Duration;
+
+use anyhow::Context;
+
+use benchlib::comm::messages::BenchmarkResult;
+
+use crate::compile::execute::Stats;
+use crate::runtime::qualified_name;
+
+/// Result of a single runtime benchmark, together with the name of the group that contains it.
+#[derive(Debug, serde::Serialize)]
+pub struct RuntimeBenchmarkResult {
+    pub group: String,
+    pub result: BenchmarkResult,
+    /// Set if the measurement of the benchmark is likely to be unstable, e.g. because it was
+    /// executed for too short a time.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub warning: Option<String>,
+    /// Number of times the benchmark process was executed to gather the result (see
+    /// `RunUntil::Converged`).
+    #[serde(skip_serializing_if = "is_single_run")]
+    pub runs: u32,
+}
+
+fn is_single_run(runs: &u32) -> bool {
+    *runs == 1
+}
+
+/// If the wall time of a benchmark summed over all its iterations is lower than this, the results
+/// are probably dominated by noise.
+pub const MIN_TOTAL_MEASURED_TIME: Duration = Duration::from_millis(1);
+
+/// A runtime benchmark group that could not be executed.
+#[derive(Debug, serde::Serialize)]
+pub struct RuntimeBenchmarkFailure {
+    pub group: String,
+    /// Benchmarks of the group that were selected to be executed, but which have not produced
+    /// any result.
+    pub benchmarks: Vec<String>,
+    pub error: String,
+}
+
+/// Outcome of executing a runtime benchmark suite.
+/// A failure of a single benchmark group does not abort the whole run. It is recorded in
+/// `failures` instead, similarly to how compilation errors are gathered in `failed_to_compile`.
+#[derive(Debug, Default, serde::Serialize)]
+pub struct RunReport {
+    /// Identifier of the run that produced the report (see `RuntimeRunOpts::run_id`).
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub run_id: Option<String>,
+    pub successes: Vec<RuntimeBenchmarkResult>,
+    pub failures: Vec<RuntimeBenchmarkFailure>,
+    /// True if the run was stopped early because it exceeded its total timeout.
+    pub timed_out: bool,
+    /// Benchmark group that was terminated because the run exceeded its total timeout, together
+    /// with its benchmarks that were skipped because of that. Unlike `failures`, the group is not
+    /// recorded as failed in the database.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub interrupted: Option<RuntimeBenchmarkFailure>,
+    pub(super) phase_timings: PhaseTimings,
+}
+
+/// Time spent by the collector in the individual phases of a runtime benchmark run.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, serde::Serialize)]
+pub struct PhaseTimings {
+    /// Finding the benchmark crates.
+    pub discovery: Duration,
+    /// Compiling the benchmark crates and listing their benchmarks.
+    pub compilation: Duration,
+    /// Executing the benchmarks.
+    pub running: Duration,
+}
+
+impl RunReport {
+    pub fn add_success(&mut self, group: &str, result: BenchmarkResult) {
+        self.add_repeated_success(group, result, 1);
+    }
+
+    /// Records a result gathered from `runs` executions of the benchmark process.
+    pub fn add_repeated_success(&mut self, group: &str, result: BenchmarkResult, runs: u32) {
+        let warning = low_measured_time_warning(&result);
+        self.successes.push(RuntimeBenchmarkResult {
+            group: group.to_string(),
+            result,
+            warning,
+            runs,
+        });
+    }
+
+    pub fn add_failure(&mut self, group: &str, benchmarks: Vec<String>, error: &anyhow::Error) {
+        self.failures.push(RuntimeBenchmarkFailure {
+            group: group.to_string(),
+            benchmarks,
+            error: format!("{error:?}"),
+        });
+    }
+
+    /// Records that `group` was terminated because the run has exceeded its total timeout.
+    pub fn add_interrupted(&mut self, group: &str, benchmarks: Vec<String>, error: &anyhow::Error) {
+        self.timed_out = true;
+        self.interrupted = Some(RuntimeBenchmarkFailure {
+            group: group.to_string(),
+            benchmarks,
+            error: format!("{error:?}"),
+        });
+    }
+
+    pub fn is_success(&self) -> bool {
+        self.failures.is_empty()
+    }
+
+    /// Returns how long did the discovery and the compilation of the executed suite take, and
+    /// how long did its execution take.
+    pub fn phase_timings(&self) -> PhaseTimings {
+        self.phase_timings
+    }
+
+    /// Returns a warning for each set of (at least two) benchmarks that have reported exactly the
+    /// same statistics and checksum. Real measurements practically never match exactly, so this
+    /// usually means that a benchmark is an accidental copy of another one, or that it does not
+    /// do any work.
+    pub fn identical_results_warnings(&self) -> Vec<String> {
+        let mut identical: Vec<(String, Vec<String>)> = vec![];
+        for success in &self.successes {
+            let result = &success.result;
+            let key = serde_json::to_string(&(&result.stats, result.checksum))
+                .expect("Cannot serialize benchmark stats");
+            let name = qualified_name(&success.group, &result.name);
+            match identical.iter_mut().find(|(k, _)| *k == key) {
+                Some((_, names)) => names.push(name),
+                None => identical.push((key, vec![name])),
+            }
+        }
+        identical
+            .into_iter()
+            .filter(|(_, names)| names.len() > 1)
+            .map(|(_, names)| {
+                format!(
+                    "Benchmarks {} have reported identical results, one of them might be a copy \
+of another or a no-op",
+                    names
+                        .iter()
+                        .map(|name| format!("`{name}`"))
+                        .collect::<Vec<_>>()
+                        .join(", ")
+                )
+            })
+            .collect()
+    }
+}
+
+/// Returns a warning if the total measured time of the benchmark is below
+/// `MIN_TOTAL_MEASURED_TIME`.
+fn low_measured_time_warning(result: &BenchmarkResult) -> Option<String> {
+    let total: Duration = result.stats.iter().map(|stats| stats.wall_time).sum();
+    (total < MIN_TOTAL_MEASURED_TIME).then(|| {
+        format!(
+            "Benchmark `{}` was only measured for {total:?} in {} iteration(s), consider \
+increasing the iteration count (to at least {MIN_TOTAL_MEASURED_TIME:?} in total) to get stable results",
+            result.name,
+            result.stats.len()
+        )
+    })
+}
+
+/// Statistics gathered by a single compile-time benchmark execution.
+#[derive(Debug, Clone, PartialEq, serde::Serialize)]
+pub struct CompileBenchmarkResult {
+    pub name: String,
+    pub profile: String,
+    pub scenario: String,
+    /// Maps metric names (e.g. `instructions:u`) to their values.
+    pub stats: BTreeMap<String, f64>,
+}
+
+impl CompileBenchmarkResult {
+    pub fn new(name: &str, profile: &str, scenario: &str, stats: &Stats) -> Self {
+        Self {
+            name: name.to_string(),
+            profile: profile.to_string(),
+            scenario: scenario.to_string(),
+            stats: stats
+                .iter()
+                .map(|(stat, value)| (stat.to_string(), value))
+                .collect(),
+        }
+    }
+}
+
+/// Results of both compile-time and runtime benchmarks, so that they can be rendered together.
+/// Both kinds of results are identified by their `name`.
+#[derive(Debug, Default, serde::Serialize)]
+pub struct CombinedReport {
+    pub compile: Vec<CompileBenchmarkResult>,
+    pub runtime: Vec<BenchmarkResult>,
+}
+
+impl CombinedReport {
+    /// Creates a combined report from compile-time results and the successful results of a
+    /// runtime benchmark run.
+    pub fn new(compile: Vec<CompileBenchmarkResult>, runtime: RunReport) -> Self {
+        Self {
+            compile,
+            runtime: runtime
+                .successes
+                .into_iter()
+                .map(|success| success.result)
+                .collect(),
+        }
+    }
+}
+
+/// Writes the combined report as JSON.
+pub fn write_combined_report<W: Write>(report: &CombinedReport, mut out: W) -> anyhow::Result<()> {
+    serde_json::to_writer_pretty(&mut out, report)?;
+    writeln!(out)?;
+    Ok(())
+}
+
+/// Writes the report in the JUnit XML format, so that it can be displayed by CI systems.
+/// Every benchmark is represented by a single `<testcase>`, grouped into a `<testsuite>` per
+/// benchmark group. All selected benchmarks of a failed group are marked with `<failure>`, and
+/// the benchmarks skipped by a group that was terminated by the total timeout with `<error>`.
+pub fn write_junit<W: Write>(report: &RunReport, mut out: W) -> anyhow::Result<()> {
+    /// Why did a test case not succeed.
+    #[derive(Clone, Copy, PartialEq, Eq)]
+    enum Problem {
+        Failure,
+        Error,
+    }
+
+    impl Problem {
+        fn tag(self) -> &'static str {
+            match self {
+                Problem::Failure => "failure",
+                Problem::Error => "error",
+            }
+        }
+    }
+
+    struct TestCase<'a> {
+        name: &'a str,
+        time: f64,
+        problem: Option<(Problem, &'a str)>,
+    }
+
+    let mut suites: BTreeMap<&str, Vec<TestCase>> = BTreeMap::new();
+    for success in &report.successes {
+        let time = success
+            .result
+            .stats
+            .iter()
+            .map(|stats| stats.wall_time.as_secs_f64())
+            .sum::<f64>()
+            / success.result.stats.len().max(1) as f64;
+        suites.entry(&success.group).or_default().push(TestCase {
+            name: &success.result.name,
+            time,
+            problem: None,
+        });
+    }
+    let failures = report
+        .failures
+        .iter()
+        .map(|failure| (Problem::Failure, failure))
+        .chain(
+            report
+                .interrupted
+                .iter()
+                .map(|interrupted| (Problem::Error, interrupted)),
+        );
+    for (problem, failure) in failures {
+        let cases = suites.entry(&failure.group).or_default();
+        // If we do not know which benchmarks were affected, report the group itself.
+        let names: Vec<&str> = if failure.benchmarks.is_empty() {
+            vec![&failure.group]
+        } else {
+            failure
+                .benchmarks
+                .iter()
+                .map(|name| name.as_str())
+                .collect()
+        };
+        cases.extend(names.into_iter().map(|name| TestCase {
+            name,
+            time: 0.0,
+            problem: Some((problem, &failure.error)),
+        }));
+    }
+
+    let count = |cases: &[TestCase], problem: Problem| {
+        cases
+            .iter()
+            .filter(|case| matches!(case.problem, Some((p, _)) if p == problem))
+            .count()
+    };
+    let tests: usize = suites.values().map(|cases| cases.len()).sum();
+    let failures: usize = suites
+        .values()
+        .map(|cases| count(cases, Problem::Failure))
+        .sum();
+    let errors: usize = suites
+        .values()
+        .map(|cases| count(cases, Problem::Error))
+        .sum();
+
+    writeln!(out, r#"<?xml version="1.0" encoding="UTF-8"?>"#)?;
+    writeln!(
+        out,
+        r#"<testsuites name="runtime-benchmarks" tests="{tests}" failures="{failures}" errors="{errors}">"#
+    )?;
+    for (group, cases) in suites {
+        let group = escape_xml(group);
+        let failures = count(&cases, Problem::Failure);
+        let errors = count(&cases, Problem::Error);
+        writeln!(
+            out,
+            r#"  <testsuite name="{group}" tests="{}" failures="{failures}" errors="{errors}">"#,
+            cases.len()
+        )?;
+        for case in cases {
+            let name = escape_xml(case.name);
+            let attributes = format!(
+                r#"classname="{group}" name="{name}" time="{:.6}""#,
+                case.time
+            );
+            match case.problem {
+                Some((problem, error)) => {
+                    let message = escape_xml(error.lines().next().unwrap_or_default());
+                    let tag = problem.tag();
+                    writeln!(out, "    <testcase {attributes}>")?;
+                    writeln!(
+                        out,
+                        r#"      <{tag} message="{message}">{}</{tag}>"#,
+                        escape_xml(error)
+                    )?;
+                    writeln!(out, "    </testcase>")?;
+                }
+                None => writeln!(out, "    <testcase {attributes}/>")?,
+            }
+        }
+        writeln!(out, "  </testsuite>")?;
+    }
+    writeln!(out, "</testsuites>")?;
+    Ok(())
+}
+
+/// Writes the report as JSON into a temporary file and executes the `validator` command with the
+/// path to the file appended to its arguments. Returns an error if the validator does not exit
+/// successfully.
+pub fn validate_results(report: &RunReport, validator: &[String]) -> anyhow::Result<()> {
+    let Some((program, args)) = validator.split_first() else {
+        return Err(anyhow::anyhow!("The result validator command is empty"));
+    };
+
+    let mut results = tempfile::Builder::new()
+        .prefix("runtime-results")
+        .suffix(".json")
+        .tempfile()
+        .context("Cannot create results file for the validator")?;
+    serde_json::to_writer(&mut results, report)?;
+    results.flush()?;
+
+    let status = Command::new(program)
+        .args(args)
+        .arg(results.path())
+        .status()
+        .with_context(|| format!("Cannot execute result validator `{program}`"))?;
+    if !status.success() {
+        return Err(anyhow::anyhow!(
+            "Result validator `{}` rejected the results (exit code {})",
+            validator.join(" "),
+            status.code().unwrap_or(-1)
+        ));
+    }
+    Ok(())
+}
+
+/// Writes the mean wall time of each benchmark in the Prometheus text exposition format, e.g.
+/// `rustc_perf_runtime_benchmark_ns{group="hashmap",benchmark="insert"} 1500`.
+pub fn write_prometheus<W: Write>(
+    results: &[RuntimeBenchmarkResult],
+    mut out: W,
+) -> anyhow::Result<()> {
+    const METRIC: &str = "rustc_perf_runtime_benchmark_ns";
+
+    writeln!(
+        out,
+        "# HELP {METRIC} Mean wall time of a runtime benchmark in nanoseconds."
+    )?;
+    writeln!(out, "# TYPE {METRIC} gauge")?;
+    for result in results {
+        let stats = &result.result.stats;
+        if stats.is_empty() {
+            continue;
+        }
+        let mean = stats
+            .iter()
+            .map(|stats| stats.wall_time.as_nanos() as f64)
+            .sum::<f64>()
+            / stats.len() as f64;
+        writeln!(
+            out,
+            r#"{METRIC}{{group="{}",benchmark="{}"}} {mean}"#,
+            escape_prometheus_label(&result.group),
+            escape_prometheus_label(&result.result.name)
+        )?;
+    }
+    Ok(())
+}
+
+fn escape_prometheus_label(value: &str) -> String {
+    let mut escaped = String::with_capacity(value.len());
+    for c in value.chars() {
+        match c {
+            '\\' => escaped.push_str("\\\\"),
+            '"' => escaped.push_str("\\\""),
+            '\n' => escaped.push_str("\\n"),
+            c => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+fn escape_xml(value: &str) -> String {
+    let mut escaped = String::with_capacity(value.len());
+    for c in value.chars() {
+        match c {
+            '&' => escaped.push_str("&amp;"),
+            '<' => escaped.push_str("&lt;"),
+            '>' => escaped.push_str("&gt;"),
+            '"' => escaped.push_str("&quot;"),
+            '\'' => escaped.push_str("&apos;"),
+            c => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use benchlib::comm::messages::{BenchmarkResult, BenchmarkStats};
+    use quick_xml::events::Event;
+    use quick_xml::Reader;
+
+    use super::{
+        validate_results, write_combined_report, write_junit, write_prometheus, CombinedReport,
+        CompileBenchmarkResult, RunReport,
+    };
+    use crate::compile::execute::Stats;
+
+    fn result(name: &str) -> BenchmarkResult {
+        BenchmarkResult {
+            name: name.to_string(),
+            stats: vec![BenchmarkStats {
+                cycles: None,
+                instructions: None,
+                branch_misses: None,
+                cache_misses: None,
+                cache_references: None,
+                wall_time: Duration::from_millis(500),
+                max_rss: None,
+            }],
+            checksum: None,
+        }
+    }
+
+    #[test]
+    fn test_identical_results_warnings() {
+        let mut report = RunReport::default();
+        report.add_success("hashmap", result("insert"));
+        report.add_success("hashmap", result("insert-copy"));
+        let mut slower = result("remove");
+        slower.stats[0].wall_time = Duration::from_millis(600);
+        report.add_success("hashmap", slower);
+        // The same timings do not matter if the benchmarks have computed different results.
+        let mut checksummed = result("lookup");
+        checksummed.checksum = Some(1);
+        report.add_success("hashmap", checksummed);
+
+        let warnings = report.identical_results_warnings();
+        assert_eq!(warnings.len(), 1);
+        assert!(
+            warnings[0].starts_with("Benchmarks `hashmap/insert`, `hashmap/insert-copy` have"),
+            "{}",
+            warnings[0]
+        );
+    }
+
+    #[test]
+    fn test_write_junit() {
+        let mut report = RunReport::default();
+        report.add_success("hashmap", result("insert"));
+        report.add_success("hashmap", result("remove"));
+        report.add_failure(
+            "nbody",
+            vec!["nbody-10k".to_string()],
+            &anyhow::anyhow!("Process finished with exit code 1 <crash>"),
+        );
+        report.add_interrupted(
+            "sort",
+            vec!["sort-large".to_string(), "sort-small".to_string()],
+            &anyhow::anyhow!("The total timeout of the run was exceeded"),
+        );
+        assert!(report.timed_out);
+
+        let mut out = Vec::new();
+        write_junit(&report, &mut out).unwrap();
+        let xml = String::from_utf8(out).unwrap();
+
+        // (classname, name, failure or error)
+        let mut cases: Vec<(String, String, Option<&str>)> = vec![];
+        let mut reader = Reader::from_str(&xml);
+        reader.trim_text(true);
+        loop {
+            match reader.read_event().unwrap() {
+                Event::Start(e) | Event::Empty(e) if e.name().as_ref() == b"testcase" => {
+                    let attr = |name: &str| {
+                        e.try_get_attribute(name)
+                            .unwrap()
+                            .unwrap()
+                            .unescape_value()
+                            .unwrap()
+                            .to_string()
+                    };
+                    cases.push((attr("classname"), attr("name"), None));
+                }
+                Event::Start(e) if e.name().as_ref() == b"failure" => {
+                    cases.last_mut().unwrap().2 = Some("failure");
+                }
+                Event::Start(e) if e.name().as_ref() == b"error" => {
+                    cases.last_mut().unwrap().2 = Some("error");
+                }
+                Event::Eof => break,
+                _ => {}
+            }
+        }
+
+        assert_eq!(
+            cases,
+            vec![
+                ("hashmap".to_string(), "insert".to_string(), None),
+                ("hashmap".to_string(), "remove".to_string(), None),
+                (
+                    "nbody".to_string(),
+                    "nbody-10k".to_string(),
+                    Some("failure")
+                ),
+                ("sort".to_string(), "sort-large".to_string(), Some("error")),
+                ("sort".to_string(), "sort-small".to_string(), Some("error")),
+            ]
+        );
+        assert!(xml.contains(r#"tests="5" failures="1" errors="2""#));
+        assert!(xml.contains(r#"<testsuite name="sort" tests="2" failures="0" errors="2">"#));
+        assert!(xml.contains("exit code 1 &lt;crash&gt;"));
+        assert!(xml.contains(r#"<error message="The total timeout of the run was exceeded">"#));
+    }
+
+    #[test]
+    fn test_write_combined_report() {
+        let mut stats = Stats::new();
+        stats.insert("instructions:u".to_string(), 1000.0);
+        let compile = vec![CompileBenchmarkResult::new(
+            "serde", "Check", "full", &stats,
+        )];
+        let mut runtime = RunReport::default();
+        runtime.add_success("hashmap", result("insert"));
+        runtime.add_failure("nbody", vec![], &anyhow::anyhow!("crashed"));
+
+        let report = CombinedReport::new(compile, runtime);
+        let mut out = Vec::new();
+        write_combined_report(&report, &mut out).unwrap();
+        let json: serde_json::Value = serde_json::from_slice(&out).unwrap();
+
+        assert_eq!(
+            json["compile"],
+            serde_json::json!([{
+                "name": "serde",
+                "profile": "Check",
+                "scenario": "full",
+                "stats": {"instructions:u": 1000.0},
+            }])
+        );
+        let runtime = json["runtime"].as_array().unwrap();
+        assert_eq!(runtime.len(), 1);
+        assert_eq!(runtime[0]["name"], "insert");
+        assert_eq!(runtime[0]["stats"][0]["wall_time"]["nanos"], 500_000_000);
+    }
+
+    #[test]
+    fn test_low_measured_time_warning() {
+        let mut report = RunReport::default();
+        report.add_success("hashmap", result("insert"));
+        let mut fast = result("len");
+        fast.stats[0].wall_time = Duration::from_nanos(20);
+        report.add_success("hashmap", fast);
+
+        assert_eq!(report.successes[0].warning, None);
+        let warning = report.successes[1].warning.as_deref().unwrap();
+        assert!(warning.contains("`len`"), "{warning}");
+        assert!(warning.contains("1 iteration(s)"), "{warning}");
+    }
+
+    /// Parses a sample line of the Prometheus text format into the metric name, labels and value.
+    fn parse_prometheus_sample(line: &str) -> (String, Vec<(String, String)>, f64) {
+        let (name, rest) = line.split_once('{').unwrap();
+        assert!(name
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || c == '_' || c == ':'));
+
+        let mut labels = vec![];
+        let mut chars = rest.chars();
+        loop {
+            let label: String = chars.by_ref().take_while(|&c| c != '=').collect();
+            assert_eq!(chars.next(), Some('"'));
+            let mut value = String::new();
+            loop {
+                match chars.next().unwrap() {
+                    '\\' => match chars.next().unwrap() {
+                        'n' => value.push('\n'),
+                        c @ ('\\' | '"') => value.push(c),
+                        c => panic!("Invalid escape `\\{c}`"),
+                    },
+                    '"' => break,
+                    c => value.push(c),
+                }
+            }
+            labels.push((label, value));
+            match chars.next().unwrap() {
+                ',' => continue,
+                '}' => break,
+                c => panic!("Unexpected `{c}`"),
+            }
+        }
+        let value = chars.as_str().strip_prefix(' ').unwrap().parse().unwrap();
+        (name.to_string(), labels, value)
+    }
+
+    #[test]
+    fn test_write_prometheus() {
+        let mut report = RunReport::default();
+        report.add_success("hashmap", result("insert"));
+        report.add_success("weird \"group\"", result("back\\slash\nline"));
+
+        let mut out = Vec::new();
+        write_prometheus(&report.successes, &mut out).unwrap();
+        let output = String::from_utf8(out).unwrap();
+        let mut lines = output.lines();
+        assert!(lines
+            .next()
+            .unwrap()
+            .starts_with("# HELP rustc_perf_runtime_benchmark_ns "));
+        assert_eq!(
+            lines.next().unwrap(),
+            "# TYPE rustc_perf_runtime_benchmark_ns gauge"
+        );
+
+        let samples: Vec<_> = lines.map(parse_prometheus_sample).collect();
+        let label = |group: &str, benchmark: &str| {
+            vec![
+                ("group".to_string(), group.to_string()),
+                ("benchmark".to_string(), benchmark.to_string()),
+            ]
+        };
+        assert_eq!(
+            samples,
+            vec![
+                (
+                    "rustc_perf_runtime_benchmark_ns".to_string(),
+                    label("hashmap", "insert"),
+                    500_000_000.0
+                ),
+                (
+                    "rustc_perf_runtime_benchmark_ns".to_string(),
+                    label("weird \"group\"", "back\\slash\nline"),
+                    500_000_000.0
+                ),
+            ]
+        );
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_validate_results() {
+        // Rejects results that contain a benchmark marked as a regression. The path to the
+        // results is passed as `$1`.
+        let validator: Vec<String> = ["sh", "-c", r#"! grep -q REGRESSION "$1""#, "validator"]
+            .iter()
+            .map(|arg| arg.to_string())
+            .collect();
+
+        let mut report = RunReport::default();
+        report.add_success("hashmap", result("insert"));
+        validate_results(&report, &validator).unwrap();
+
+        report.add_success("hashmap", result("remove-REGRESSION"));
+        let error = validate_results(&report, &validator).unwrap_err();
+        assert!(
+            error.to_string().contains("rejected the results"),
+            "{error}"
+        );
+
+        assert!(validate_results(&report, &[]).is_err());
+    }
+}
@@ -0,0 +1,181 @@
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+
+use crate::runtime::{BenchmarkSuite, RunConfig, RunMeta};
+use crate::toolchain::Toolchain;
+use crate::utils::git::get_rustc_perf_commit;
+
+/// Prefixes of environment variables that can affect the compilation or the execution of the
+/// runtime benchmarks (e.g. `RUSTFLAGS`, `CARGO_PROFILE_RELEASE_LTO` or `RUSTC_PERF_SEED`).
+const RECORDED_ENV_PREFIXES: &[&str] = &["RUST", "CARGO_"];
+
+/// Everything that is needed to reproduce a runtime benchmark run later.
+#[derive(Debug, serde::Serialize)]
+pub struct ReproManifest<'a> {
+    pub toolchain: ToolchainIdentity,
+    pub benchmark_dir: BenchmarkDirIdentity,
+    /// Machine and collector on which the run was executed.
+    pub environment: RunMeta,
+    /// Resolved configuration of the run.
+    pub config: &'a RunConfig,
+    /// Environment variables of the collector that can affect the results.
+    pub env_vars: BTreeMap<String, String>,
+    /// Qualified names of the benchmarks in the suite, in the canonical order.
+    pub benchmarks: Vec<String>,
+}
+
+#[derive(Debug, serde::Serialize)]
+pub struct ToolchainIdentity {
+    pub id: String,
+    pub triple: String,
+    /// Output of `rustc -vV`, if it could be executed.
+    pub rustc_version: Option<String>,
+}
+
+#[derive(Debug, serde::Serialize)]
+pub struct BenchmarkDirIdentity {
+    pub path: PathBuf,
+    /// Git commit checked out in the benchmark directory, if it is a part of a repository.
+    pub commit: Option<String>,
+}
+
+/// Gathers the inputs of a run with the given configuration over the given (compiled) suite.
+pub fn reproducibility_manifest<'a>(
+    config: &'a RunConfig,
+    toolchain: &Toolchain,
+    benchmark_dir: &Path,
+    suite: &BenchmarkSuite,
+) -> ReproManifest<'a> {
+    ReproManifest {
+        toolchain: ToolchainIdentity {
+            id: toolchain.id.clone(),
+            triple: toolchain.triple.clone(),
+            rustc_version: command_stdout(Command::new(&toolchain.components.rustc).arg("-vV")),
+        },
+        benchmark_dir: BenchmarkDirIdentity {
+            path: benchmark_dir.to_path_buf(),
+            commit: command_stdout(
+                Command::new("git")
+                    .arg("rev-parse")
+                    .arg("HEAD")
+                    .current_dir(benchmark_dir),
+            ),
+        },
+        environment: RunMeta::gather(
+            &toolchain.id,
+            get_rustc_perf_commit().trim(),
+            config.opts.seed,
+        ),
+        config,
+        env_vars: recorded_env_vars(std::env::vars()),
+        benchmarks: suite.qualified_names().collect(),
+    }
+}
+
+fn recorded_env_vars(vars: impl Iterator<Item = (String, String)>) -> BTreeMap<String, String> {
+    vars.filter(|(name, _)| {
+        RECORDED_ENV_PREFIXES
+            .iter()
+            .any(|prefix| name.starts_with(prefix))
+    })
+    .collect()
+}
+
+/// Returns the trimmed stdout of the command, if it has finished successfully.
+fn command_stdout(command: &mut Command) -> Option<String> {
+    let output = command.output().ok()?;
+    if !output.status.success() {
+        return None;
+    }
+    let stdout = String::from_utf8(output.stdout).ok()?;
+    Some(stdout.trim().to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::runtime::{
+        BenchmarkGroup, BenchmarkGroupFilter, BenchmarkSuite, CargoIsolationMode, RunConfig,
+        RuntimeBenchmarkFilter, RuntimeRunOpts, DEFAULT_RUNTIME_ITERATIONS,
+    };
+    use crate::toolchain::{Toolchain, ToolchainComponents};
+
+    use super::{recorded_env_vars, reproducibility_manifest};
+
+    #[test]
+    fn test_reproducibility_manifest() {
+        let toolchain = Toolchain {
+            components: ToolchainComponents {
+                rustc: "/nonexistent/rustc".into(),
+                ..Default::default()
+            },
+            id: "nightly".to_string(),
+            triple: "x86_64-unknown-linux-gnu".to_string(),
+        };
+        let config = RunConfig {
+            toolchain: toolchain.id.clone(),
+            group: None,
+            isolation_mode: CargoIsolationMode::Isolated,
+            group_filter: BenchmarkGroupFilter::keep_all(),
+            filter: RuntimeBenchmarkFilter::keep_all(),
+            tags: None,
+            sample: None,
+            iterations: DEFAULT_RUNTIME_ITERATIONS,
+            opts: RuntimeRunOpts::default().seed(42),
+        };
+        let suite = BenchmarkSuite::from_groups(
+            toolchain.clone(),
+            vec![BenchmarkGroup {
+                binary: "hashmap".into(),
+                name: "hashmap".to_string(),
+                benchmark_names: vec!["remove".to_string(), "insert".to_string()],
+                features: vec![],
+                cached: false,
+                binary_size: 0,
+                original_names: Default::default(),
+                tags: vec![],
+                llvm_ir_size: None,
+                protocol_version: None,
+            }],
+        );
+        let dir = tempfile::TempDir::new().unwrap();
+
+        let manifest = reproducibility_manifest(&config, &toolchain, dir.path(), &suite);
+        let json = serde_json::to_value(&manifest).unwrap();
+        for section in [
+            "toolchain",
+            "benchmark_dir",
+            "environment",
+            "config",
+            "env_vars",
+            "benchmarks",
+        ] {
+            assert!(json.get(section).is_some(), "missing section {section}");
+        }
+        assert_eq!(json["toolchain"]["id"], "nightly");
+        assert_eq!(json["toolchain"]["rustc_version"], serde_json::Value::Null);
+        assert_eq!(json["benchmark_dir"]["commit"], serde_json::Value::Null);
+        assert_eq!(json["environment"]["seed"], 42);
+        assert_eq!(json["config"]["isolation_mode"], "isolated");
+        assert_eq!(
+            json["benchmarks"],
+            serde_json::json!(["hashmap/insert", "hashmap/remove"])
+        );
+    }
+
+    #[test]
+    fn test_recorded_env_vars() {
+        let vars = [
+            ("RUSTFLAGS", "-Ctarget-cpu=native"),
+            ("CARGO_PROFILE_RELEASE_LTO", "fat"),
+            ("RUSTC_PERF_SEED", "1"),
+            ("HOME", "/root"),
+        ]
+        .map(|(name, value)| (name.to_string(), value.to_string()));
+        let recorded = recorded_env_vars(vars.into_iter());
+        assert_eq!(
+            recorded.keys().collect::<Vec<_>>(),
+            ["CARGO_PROFILE_RELEASE_LTO", "RUSTC_PERF_SEED", "RUSTFLAGS"]
+        );
+    }
+}
@@ -0,0 +1,220 @@
+use std::collections::HashMap;
+use std::hash::{DefaultHasher, Hash, Hasher};
+use std::path::{Path, PathBuf};
+
+use anyhow::Context;
+
+use crate::runtime::BenchmarkGroup;
+
+/// Remembers which runtime benchmark groups have already been compiled, so that an interrupted
+/// compilation can be resumed without recompiling them.
+///
+/// The state is persisted to a JSON file after each compiled group. A group is only reused if
+/// its fingerprint has not changed and its binary still exists, so this is mostly useful in
+/// the cached mode, where the binaries outlive the collector process.
+pub struct CompilationResume {
+    path: PathBuf,
+    state: ResumeState,
+}
+
+#[derive(Default, serde::Serialize, serde::Deserialize)]
+struct ResumeState {
+    /// Maps group names to their compiled artifacts.
+    groups: HashMap<String, CompiledGroup>,
+}
+
+#[derive(Clone, serde::Serialize, serde::Deserialize)]
+struct CompiledGroup {
+    fingerprint: u64,
+    binary: PathBuf,
+    benchmark_names: Vec<String>,
+    features: Vec<String>,
+    binary_size: u64,
+    #[serde(default)]
+    original_names: HashMap<String, String>,
+    #[serde(default)]
+    llvm_ir_size: Option<u64>,
+    #[serde(default)]
+    protocol_version: Option<u32>,
+}
+
+impl CompilationResume {
+    /// Loads the resume state from `path`. If the file does not exist, the state is empty.
+    pub fn open(path: &Path) -> anyhow::Result<Self> {
+        let state = if path.is_file() {
+            let contents = std::fs::read(path)
+                .with_context(|| format!("Cannot read resume file {}", path.display()))?;
+            serde_json::from_slice(&contents)
+                .with_context(|| format!("Cannot parse resume file {}", path.display()))?
+        } else {
+            ResumeState::default()
+        };
+        Ok(Self {
+            path: path.to_path_buf(),
+            state,
+        })
+    }
+
+    /// Returns the previously compiled group with the given name, if it is still up to date.
+    pub fn compiled_group(&self, name: &str, fingerprint: u64) -> Option<BenchmarkGroup> {
+        let compiled = self.state.groups.get(name)?;
+        if compiled.fingerprint != fingerprint || !compiled.binary.is_file() {
+            return None;
+        }
+        Some(BenchmarkGroup {
+            binary: compiled.binary.clone(),
+            name: name.to_string(),
+            benchmark_names: compiled.benchmark_names.clone(),
+            features: compiled.features.clone(),
+            cached: true,
+            binary_size: compiled.binary_size,
+            original_names: compiled.original_names.clone(),
+            // Tags are read from the manifest of the crate, not from the resume state.
+            tags: vec![],
+            llvm_ir_size: compiled.llvm_ir_size,
+            protocol_version: compiled.protocol_version,
+        })
+    }
+
+    /// Records a successfully compiled group and persists the state.
+    pub fn record(&mut self, group: &BenchmarkGroup, fingerprint: u64) -> anyhow::Result<()> {
+        self.state.groups.insert(
+            group.name.clone(),
+            CompiledGroup {
+                fingerprint,
+                binary: group.binary.clone(),
+                benchmark_names: group.benchmark_names.clone(),
+                features: group.features.clone(),
+                binary_size: group.binary_size,
+                original_names: group.original_names.clone(),
+                llvm_ir_size: group.llvm_ir_size,
+                protocol_version: group.protocol_version,
+            },
+        );
+
+        // Write to a temporary file first, so that an interruption cannot corrupt the state.
+        let tmp_path = self.path.with_extension("tmp");
+        std::fs::write(&tmp_path, serde_json::to_vec_pretty(&self.state)?)
+            .with_context(|| format!("Cannot write resume file {}", tmp_path.display()))?;
+        std::fs::rename(&tmp_path, &self.path)
+            .with_context(|| format!("Cannot write resume file {}", self.path.display()))?;
+        Ok(())
+    }
+}
+
+/// Computes a fingerprint of a runtime benchmark crate from the contents of its files (except for
+/// the `target` directory) and from additional `inputs` that affect the compilation (e.g. the
+/// toolchain).
+///
+/// The fingerprint is only stable for the same build of the collector.
+pub fn fingerprint_crate(path: &Path, inputs: &[&str]) -> anyhow::Result<u64> {
+    let mut hasher = DefaultHasher::new();
+    inputs.hash(&mut hasher);
+
+    let mut files = vec![];
+    for entry in walkdir::WalkDir::new(path)
+        .into_iter()
+        .filter_entry(|entry| entry.depth() != 1 || entry.file_name() != "target")
+    {
+        let entry = entry?;
+        if entry.file_type().is_file() {
+            files.push(entry.into_path());
+        }
+    }
+    files.sort();
+
+    for file in files {
+        file.strip_prefix(path)?.hash(&mut hasher);
+        std::fs::read(&file)
+            .with_context(|| format!("Cannot read {}", file.display()))?
+            .hash(&mut hasher);
+    }
+    Ok(hasher.finish())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::Path;
+
+    use super::{fingerprint_crate, CompilationResume};
+    use crate::runtime::BenchmarkGroup;
+
+    fn create_crate(dir: &Path, name: &str) {
+        let path = dir.join(name);
+        std::fs::create_dir_all(path.join("src")).unwrap();
+        std::fs::write(
+            path.join("Cargo.toml"),
+            format!("[package]\nname = \"{name}\"\n"),
+        )
+        .unwrap();
+        std::fs::write(path.join("src/main.rs"), "fn main() {}").unwrap();
+    }
+
+    /// Pretends to compile the group, creating its binary.
+    fn compile(dir: &Path, name: &str) -> BenchmarkGroup {
+        let binary = dir.join(name).join("target/release").join(name);
+        std::fs::create_dir_all(binary.parent().unwrap()).unwrap();
+        std::fs::write(&binary, "binary").unwrap();
+        BenchmarkGroup {
+            binary,
+            name: name.to_string(),
+            benchmark_names: vec![format!("{name}-bench")],
+            features: vec![],
+            cached: false,
+            binary_size: 6,
+            original_names: Default::default(),
+            tags: vec![],
+            llvm_ir_size: None,
+            protocol_version: None,
+        }
+    }
+
+    #[test]
+    fn test_resume_compilation() {
+        let dir = tempfile::TempDir::new().unwrap();
+        let resume_file = dir.path().join("resume.json");
+        let names = ["a", "b", "c", "d"];
+        for name in names {
+            create_crate(dir.path(), name);
+        }
+        let fingerprint = |name: &str| fingerprint_crate(&dir.path().join(name), &["nightly"]);
+
+        // The first run is interrupted after compiling two groups.
+        let mut resume = CompilationResume::open(&resume_file).unwrap();
+        for &name in &names[..2] {
+            let group = compile(dir.path(), name);
+            resume.record(&group, fingerprint(name).unwrap()).unwrap();
+        }
+        drop(resume);
+
+        // Compiling a group must not change its fingerprint.
+        let resume = CompilationResume::open(&resume_file).unwrap();
+        let to_compile: Vec<&str> = names
+            .into_iter()
+            .filter(|&name| {
+                resume
+                    .compiled_group(name, fingerprint(name).unwrap())
+                    .is_none()
+            })
+            .collect();
+        assert_eq!(to_compile, ["c", "d"]);
+
+        let group = resume
+            .compiled_group("a", fingerprint("a").unwrap())
+            .unwrap();
+        assert_eq!(group.benchmark_names, ["a-bench"]);
+        assert!(group.cached);
+
+        // Changing the sources of a group invalidates it.
+        std::fs::write(dir.path().join("a/src/main.rs"), "fn main() { }").unwrap();
+        assert!(resume
+            .compiled_group("a", fingerprint("a").unwrap())
+            .is_none());
+        assert!(resume
+            .compiled_group(
+                "b",
+                fingerprint_crate(&dir.path().join("b"), &["beta"]).unwrap()
+            )
+            .is_none());
+    }
+}
@@ -0,0 +1,126 @@
+use std::collections::{BTreeMap, BTreeSet};
+
+use benchlib::comm::messages::BenchmarkResult;
+
+/// Merges the results of several shards of a run (e.g. executed on different machines) into a
+/// single result set, sorted by the benchmark name.
+///
+/// Fails if a benchmark has results in more than one shard (or more than once in a single
+/// shard). If `expected` is given, it also fails if any of the expected benchmarks is missing
+/// from all shards, or if a shard contains a benchmark that is not expected. All problems are
+/// reported together.
+pub fn merge_shard_results(
+    shards: &[Vec<BenchmarkResult>],
+    expected: Option<&[String]>,
+) -> anyhow::Result<Vec<BenchmarkResult>> {
+    let mut merged: BTreeMap<&str, (usize, &BenchmarkResult)> = BTreeMap::new();
+    let mut problems = vec![];
+    for (shard, results) in shards.iter().enumerate() {
+        for result in results {
+            if let Some(&(previous, _)) = merged.get(result.name.as_str()) {
+                problems.push(format!(
+                    "`{}` has results in shards {previous} and {shard}",
+                    result.name
+                ));
+                continue;
+            }
+            merged.insert(&result.name, (shard, result));
+        }
+    }
+
+    if let Some(expected) = expected {
+        let expected: BTreeSet<&str> = expected.iter().map(|name| name.as_str()).collect();
+        for name in &expected {
+            if !merged.contains_key(name) {
+                problems.push(format!("`{name}` is missing from all shards"));
+            }
+        }
+        for (name, (shard, _)) in &merged {
+            if !expected.contains(name) {
+                problems.push(format!("`{name}` in shard {shard} is not expected"));
+            }
+        }
+    }
+
+    if !problems.is_empty() {
+        return Err(anyhow::anyhow!(
+            "Cannot merge results of {} shard(s):\n{}",
+            shards.len(),
+            problems.join("\n")
+        ));
+    }
+    Ok(merged
+        .into_values()
+        .map(|(_, result)| result.clone())
+        .collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use benchlib::comm::messages::{BenchmarkResult, BenchmarkStats};
+
+    use super::merge_shard_results;
+
+    fn result(name: &str) -> BenchmarkResult {
+        BenchmarkResult {
+            name: name.to_string(),
+            stats: vec![BenchmarkStats {
+                cycles: None,
+                instructions: None,
+                branch_misses: None,
+                cache_misses: None,
+                cache_references: None,
+                wall_time: Duration::from_nanos(1),
+                max_rss: None,
+            }],
+            checksum: None,
+        }
+    }
+
+    #[test]
+    fn test_merge_shard_results() {
+        let expected: Vec<String> = ["hashmap", "nbody", "regex", "sort"]
+            .iter()
+            .map(|name| name.to_string())
+            .collect();
+        let shards = vec![
+            vec![result("sort"), result("hashmap")],
+            vec![result("regex"), result("nbody")],
+        ];
+        let merged = merge_shard_results(&shards, Some(&expected)).unwrap();
+        let names: Vec<&str> = merged.iter().map(|result| result.name.as_str()).collect();
+        assert_eq!(names, ["hashmap", "nbody", "regex", "sort"]);
+
+        // The second shard has not finished the `nbody` benchmark.
+        let shards = vec![
+            vec![result("sort"), result("hashmap")],
+            vec![result("regex")],
+        ];
+        assert_eq!(merge_shard_results(&shards, None).unwrap().len(), 3);
+        let error = merge_shard_results(&shards, Some(&expected))
+            .unwrap_err()
+            .to_string();
+        assert!(
+            error.contains("`nbody` is missing from all shards"),
+            "{error}"
+        );
+
+        let shards = vec![
+            vec![result("sort"), result("hashmap")],
+            vec![result("sort"), result("unknown")],
+        ];
+        let error = merge_shard_results(&shards, Some(&expected))
+            .unwrap_err()
+            .to_string();
+        assert!(
+            error.contains("`sort` has results in shards 0 and 1"),
+            "{error}"
+        );
+        assert!(
+            error.contains("`unknown` in shard 1 is not expected"),
+            "{error}"
+        );
+    }
+}
@@ -0,0 +1,149 @@
+use std::fmt;
+use std::io::Write;
+use std::net::TcpStream;
+use std::path::PathBuf;
+use std::str::FromStr;
+use std::time::Duration;
+
+/// How many times is sending a single message attempted before it is dropped.
+const MAX_SEND_ATTEMPTS: u32 = 3;
+
+/// Delay before reconnecting after a failed attempt.
+const RECONNECT_DELAY: Duration = Duration::from_millis(100);
+
+/// Address to which the results of a run are streamed, either `unix:<path>` for a Unix socket,
+/// or `[tcp:]<host>:<port>` for a TCP connection.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum StreamAddress {
+    Tcp(String),
+    Unix(PathBuf),
+}
+
+impl fmt::Display for StreamAddress {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            StreamAddress::Tcp(address) => write!(f, "tcp:{address}"),
+            StreamAddress::Unix(path) => write!(f, "unix:{}", path.display()),
+        }
+    }
+}
+
+impl serde::Serialize for StreamAddress {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.collect_str(self)
+    }
+}
+
+impl FromStr for StreamAddress {
+    type Err = anyhow::Error;
+
+    fn from_str(address: &str) -> Result<Self, Self::Err> {
+        if let Some(path) = address.strip_prefix("unix:") {
+            if path.is_empty() {
+                return Err(anyhow::anyhow!("Missing socket path in `{address}`"));
+            }
+            return Ok(StreamAddress::Unix(path.into()));
+        }
+        let address = address.strip_prefix("tcp:").unwrap_or(address);
+        if !address.contains(':') {
+            return Err(anyhow::anyhow!(
+                "Invalid stream address `{address}`, expected `unix:<path>` or `<host>:<port>`"
+            ));
+        }
+        Ok(StreamAddress::Tcp(address.to_string()))
+    }
+}
+
+/// Sends messages as JSON lines to a socket. The connection is opened lazily and reopened when
+/// sending fails, so that a restarted consumer (e.g. a dashboard) still receives the following
+/// messages. Messages that cannot be delivered are dropped with a warning, they never fail the
+/// run.
+pub struct ResultStream {
+    address: StreamAddress,
+    connection: Option<Box<dyn Write + Send>>,
+}
+
+impl ResultStream {
+    pub fn new(address: StreamAddress) -> Self {
+        Self {
+            address,
+            connection: None,
+        }
+    }
+
+    pub fn send<T: serde::Serialize>(&mut self, message: &T) {
+        let mut line = match serde_json::to_vec(message) {
+            Ok(line) => line,
+            Err(error) => {
+                log::warn!("Cannot serialize message for {}: {error:?}", self.address);
+                return;
+            }
+        };
+        line.push(b'\n');
+
+        let mut last_error = None;
+        for attempt in 0..MAX_SEND_ATTEMPTS {
+            if attempt > 0 {
+                std::thread::sleep(RECONNECT_DELAY);
+            }
+            match self.write_line(&line) {
+                Ok(()) => return,
+                Err(error) => {
+                    // The connection is broken, open a new one on the next attempt.
+                    self.connection = None;
+                    last_error = Some(error);
+                }
+            }
+        }
+        log::warn!(
+            "Cannot send result to {}, dropping it: {:?}",
+            self.address,
+            last_error.unwrap()
+        );
+    }
+
+    fn write_line(&mut self, line: &[u8]) -> std::io::Result<()> {
+        let connection = match self.connection {
+            Some(ref mut connection) => connection,
+            None => self.connection.insert(connect(&self.address)?),
+        };
+        connection.write_all(line)?;
+        connection.flush()
+    }
+}
+
+fn connect(address: &StreamAddress) -> std::io::Result<Box<dyn Write + Send>> {
+    match address {
+        StreamAddress::Tcp(address) => Ok(Box::new(TcpStream::connect(address)?)),
+        #[cfg(unix)]
+        StreamAddress::Unix(path) => Ok(Box::new(std::os::unix::net::UnixStream::connect(path)?)),
+        #[cfg(not(unix))]
+        StreamAddress::Unix(_) => Err(std::io::Error::new(
+            std::io::ErrorKind::Unsupported,
+            "Unix sockets are not supported on this platform",
+        )),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::StreamAddress;
+
+    #[test]
+    fn test_parse_stream_address() {
+        assert_eq!(
+            "127.0.0.1:9000".parse::<StreamAddress>().unwrap(),
+            StreamAddress::Tcp("127.0.0.1:9000".to_string())
+        );
+        assert_eq!(
+            "tcp:localhost:9000".parse::<StreamAddress>().unwrap(),
+            StreamAddress::Tcp("localhost:9000".to_string())
+        );
+        assert_eq!(
+            "unix:/tmp/results.sock".parse::<StreamAddress>().unwrap(),
+            StreamAddress::Unix("/tmp/results.sock".into())
+        );
+        assert!("unix:".parse::<StreamAddress>().is_err());
+        assert!("localhost".parse::<StreamAddress>().is_err());
+    }
+}
@@ -0,0 +1,201 @@
+use std::fmt;
+use std::iter::Peekable;
+use std::str::{CharIndices, FromStr};
+
+/// A boolean expression over tags of runtime benchmark groups, e.g. `simd && !slow`.
+///
+/// Supports tag names, `!` (negation), `&&` (conjunction), `||` (disjunction) and parentheses.
+/// `!` binds the strongest, followed by `&&` and then `||`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum TagExpr {
+    Tag(String),
+    Not(Box<TagExpr>),
+    And(Box<TagExpr>, Box<TagExpr>),
+    Or(Box<TagExpr>, Box<TagExpr>),
+}
+
+impl TagExpr {
+    /// Evaluates the expression for a benchmark with the given tags.
+    pub fn matches<S: AsRef<str>>(&self, tags: &[S]) -> bool {
+        match self {
+            TagExpr::Tag(tag) => tags.iter().any(|t| t.as_ref() == tag),
+            TagExpr::Not(expr) => !expr.matches(tags),
+            TagExpr::And(lhs, rhs) => lhs.matches(tags) && rhs.matches(tags),
+            TagExpr::Or(lhs, rhs) => lhs.matches(tags) || rhs.matches(tags),
+        }
+    }
+}
+
+/// Prints the expression fully parenthesized, so that it can be parsed back.
+impl fmt::Display for TagExpr {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            TagExpr::Tag(tag) => write!(f, "{tag}"),
+            TagExpr::Not(expr) => write!(f, "!{expr}"),
+            TagExpr::And(lhs, rhs) => write!(f, "({lhs} && {rhs})"),
+            TagExpr::Or(lhs, rhs) => write!(f, "({lhs} || {rhs})"),
+        }
+    }
+}
+
+impl serde::Serialize for TagExpr {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.collect_str(self)
+    }
+}
+
+impl FromStr for TagExpr {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut parser = Parser {
+            input: s,
+            chars: s.char_indices().peekable(),
+        };
+        let expr = parser.parse_or()?;
+        parser.skip_whitespace();
+        match parser.chars.peek() {
+            None => Ok(expr),
+            Some(&(index, c)) => Err(parser.error(index, &format!("unexpected `{c}`"))),
+        }
+    }
+}
+
+/// Recursive descent parser of tag expressions.
+struct Parser<'a> {
+    input: &'a str,
+    chars: Peekable<CharIndices<'a>>,
+}
+
+impl Parser<'_> {
+    fn parse_or(&mut self) -> anyhow::Result<TagExpr> {
+        let mut expr = self.parse_and()?;
+        while self.eat_operator('|')? {
+            expr = TagExpr::Or(Box::new(expr), Box::new(self.parse_and()?));
+        }
+        Ok(expr)
+    }
+
+    fn parse_and(&mut self) -> anyhow::Result<TagExpr> {
+        let mut expr = self.parse_unary()?;
+        while self.eat_operator('&')? {
+            expr = TagExpr::And(Box::new(expr), Box::new(self.parse_unary()?));
+        }
+        Ok(expr)
+    }
+
+    fn parse_unary(&mut self) -> anyhow::Result<TagExpr> {
+        self.skip_whitespace();
+        match self.chars.peek().copied() {
+            Some((_, '!')) => {
+                self.chars.next();
+                Ok(TagExpr::Not(Box::new(self.parse_unary()?)))
+            }
+            Some((index, '(')) => {
+                self.chars.next();
+                let expr = self.parse_or()?;
+                self.skip_whitespace();
+                match self.chars.next() {
+                    Some((_, ')')) => Ok(expr),
+                    _ => Err(self.error(index, "unclosed `(`")),
+                }
+            }
+            Some((start, c)) if is_tag_char(c) => {
+                let mut end = start;
+                while let Some(&(index, c)) = self.chars.peek() {
+                    if !is_tag_char(c) {
+                        break;
+                    }
+                    end = index + c.len_utf8();
+                    self.chars.next();
+                }
+                Ok(TagExpr::Tag(self.input[start..end].to_string()))
+            }
+            Some((index, c)) => Err(self.error(index, &format!("expected a tag, found `{c}`"))),
+            None => Err(self.error(self.input.len(), "expected a tag, found end of input")),
+        }
+    }
+
+    /// Consumes a binary operator written as a doubled `op` character (`&&` or `||`), if it is
+    /// next in the input.
+    fn eat_operator(&mut self, op: char) -> anyhow::Result<bool> {
+        self.skip_whitespace();
+        match self.chars.peek().copied() {
+            Some((index, c)) if c == op => {
+                self.chars.next();
+                match self.chars.next() {
+                    Some((_, c)) if c == op => Ok(true),
+                    _ => Err(self.error(index, &format!("expected `{op}{op}`"))),
+                }
+            }
+            _ => Ok(false),
+        }
+    }
+
+    fn skip_whitespace(&mut self) {
+        while self.chars.next_if(|(_, c)| c.is_whitespace()).is_some() {}
+    }
+
+    fn error(&self, index: usize, message: &str) -> anyhow::Error {
+        anyhow::anyhow!(
+            "Invalid tag expression `{}`: {message} at position {index}",
+            self.input
+        )
+    }
+}
+
+fn is_tag_char(c: char) -> bool {
+    c.is_alphanumeric() || matches!(c, '-' | '_' | '.')
+}
+
+#[cfg(test)]
+mod tests {
+    use super::TagExpr;
+
+    fn matches(expr: &str, tags: &[&str]) -> bool {
+        expr.parse::<TagExpr>().unwrap().matches(tags)
+    }
+
+    #[test]
+    fn test_tag_expressions() {
+        assert!(matches("simd", &["simd", "slow"]));
+        assert!(!matches("simd", &["slow"]));
+        assert!(matches("simd && !slow", &["simd"]));
+        assert!(!matches("simd && !slow", &["simd", "slow"]));
+        assert!(matches("!!simd", &["simd"]));
+        assert!(matches("a || b", &["b"]));
+        assert!(!matches("a || b", &[]));
+    }
+
+    #[test]
+    fn test_tag_expression_precedence() {
+        // `&&` binds stronger than `||`.
+        assert!(matches("a || b && c", &["a"]));
+        assert!(!matches("(a || b) && c", &["a"]));
+        // `!` binds stronger than `&&`.
+        assert!(matches("!a && b", &["b"]));
+        assert!(!matches("!(a && b)", &["a", "b"]));
+
+        assert_eq!(
+            "a || b && !c".parse::<TagExpr>().unwrap().to_string(),
+            "(a || (b && !c))"
+        );
+        let expr: TagExpr = "(a || b) && !(c || d)".parse().unwrap();
+        assert_eq!(expr.to_string().parse::<TagExpr>().unwrap(), expr);
+    }
+
+    #[test]
+    fn test_invalid_tag_expressions() {
+        for (expr, message) in [
+            ("", "expected a tag, found end of input"),
+            ("a &&", "expected a tag, found end of input"),
+            ("a & b", "expected `&&`"),
+            ("(a || b", "unclosed `(`"),
+            ("a b", "unexpected `b`"),
+            ("a || )", "expected a tag, found `)`"),
+        ] {
+            let error = expr.parse::<TagExpr>().unwrap_err().to_string();
+            assert!(error.contains(message), "`{expr}`: {error}");
+        }
+    }
+}
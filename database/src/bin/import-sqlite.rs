@@ -22,7 +22,7 @@ async fn main() {
 
     let cid_name = format!("imported-{}", chrono::Utc::now().timestamp());
     println!("Collection ID for import is {}", cid_name);
-    let cid = postgres_conn.collection_id(&cid_name).await;
+    let cid = postgres_conn.collection_id(&cid_name, None).await;
 
     let mut benchmarks = HashSet::new();
     let benchmark_data: HashMap<String, CompileBenchmark> = sqlite_conn
@@ -139,16 +139,20 @@ impl Table for Collection {
     }
 
     fn postgres_select_statement(&self, _since_weeks_ago: Option<u32>) -> String {
-        "select id, perf_commit from ".to_string() + self.name()
+        "select id, perf_commit, run_id from ".to_string() + self.name()
     }
 
     fn sqlite_insert_statement(&self) -> &'static str {
-        "insert into collection (id, perf_commit) VALUES (?, ?)"
+        "insert into collection (id, perf_commit, run_id) VALUES (?, ?, ?)"
     }
 
     fn sqlite_execute_insert(&self, statement: &mut rusqlite::Statement, row: tokio_postgres::Row) {
         statement
-            .execute(params![row.get::<_, i32>(0), row.get::<_, Option<&str>>(1)])
+            .execute(params![
+                row.get::<_, i32>(0),
+                row.get::<_, Option<&str>>(1),
+                row.get::<_, Option<&str>>(2),
+            ])
             .unwrap();
     }
 }
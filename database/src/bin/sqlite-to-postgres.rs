@@ -164,6 +164,7 @@ struct Collection;
 struct CollectionRow<'a> {
     id: i32,
     perf_commit: Nullable<&'a str>,
+    run_id: Nullable<&'a str>,
 }
 
 impl Table for Collection {
@@ -172,11 +173,11 @@ impl Table for Collection {
     }
 
     fn sqlite_attributes() -> &'static str {
-        "id, perf_commit"
+        "id, perf_commit, run_id"
     }
 
     fn postgres_attributes() -> &'static str {
-        "id, perf_commit"
+        "id, perf_commit, run_id"
     }
 
     fn postgres_generated_id_attribute() -> Option<&'static str> {
@@ -188,6 +189,7 @@ impl Table for Collection {
             .serialize(CollectionRow {
                 id: row.get(0).unwrap(),
                 perf_commit: row.get_ref(1).unwrap().try_into().unwrap(),
+                run_id: row.get_ref(2).unwrap().try_into().unwrap(),
             })
             .unwrap();
     }
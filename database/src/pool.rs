@@ -36,7 +36,8 @@ pub trait Connection: Send + Sync {
     async fn record_duration(&self, artifact: ArtifactIdNumber, duration: Duration);
 
     /// One collection corresponds to all gathered metrics for a single iteration of a test case.
-    async fn collection_id(&self, version: &str) -> CollectionId;
+    /// `run_id` optionally identifies the collector run that gathered the metrics.
+    async fn collection_id(&self, version: &str, run_id: Option<&str>) -> CollectionId;
     async fn artifact_id(&self, artifact: &ArtifactId) -> ArtifactIdNumber;
 
     #[allow(clippy::too_many_arguments)]
@@ -324,6 +324,7 @@ static MIGRATIONS: &[&str] = &[
     CREATE UNIQUE INDEX collector_config_target_bench_active_uniq ON collector_config
         (target, benchmark_set, is_active) WHERE is_active = TRUE;
     "#,
+    r#"alter table collection add column run_id text;"#,
 ];
 
 #[async_trait::async_trait]
@@ -514,7 +515,7 @@ impl PostgresConnection {
                 get_error: conn.prepare("select benchmark, error from error where aid = $1").await.unwrap(),
                 insert_pstat_series: conn.prepare("insert into pstat_series (crate, profile, scenario, backend, target, metric) VALUES ($1, $2, $3, $4, $5, $6) ON CONFLICT DO NOTHING RETURNING id").await.unwrap(),
                 select_pstat_series: conn.prepare("select id from pstat_series where crate = $1 and profile = $2 and scenario = $3 and backend = $4 and target = $5 and metric = $6").await.unwrap(),
-                collection_id: conn.prepare("insert into collection (perf_commit) VALUES ($1) returning id").await.unwrap(),
+                collection_id: conn.prepare("insert into collection (perf_commit, run_id) VALUES ($1, $2) returning id").await.unwrap(),
                 record_duration: conn.prepare("
                     insert into artifact_collection_duration (
                         aid,
@@ -863,10 +864,13 @@ where
             backends: row.get(7),
         })
     }
-    async fn collection_id(&self, version: &str) -> CollectionId {
+    async fn collection_id(&self, version: &str, run_id: Option<&str>) -> CollectionId {
         CollectionId(
             self.conn()
-                .query_one(&self.statements().collection_id, &[&version.trim()])
+                .query_one(
+                    &self.statements().collection_id,
+                    &[&version.trim(), &run_id],
+                )
                 .await
                 .unwrap()
                 .get(0),
@@ -405,6 +405,7 @@ static MIGRATIONS: &[Migration] = &[
         alter table pstat_series_with_target rename to pstat_series;
     "#,
     ),
+    Migration::new("alter table collection add column run_id text"),
 ];
 
 #[async_trait::async_trait]
@@ -644,11 +645,11 @@ impl Connection for SqliteConnection {
             .execute(params![artifact.0, duration.as_secs() as i64])
             .unwrap();
     }
-    async fn collection_id(&self, version: &str) -> CollectionId {
+    async fn collection_id(&self, version: &str, run_id: Option<&str>) -> CollectionId {
         let raw = self.raw_ref();
         raw.execute(
-            "insert into collection (perf_commit) values (?)",
-            params![version],
+            "insert into collection (perf_commit, run_id) values (?, ?)",
+            params![version, run_id],
         )
         .unwrap();
         CollectionId(
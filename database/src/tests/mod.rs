@@ -1,11 +1,12 @@
 #![allow(dead_code)]
 
 use std::future::Future;
+use std::path::Path;
 use tokio_postgres::config::Host;
 use tokio_postgres::Config;
 
 use crate::pool::postgres::make_client;
-use crate::Pool;
+use crate::{ArtifactIdNumber, Pool};
 
 enum TestDb {
     Postgres {
@@ -152,3 +153,23 @@ where
     let ctx = f(ctx).await.expect("SQLite test failed");
     ctx.finish().await;
 }
+
+/// Returns the identifiers of the collector runs that recorded runtime statistics of the given
+/// artifact into the SQLite database at `path`, sorted alphabetically.
+pub fn sqlite_runtime_run_ids(path: &Path, artifact: ArtifactIdNumber) -> Vec<String> {
+    let conn = rusqlite::Connection::open(path).expect("Cannot open SQLite database");
+    let mut query = conn
+        .prepare(
+            "select distinct collection.run_id from runtime_pstat \
+            join collection on collection.id = runtime_pstat.cid \
+            where runtime_pstat.aid = ? and collection.run_id is not null \
+            order by collection.run_id",
+        )
+        .unwrap();
+    let run_ids = query
+        .query_map([artifact.0], |row| row.get(0))
+        .unwrap()
+        .collect::<Result<_, _>>()
+        .unwrap();
+    run_ids
+}